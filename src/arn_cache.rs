@@ -0,0 +1,90 @@
+use crate::cache;
+use anyhow::Result;
+use query_rds_data::MyArns;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+use tracing::{info, warn};
+
+/// The ARNs discovery found for a target.
+#[derive(Deserialize, Serialize)]
+struct CachedArns {
+    resource_arn: String,
+    secret_arn: String,
+    engine: Option<String>,
+}
+
+/// The ARNs of every target discovered, keyed by how it was asked for,
+/// in `arns.json` in the cache directory, so that --offline-discovery can
+/// find them again without calling RDS or Secrets Manager.
+pub struct ArnCache {
+    path: PathBuf,
+}
+
+/// What a target is asked for by: the profile, region, cluster and user.
+pub fn key(parts: &[Option<&str>]) -> String {
+    serde_json::json!(parts).to_string()
+}
+
+impl ArnCache {
+    pub fn new() -> Result<ArnCache> {
+        Ok(ArnCache {
+            path: cache::dir()?.join("arns.json"),
+        })
+    }
+
+    fn entries(&self) -> Result<BTreeMap<String, CachedArns>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The cached ARNs for the key. Problems reading the cache are logged,
+    /// and treated as a miss.
+    pub fn get(&self, key: &str) -> Option<MyArns> {
+        match self.entries() {
+            Ok(mut entries) => entries.remove(key).map(|cached| {
+                info!("Using cached ARNs for {}", key);
+                MyArns {
+                    aws_secret_store_arn: cached.secret_arn,
+                    db_cluster_or_instance_arn: cached.resource_arn,
+                    engine: cached.engine,
+                }
+            }),
+            Err(e) => {
+                warn!("Ignoring cached ARNs in {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Keep the ARNs for the key. Failing to do so is logged.
+    pub fn put(&self, key: &str, arns: &MyArns) {
+        let cached = CachedArns {
+            resource_arn: arns.db_cluster_or_instance_arn.clone(),
+            secret_arn: arns.aws_secret_store_arn.clone(),
+            engine: arns.engine.clone(),
+        };
+        // Write then rename, so a concurrent reader never sees half a file.
+        let temporary = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        let written = self
+            .entries()
+            .and_then(|mut entries| {
+                entries.insert(key.to_owned(), cached);
+                Ok(serde_json::to_vec_pretty(&entries)?)
+            })
+            .and_then(|bytes| {
+                if let Some(dir) = self.path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                Ok(fs::write(&temporary, bytes)?)
+            })
+            .and_then(|()| Ok(fs::rename(&temporary, &self.path)?));
+        if let Err(e) = written {
+            warn!("Failed to cache ARNs in {}: {}", self.path.display(), e);
+        }
+    }
+}
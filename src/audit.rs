@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Audit settings from the `[audit]` section of the configuration file.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AuditConfig {
+    /// Append a JSON line per executed statement to this file.
+    pub log: Option<PathBuf>,
+
+    /// Record a SHA-256 of the SQL instead of the SQL itself.
+    #[serde(default)]
+    pub hash_sql: bool,
+}
+
+/// One line of the audit log.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub profile: Option<String>,
+    pub region: Option<String>,
+    pub cluster: String,
+    pub secret: String,
+    /// Of the cluster, so that a rerun parses its SQL the same way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sql_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    pub records_updated: Option<i64>,
+    pub rows: Option<usize>,
+    pub duration_seconds: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Every entry in the audit log, oldest first.
+pub fn read(path: &Path) -> Result<Vec<AuditEntry>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read audit log {}: {}", path.display(), e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_index, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                anyhow!(
+                    "Failed to parse audit log {} line {}: {}",
+                    path.display(),
+                    index + 1,
+                    e
+                )
+            })
+        })
+        .collect()
+}
+
+pub fn now_timestamp() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// An append-only JSON lines file of executed statements.
+pub struct AuditLog {
+    file: File,
+    hash_sql: bool,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path, hash_sql: bool) -> Result<AuditLog> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| anyhow!("Failed to open audit log {}: {}", path.display(), e))?;
+        Ok(AuditLog { file, hash_sql })
+    }
+
+    pub fn record(&mut self, mut entry: AuditEntry) -> Result<()> {
+        if self.hash_sql {
+            entry.sql_sha256 = entry.sql.take().map(|sql| sha256_hex(sql.as_bytes()));
+            // Parameter values are at least as sensitive as the SQL.
+            entry.params.clear();
+        }
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .map_err(|e| anyhow!("Failed to write audit log: {}", e))
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    guard::Guard,
+    import::{json_field, open_input, parse_rate_limit, ImportSummary, Loader, RateLimit},
+    merge_params, Param, Request,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::types::SqlParameter;
+use clap::{ArgGroup, Args};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Run one statement once for each of many parameter sets.
+///
+/// The parameter sets are sent in batches through
+/// `batch_execute_statement`. Any --param is added to every set, unless
+/// the set has its own value for it.
+#[derive(Args, Clone, Debug)]
+#[command(group(ArgGroup::new("parameter_sets").required(true)))]
+pub struct BatchExecuteArgs {
+    /// Read parameter sets from this CSV file, or `-` for standard input.
+    /// The header line names the parameters, optionally typed as
+    /// `name:type`, and each row is one parameter set.
+    #[clap(group = "parameter_sets", long, value_name = "FILE")]
+    params_csv: Option<PathBuf>,
+
+    /// Read parameter sets from this file of JSON objects, one per line, or
+    /// `-` for standard input. Keys name the parameters, and values are
+    /// sent as the matching type. Sets are sent as each batch fills, so
+    /// this can be fed from a pipeline.
+    #[clap(group = "parameter_sets", long, value_name = "FILE")]
+    params_ndjson: Option<PathBuf>,
+
+    /// Most parameter sets to send per batch. Batches are also split to
+    /// stay under the Data API request size limit.
+    #[clap(default_value_t = 1000, long, value_parser = clap::value_parser!(u32).range(1..))]
+    batch_size: u32,
+
+    /// How many times to retry a failed batch before rejecting it.
+    #[clap(default_value_t = 2, long)]
+    retries: u32,
+
+    /// Send no faster than this, such as `500rows/s` or `2batches/s`.
+    /// A bare number is parameter sets per second.
+    #[clap(long, value_name = "RATE", value_parser = parse_rate_limit)]
+    rate_limit: Option<RateLimit>,
+
+    /// SQL statement, referring to parameters as `:name`.
+    pub sql: String,
+}
+
+/// Parse a CSV header name, `name` or `name:type`, into an empty parameter.
+fn header_param(header: &str) -> Result<Param> {
+    format!("{}=", header)
+        .parse()
+        .map_err(|e| anyhow!("CSV header \"{}\": {}", header, e))
+}
+
+/// Every parameter set from the CSV file. Rows are numbered from 1, not
+/// counting the header line.
+async fn load_csv(loader: &mut Loader<'_>, request: &Request, path: &Path) -> Result<()> {
+    let mut reader = csv::Reader::from_reader(open_input(path)?);
+    let headers = reader
+        .headers()
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?
+        .iter()
+        .map(header_param)
+        .collect::<Result<Vec<_>>>()?;
+    if headers.is_empty() {
+        return Err(anyhow!("No parameters found in {}", path.display()));
+    }
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let row_params: Vec<Param> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, value)| Param {
+                value: value.to_owned(),
+                ..header.clone()
+            })
+            .collect();
+        let parameter_set = merge_params(&request.params, &row_params)
+            .iter()
+            .map(Param::to_sql_parameter)
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| anyhow!("Row {}: {}", index + 1, e))?;
+        loader.push(parameter_set).await?;
+    }
+    Ok(())
+}
+
+/// Every parameter set from the NDJSON file, read as it arrives.
+async fn load_ndjson(loader: &mut Loader<'_>, request: &Request, path: &Path) -> Result<()> {
+    let defaults = request
+        .params
+        .iter()
+        .map(Param::to_sql_parameter)
+        .collect::<Result<Vec<_>>>()?;
+    let values = serde_json::Deserializer::from_reader(open_input(path)?).into_iter::<Value>();
+    for (index, value) in values.enumerate() {
+        let value = value.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let Value::Object(object) = value else {
+            return Err(anyhow!("Row {} is not a JSON object", index + 1));
+        };
+        let mut parameter_set: Vec<SqlParameter> = defaults
+            .iter()
+            .filter(|default| {
+                default
+                    .name
+                    .as_ref()
+                    .is_none_or(|name| !object.contains_key(name))
+            })
+            .cloned()
+            .collect();
+        parameter_set.extend(object.into_iter().map(|(name, value)| {
+            SqlParameter::builder()
+                .name(name)
+                .value(json_field(value))
+                .build()
+        }));
+        loader.push(parameter_set).await?;
+    }
+    Ok(())
+}
+
+/// Execute the statement for every parameter set read. The statement is
+/// checked, and confirmed if destructive, before any set is read, since
+/// they may be streaming in.
+pub async fn batch_execute(
+    guard: &Guard,
+    config: &SdkConfig,
+    request: &Request,
+    batch_args: &BatchExecuteArgs,
+) -> Result<ImportSummary> {
+    info!("{}", request.sql);
+    guard.check(&[(config, request)], None).await?;
+    let mut loader = Loader::new(
+        guard,
+        config,
+        &request.target,
+        request.sql.clone(),
+        batch_args.batch_size,
+        batch_args.retries,
+        batch_args.rate_limit,
+    );
+    match (&batch_args.params_csv, &batch_args.params_ndjson) {
+        (Some(path), _) => load_csv(&mut loader, request, path).await?,
+        (None, Some(path)) => load_ndjson(&mut loader, request, path).await?,
+        (None, None) => return Err(anyhow!("No parameter sets given")),
+    }
+    loader.finish().await
+}
@@ -0,0 +1,46 @@
+use crate::{
+    format_header, format_value, output_pattern, partition::escape, transform::column_indexes,
+};
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use std::{collections::HashMap, io::Write, path::Path};
+
+/// Write each blob to its own file, under `dir` in a directory named for
+/// its column, and named by the row's value of the `key` column, or else
+/// the row number counting from 1. The blob's value becomes the file's
+/// path.
+pub fn extract(output: &mut ExecuteStatementOutput, dir: &Path, key: Option<&str>) -> Result<()> {
+    let header: Vec<String> = format_header(output).map(escape).collect();
+    let key_index = match key {
+        Some(key) => Some(column_indexes(output, &[key])?[0]),
+        None => None,
+    };
+    // Which row first used each file name.
+    let mut rows: HashMap<String, usize> = HashMap::new();
+    for (index, record) in output.records.iter_mut().flatten().enumerate() {
+        let row = index + 1;
+        let name = match key_index.and_then(|key_index| record.get(key_index)) {
+            Some(field) => escape(&format_value(field)),
+            None => row.to_string(),
+        };
+        for (field, column) in record.iter_mut().zip(&header) {
+            let Field::BlobValue(blob) = &*field else {
+                continue;
+            };
+            let path = dir.join(column).join(&name);
+            if let Some(earlier) = rows.insert(path.display().to_string(), row) {
+                return Err(anyhow!(
+                    "Rows {} and {} would both write {}, give a --blob-key column of unique values",
+                    earlier,
+                    row,
+                    path.display()
+                ));
+            }
+            output_pattern::create(&path)?
+                .write_all(blob.as_ref())
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+            *field = Field::StringValue(path.display().to_string());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,300 @@
+use crate::{
+    audit::sha256_hex, driver::Driver, policy::reads_only, transaction::Isolation, Request,
+};
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::{
+    operation::execute_statement::ExecuteStatementOutput,
+    types::{ArrayValue, ColumnMetadata, Field},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+/// Parse a duration such as `90`, `90s`, `15m`, `2h` or `1d`.
+/// A bare number is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => 0,
+    };
+    match number.parse::<u64>() {
+        Ok(number) if seconds > 0 => Ok(Duration::from_secs(number * seconds)),
+        _ => Err(anyhow!(
+            "Duration \"{}\" is not a number of seconds, or a number followed by one of {:?}",
+            s,
+            ["s", "m", "h", "d"],
+        )),
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum CachedArray {
+    Booleans(Vec<Option<bool>>),
+    Longs(Vec<Option<i64>>),
+    Doubles(Vec<Option<f64>>),
+    Strings(Vec<Option<String>>),
+    Arrays(Vec<Option<CachedArray>>),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum CachedField {
+    Null,
+    Boolean(bool),
+    Long(i64),
+    Double(f64),
+    String(String),
+    Blob(Vec<u8>),
+    Array(CachedArray),
+}
+
+#[derive(Deserialize, Serialize)]
+struct CachedColumn {
+    name: Option<String>,
+    label: Option<String>,
+    type_name: Option<String>,
+    nullable: i32,
+    precision: i32,
+    scale: i32,
+}
+
+/// What we keep of an `ExecuteStatementOutput`, which is not serializable.
+#[derive(Deserialize, Serialize)]
+struct CachedResult {
+    columns: Vec<CachedColumn>,
+    records: Vec<Vec<CachedField>>,
+    number_of_records_updated: i64,
+}
+
+fn cached_array(array: &ArrayValue) -> CachedArray {
+    match array {
+        ArrayValue::BooleanValues(values) => CachedArray::Booleans(values.clone()),
+        ArrayValue::LongValues(values) => CachedArray::Longs(values.clone()),
+        ArrayValue::DoubleValues(values) => CachedArray::Doubles(values.clone()),
+        ArrayValue::ArrayValues(values) => CachedArray::Arrays(
+            values
+                .iter()
+                .map(|value| value.as_ref().map(cached_array))
+                .collect(),
+        ),
+        // Strings, and any type the SDK learns about later, as strings.
+        ArrayValue::StringValues(values) => CachedArray::Strings(values.clone()),
+        _ => CachedArray::Strings(Vec::new()),
+    }
+}
+
+fn array_value(array: CachedArray) -> ArrayValue {
+    match array {
+        CachedArray::Booleans(values) => ArrayValue::BooleanValues(values),
+        CachedArray::Longs(values) => ArrayValue::LongValues(values),
+        CachedArray::Doubles(values) => ArrayValue::DoubleValues(values),
+        CachedArray::Strings(values) => ArrayValue::StringValues(values),
+        CachedArray::Arrays(values) => ArrayValue::ArrayValues(
+            values
+                .into_iter()
+                .map(|value| value.map(array_value))
+                .collect(),
+        ),
+    }
+}
+
+fn cached_field(field: &Field) -> CachedField {
+    match field {
+        Field::BooleanValue(value) => CachedField::Boolean(*value),
+        Field::LongValue(value) => CachedField::Long(*value),
+        Field::DoubleValue(value) => CachedField::Double(*value),
+        Field::StringValue(value) => CachedField::String(value.clone()),
+        Field::BlobValue(value) => CachedField::Blob(value.clone().into_inner()),
+        Field::ArrayValue(value) => CachedField::Array(cached_array(value)),
+        _ => CachedField::Null,
+    }
+}
+
+fn field(cached: CachedField) -> Field {
+    match cached {
+        CachedField::Null => Field::IsNull(true),
+        CachedField::Boolean(value) => Field::BooleanValue(value),
+        CachedField::Long(value) => Field::LongValue(value),
+        CachedField::Double(value) => Field::DoubleValue(value),
+        CachedField::String(value) => Field::StringValue(value),
+        CachedField::Blob(value) => Field::BlobValue(value.into()),
+        CachedField::Array(value) => Field::ArrayValue(array_value(value)),
+    }
+}
+
+impl From<&ExecuteStatementOutput> for CachedResult {
+    fn from(output: &ExecuteStatementOutput) -> Self {
+        CachedResult {
+            columns: output
+                .column_metadata
+                .iter()
+                .flatten()
+                .map(|column| CachedColumn {
+                    name: column.name.clone(),
+                    label: column.label.clone(),
+                    type_name: column.type_name.clone(),
+                    nullable: column.nullable,
+                    precision: column.precision,
+                    scale: column.scale,
+                })
+                .collect(),
+            records: output
+                .records
+                .iter()
+                .flatten()
+                .map(|record| record.iter().map(cached_field).collect())
+                .collect(),
+            number_of_records_updated: output.number_of_records_updated,
+        }
+    }
+}
+
+impl From<CachedResult> for ExecuteStatementOutput {
+    fn from(cached: CachedResult) -> Self {
+        let columns = cached
+            .columns
+            .into_iter()
+            .map(|column| {
+                ColumnMetadata::builder()
+                    .set_name(column.name)
+                    .set_label(column.label)
+                    .set_type_name(column.type_name)
+                    .nullable(column.nullable)
+                    .precision(column.precision)
+                    .scale(column.scale)
+                    .build()
+            })
+            .collect();
+        let records = cached
+            .records
+            .into_iter()
+            .map(|record| record.into_iter().map(field).collect())
+            .collect();
+        ExecuteStatementOutput::builder()
+            .set_column_metadata(Some(columns))
+            .set_records(Some(records))
+            .number_of_records_updated(cached.number_of_records_updated)
+            .build()
+    }
+}
+
+/// query-rds-data in your cache directory, for example ~/.cache.
+pub fn dir() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()
+        .ok_or_else(|| anyhow!("Failed to find a cache directory"))?
+        .join("query-rds-data"))
+}
+
+/// Query results kept on local disk, under query-rds-data in your cache
+/// directory, for example ~/.cache. Only the results of statements that
+/// only read are kept, so statements that change data always run, even
+/// those returning rows.
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    session: serde_json::Value,
+}
+
+impl Cache {
+    /// A cache for statements run with this driver, after these setup
+    /// statements, at this isolation level.
+    pub fn new(
+        ttl: Duration,
+        driver: Driver,
+        init_sql: &[String],
+        isolation: Option<Isolation>,
+    ) -> Result<Cache> {
+        let session = serde_json::json!([
+            format!("{:?}", driver),
+            init_sql,
+            isolation.map(|isolation| format!("{:?}", isolation)),
+        ]);
+        Ok(Cache {
+            dir: dir()?,
+            ttl,
+            session,
+        })
+    }
+
+    /// Results are keyed on everything that could change them.
+    fn path(&self, request: &Request) -> PathBuf {
+        let key = serde_json::json!([
+            request.target.resource_arn,
+            request.target.secret_arn,
+            request.target.database,
+            request.target.schema,
+            self.session,
+            request.sql,
+            request
+                .params
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+        ]);
+        self.dir
+            .join(format!("{}.json", sha256_hex(key.to_string().as_bytes())))
+    }
+
+    /// A fresh cached result for the request. Problems reading the cache
+    /// are logged, and treated as a miss.
+    pub fn get(&self, request: &Request) -> Option<ExecuteStatementOutput> {
+        if !reads_only(request) {
+            return None;
+        }
+        let path = self.path(request);
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age > self.ttl {
+            info!("Cached result {} is stale", path.display());
+            return None;
+        }
+        let cached = fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice::<CachedResult>(&bytes)?));
+        match cached {
+            Ok(cached) => {
+                info!("Using cached result {}", path.display());
+                Some(cached.into())
+            }
+            Err(e) => {
+                warn!("Ignoring cached result {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Keep the result, when the request only reads and the result has
+    /// columns. Failing to do so is logged.
+    pub fn put(&self, request: &Request, output: &ExecuteStatementOutput) {
+        if !reads_only(request) || output.column_metadata.as_ref().is_none_or(Vec::is_empty) {
+            return;
+        }
+        let path = self.path(request);
+        // Write then rename, so a concurrent reader never sees half a file.
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = fs::create_dir_all(&self.dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| Ok(serde_json::to_vec(&CachedResult::from(output))?))
+            .and_then(|bytes| Ok(fs::write(&temporary, bytes)?))
+            .and_then(|()| Ok(fs::rename(&temporary, &path)?));
+        if let Err(e) = written {
+            warn!("Failed to cache result in {}: {}", path.display(), e);
+        }
+    }
+}
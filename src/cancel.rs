@@ -0,0 +1,127 @@
+use crate::{
+    dialect::Dialect,
+    guard::Guard,
+    params::{Param, ParamType},
+    Request, Target,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::types::Field;
+use clap::{ArgGroup, Args};
+use tracing::info;
+
+/// Cancel running statements on the target cluster.
+///
+/// Uses `pg_cancel_backend` on PostgreSQL and `KILL QUERY` on MySQL. Only
+/// the running statement is cancelled; its session stays connected.
+#[derive(Args, Clone, Debug)]
+#[command(group(ArgGroup::new("statements").required(true)))]
+pub struct CancelArgs {
+    /// Cancel the statement running in the session with this process id.
+    #[clap(group = "statements", long)]
+    pid: Option<i64>,
+
+    /// Cancel every statement whose text matches this SQL LIKE pattern,
+    /// for example `%daily_report%`.
+    #[clap(group = "statements", long, value_name = "PATTERN")]
+    query_pattern: Option<String>,
+}
+
+/// The condition on a session, and its parameter, for the arguments.
+fn condition(cancel_args: &CancelArgs, id_column: &str, query_column: &str) -> (String, Param) {
+    match (&cancel_args.pid, &cancel_args.query_pattern) {
+        (Some(pid), _) => (
+            format!("{} = :pid", id_column),
+            Param {
+                name: "pid".to_owned(),
+                param_type: ParamType::Long,
+                value: pid.to_string(),
+            },
+        ),
+        (None, pattern) => (
+            format!("{} LIKE :pattern", query_column),
+            Param {
+                name: "pattern".to_owned(),
+                param_type: ParamType::String,
+                value: pattern.clone().unwrap_or_default(),
+            },
+        ),
+    }
+}
+
+/// The process ids in the first column of the statement's result, and
+/// whether PostgreSQL cancelled them, when it says.
+async fn pids(guard: &Guard, config: &SdkConfig, request: Request) -> Result<Vec<(i64, bool)>> {
+    let output = guard.execute(config, request).await?;
+    Ok(output
+        .records
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|record| match record.as_slice() {
+            [Field::LongValue(pid)] => Some((*pid, true)),
+            [Field::LongValue(pid), Field::BooleanValue(cancelled)] => Some((*pid, *cancelled)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The request finding the matching running statements, other than our
+/// own. On PostgreSQL, it cancels them too.
+pub fn lookup_request(target: &Target, cancel_args: &CancelArgs) -> Request {
+    let (sql, param) = match target.dialect() {
+        Dialect::Postgres => {
+            let (condition, param) = condition(cancel_args, "pid", "query");
+            let sql = format!(
+                "SELECT pid, pg_cancel_backend(pid) FROM pg_stat_activity \
+                 WHERE state = 'active' AND pid <> pg_backend_pid() AND {}",
+                condition
+            );
+            (sql, param)
+        }
+        Dialect::MySql => {
+            let (condition, param) = condition(cancel_args, "id", "info");
+            let sql = format!(
+                "SELECT id FROM information_schema.processlist \
+                 WHERE command = 'Query' AND id <> CONNECTION_ID() AND {}",
+                condition
+            );
+            (sql, param)
+        }
+    };
+    Request {
+        target: target.clone(),
+        params: vec![param],
+        sql,
+    }
+}
+
+/// Cancel the matching statements, returning the process ids of those
+/// cancelled.
+pub async fn cancel(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    cancel_args: &CancelArgs,
+) -> Result<Vec<i64>> {
+    let matched = pids(guard, config, lookup_request(target, cancel_args)).await?;
+    if matched.is_empty() {
+        return Err(anyhow!("No running statement matched"));
+    }
+    let mut cancelled = Vec::new();
+    for (pid, postgres_cancelled) in matched {
+        if target.dialect() == Dialect::MySql {
+            let request = Request {
+                target: target.clone(),
+                params: Vec::new(),
+                // The id is a number, so needs no quoting.
+                sql: format!("KILL QUERY {}", pid),
+            };
+            guard.execute(config, request).await?;
+        } else if !postgres_cancelled {
+            info!("Statement in session {} was not cancelled", pid);
+            continue;
+        }
+        cancelled.push(pid);
+    }
+    Ok(cancelled)
+}
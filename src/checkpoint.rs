@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// How far an import has got, saved after each batch so that an
+/// interrupted import can carry on without inserting rows twice.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Checkpoint {
+    /// The file being imported.
+    pub file: PathBuf,
+    pub table: String,
+    /// Rows inserted so far, counted from the start of the file.
+    pub rows: usize,
+    /// Where the checkpoint is saved.
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    /// A checkpoint for a new import, with no rows inserted yet.
+    pub fn start(path: &Path, file: &Path, table: &str) -> Checkpoint {
+        Checkpoint {
+            file: file.to_owned(),
+            table: table.to_owned(),
+            rows: 0,
+            path: path.to_owned(),
+        }
+    }
+
+    /// The checkpoint saved by an earlier import of the same file into
+    /// the same table.
+    pub fn resume(path: &Path, file: &Path, table: &str) -> Result<Checkpoint> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read checkpoint {}: {}", path.display(), e))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Failed to parse checkpoint {}: {}", path.display(), e))?;
+        if checkpoint.file != file || checkpoint.table != table {
+            return Err(anyhow!(
+                "Checkpoint {} is for importing {} into \"{}\", not {} into \"{}\"",
+                path.display(),
+                checkpoint.file.display(),
+                checkpoint.table,
+                file.display(),
+                table,
+            ));
+        }
+        Ok(Checkpoint {
+            path: path.to_owned(),
+            ..checkpoint
+        })
+    }
+
+    /// Record that the first `rows` rows are inserted.
+    pub fn save(&mut self, rows: usize) -> Result<()> {
+        self.rows = rows;
+        // Write then rename, so a crash never leaves half a checkpoint.
+        let temporary = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, serde_json::to_vec(self)?)
+            .and_then(|()| fs::rename(&temporary, &self.path))
+            .map_err(|e| anyhow!("Failed to save checkpoint {}: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("checkpoint-{}-{}.json", std::process::id(), name))
+    }
+
+    #[test]
+    fn resumes_after_the_saved_rows() {
+        let path = checkpoint_path("resume");
+        let mut checkpoint = Checkpoint::start(&path, Path::new("rows.csv"), "people");
+        assert_eq!(checkpoint.rows, 0);
+        checkpoint.save(500).unwrap();
+        checkpoint.save(1000).unwrap();
+        let resumed = Checkpoint::resume(&path, Path::new("rows.csv"), "people").unwrap();
+        assert_eq!(resumed.rows, 1000);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_checkpoint_for_another_import() {
+        let path = checkpoint_path("other");
+        Checkpoint::start(&path, Path::new("rows.csv"), "people")
+            .save(10)
+            .unwrap();
+        let error = Checkpoint::resume(&path, Path::new("rows.csv"), "pets").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("into \"people\", not rows.csv into \"pets\""));
+        assert!(Checkpoint::resume(&path, Path::new("other.csv"), "people").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_missing_or_broken_checkpoint() {
+        let path = checkpoint_path("broken");
+        let error = Checkpoint::resume(&path, Path::new("rows.csv"), "people").unwrap_err();
+        assert!(error.to_string().starts_with("Failed to read checkpoint"));
+        fs::write(&path, "{").unwrap();
+        let error = Checkpoint::resume(&path, Path::new("rows.csv"), "people").unwrap_err();
+        assert!(error.to_string().starts_with("Failed to parse checkpoint"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{audit::sha256_hex, format_header, format_value};
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// How to hash results.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Algorithm {
+    Sha256,
+}
+
+/// One line of the canonical form: a JSON array of text values, with NULL
+/// as `null`, so that it differs from the text "NULL".
+fn line(values: impl Iterator<Item = Option<String>>) -> String {
+    let values: Vec<Value> = values
+        .map(|value| value.map_or(Value::Null, Value::from))
+        .collect();
+    Value::Array(values).to_string() + "\n"
+}
+
+/// A hash of the column names and rows, in the order returned or, when
+/// `sorted`, regardless of it. Values are hashed as their text, so the
+/// same data hashes the same whichever driver fetched it.
+pub fn checksum(algorithm: Algorithm, output: &ExecuteStatementOutput, sorted: bool) -> String {
+    let header = line(format_header(output).map(|name| Some(name.to_owned())));
+    let mut rows: Vec<String> = output
+        .records
+        .as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .map(|record| {
+            line(record.iter().map(|value| match value {
+                Field::IsNull(_) => None,
+                value => Some(format_value(value)),
+            }))
+        })
+        .collect();
+    if sorted {
+        rows.sort();
+    }
+    let canonical = header + &rows.concat();
+    match algorithm {
+        Algorithm::Sha256 => format!("sha256:{}", sha256_hex(canonical.as_bytes())),
+    }
+}
@@ -0,0 +1,350 @@
+use crate::{error::Error, params::sql_parameters, Request, Target};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rds::types::{DbCluster, DbInstance};
+use aws_sdk_rdsdata::{
+    error::{ProvideErrorMetadata, SdkError},
+    operation::{
+        execute_statement::{ExecuteStatementError, ExecuteStatementOutput},
+        RequestId,
+    },
+    types::{DecimalReturnType, ResultSetOptions, SqlParameter},
+};
+use aws_sdk_secretsmanager::types::SecretListEntry;
+use futures::{future::BoxFuture, prelude::*};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{field::Empty, info, info_span, warn, Instrument, Span};
+
+/// The AWS operations we depend on. [`AwsClient`] calls AWS; tests, and
+/// users of the library, can supply a fake instead.
+pub trait Client: Send + Sync {
+    /// The DB clusters visible to us, following every page.
+    fn describe_db_clusters(&self) -> BoxFuture<'_, Result<Vec<DbCluster>>>;
+
+    /// The DB instance with this identifier.
+    fn describe_db_instance(
+        &self,
+        db_instance_identifier: String,
+    ) -> BoxFuture<'_, Result<DbInstance>>;
+
+    /// The secrets visible to us, following every page.
+    fn list_secrets(&self) -> BoxFuture<'_, Result<Vec<SecretListEntry>>>;
+
+    /// Run one statement, optionally inside a transaction.
+    fn execute_statement(
+        &self,
+        request: Request,
+        transaction_id: Option<String>,
+    ) -> BoxFuture<'_, Result<ExecuteStatementOutput>>;
+
+    /// Begin a transaction, returning its id.
+    fn begin_transaction(&self, target: Target) -> BoxFuture<'_, Result<String>>;
+
+    /// Commit the transaction with this id.
+    fn commit_transaction(
+        &self,
+        target: Target,
+        transaction_id: String,
+    ) -> BoxFuture<'_, Result<()>>;
+
+    /// Roll back the transaction with this id.
+    fn rollback_transaction(
+        &self,
+        target: Target,
+        transaction_id: String,
+    ) -> BoxFuture<'_, Result<()>>;
+}
+
+/// How many times [`AwsClient`] retries a read after a transient failure.
+pub const DEFAULT_TRANSIENT_RETRIES: u32 = 3;
+
+/// Bad requests that are really the database going away for a moment,
+/// while Aurora scales or fails over.
+const TRANSIENT_MESSAGES: &[&str] = &[
+    "Communications link failure",
+    "Connection refused",
+    "Connection reset",
+    "terminating connection due to administrator command",
+    "The last packet sent successfully to the server was",
+];
+
+fn bad_request_message(e: &SdkError<ExecuteStatementError>) -> Option<&str> {
+    match e.as_service_error() {
+        Some(ExecuteStatementError::BadRequestException(bad_request)) => {
+            Some(bad_request.message().unwrap_or_default())
+        }
+        _ => None,
+    }
+}
+
+/// Whether the Data API call failed because the database went away for a
+/// moment, so that sending it again may well succeed.
+pub fn is_transient<E: ProvideErrorMetadata>(e: &SdkError<E>) -> bool {
+    e.as_service_error()
+        .filter(|service_error| service_error.code() == Some("BadRequestException"))
+        .and_then(ProvideErrorMetadata::message)
+        .is_some_and(|message| {
+            TRANSIENT_MESSAGES
+                .iter()
+                .any(|transient| message.contains(transient))
+        })
+}
+
+/// Recognize a disabled HTTP endpoint, which otherwise shows up as an
+/// opaque bad request.
+fn execute_statement_error(
+    e: SdkError<ExecuteStatementError>,
+    resource_arn: String,
+) -> anyhow::Error {
+    if let Some(message) = bad_request_message(&e) {
+        if message.contains("HttpEndpoint is not enabled") {
+            return Error::DataApiNotEnabled { resource_arn }.into();
+        }
+    }
+    e.into()
+}
+
+/// The real SDK clients.
+#[derive(Clone, Debug)]
+pub struct AwsClient {
+    rds: aws_sdk_rds::Client,
+    secrets_manager: aws_sdk_secretsmanager::Client,
+    rds_data: aws_sdk_rdsdata::Client,
+    transient_retries: u32,
+}
+
+impl AwsClient {
+    pub fn new(config: &SdkConfig) -> AwsClient {
+        AwsClient {
+            rds: aws_sdk_rds::Client::new(config),
+            secrets_manager: aws_sdk_secretsmanager::Client::new(config),
+            rds_data: aws_sdk_rdsdata::Client::new(config),
+            transient_retries: DEFAULT_TRANSIENT_RETRIES,
+        }
+    }
+
+    /// Put together from clients configured separately, for example each
+    /// with its own test HTTP client.
+    pub fn from_clients(
+        rds: aws_sdk_rds::Client,
+        secrets_manager: aws_sdk_secretsmanager::Client,
+        rds_data: aws_sdk_rdsdata::Client,
+    ) -> AwsClient {
+        AwsClient {
+            rds,
+            secrets_manager,
+            rds_data,
+            transient_retries: DEFAULT_TRANSIENT_RETRIES,
+        }
+    }
+
+    /// Retry a statement this many times, with backoff, when the Data API
+    /// reports a dropped database connection. Only statements that read are
+    /// retried: one that writes may have committed before the connection
+    /// dropped. Statements in a transaction are never retried, since the
+    /// transaction is gone with the connection.
+    pub fn with_transient_retries(mut self, transient_retries: u32) -> AwsClient {
+        self.transient_retries = transient_retries;
+        self
+    }
+
+    async fn execute_statement_once(
+        &self,
+        request: &Request,
+        parameters: Option<Vec<SqlParameter>>,
+        transaction_id: Option<String>,
+    ) -> Result<ExecuteStatementOutput, SdkError<ExecuteStatementError>> {
+        let result_set_options = ResultSetOptions::builder()
+            .decimal_return_type(DecimalReturnType::String)
+            .build();
+        let result = self
+            .rds_data
+            .execute_statement()
+            .set_database(request.target.database.clone())
+            .include_result_metadata(true)
+            .set_parameters(parameters)
+            .resource_arn(&request.target.resource_arn)
+            .result_set_options(result_set_options)
+            .set_schema(request.target.schema.clone())
+            .secret_arn(&request.target.secret_arn)
+            .sql(&request.sql)
+            .set_transaction_id(transaction_id)
+            .send()
+            .await;
+        let request_id = match &result {
+            Ok(output) => output.request_id(),
+            Err(e) => e.request_id(),
+        };
+        Span::current().record("aws.request_id", request_id);
+        result
+    }
+}
+
+impl Client for AwsClient {
+    fn describe_db_clusters(&self) -> BoxFuture<'_, Result<Vec<DbCluster>>> {
+        async move {
+            let db_clusters = self
+                .rds
+                .describe_db_clusters()
+                .max_records(100)
+                .into_paginator()
+                .items()
+                .send()
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("Failed to lookup clusters: {}", e));
+            info!("{:?}", db_clusters);
+            db_clusters
+        }
+        .boxed()
+    }
+
+    fn describe_db_instance(
+        &self,
+        db_instance_identifier: String,
+    ) -> BoxFuture<'_, Result<DbInstance>> {
+        async move {
+            let db_instance_message = self
+                .rds
+                .describe_db_instances()
+                .db_instance_identifier(&db_instance_identifier)
+                .send()
+                .await
+                .map_err(|e| {
+                    anyhow!(
+                        "Failed to lookup DB instance \"{}\": {}",
+                        db_instance_identifier,
+                        e
+                    )
+                });
+            info!("{:?}", db_instance_message);
+            db_instance_message?
+                .db_instances
+                .unwrap_or_default()
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("No DB instance matched \"{}\"", db_instance_identifier))
+        }
+        .boxed()
+    }
+
+    fn list_secrets(&self) -> BoxFuture<'_, Result<Vec<SecretListEntry>>> {
+        async move {
+            let secret_list = self
+                .secrets_manager
+                .list_secrets()
+                .max_results(100)
+                .into_paginator()
+                .send()
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("Failed to lookup secrets: {}", e));
+            info!("{:?}", secret_list);
+            Ok(secret_list?
+                .into_iter()
+                .flat_map(|page| page.secret_list.unwrap_or_default())
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn execute_statement(
+        &self,
+        request: Request,
+        transaction_id: Option<String>,
+    ) -> BoxFuture<'_, Result<ExecuteStatementOutput>> {
+        async move {
+            let parameters = sql_parameters(&request.params)?;
+            let retries = match transaction_id {
+                None if request.target.dialect().reads_only(&request.sql) => self.transient_retries,
+                _ => 0,
+            };
+            let mut attempt = 0;
+            loop {
+                match self
+                    .execute_statement_once(&request, parameters.clone(), transaction_id.clone())
+                    .await
+                {
+                    Ok(execute_statement_output) => {
+                        info!("{:?}", execute_statement_output);
+                        return Ok(execute_statement_output);
+                    }
+                    Err(e) if attempt < retries && is_transient(&e) => {
+                        warn!("Statement failed, retrying: {}", e);
+                        sleep(Duration::from_millis(500 << attempt.min(6))).await;
+                        attempt += 1;
+                    }
+                    Err(e) => return Err(execute_statement_error(e, request.target.resource_arn)),
+                }
+            }
+        }
+        .instrument(info_span!("execute_statement", aws.request_id = Empty))
+        .boxed()
+    }
+
+    fn begin_transaction(&self, target: Target) -> BoxFuture<'_, Result<String>> {
+        async move {
+            let output = self
+                .rds_data
+                .begin_transaction()
+                .resource_arn(target.resource_arn)
+                .secret_arn(target.secret_arn)
+                .set_database(target.database)
+                .set_schema(target.schema)
+                .send()
+                .await
+                .map_err(|e| anyhow!("Failed to begin transaction: {}", e))?;
+            output
+                .transaction_id
+                .ok_or_else(|| anyhow!("The Data API returned no transaction id"))
+        }
+        .boxed()
+    }
+
+    fn commit_transaction(
+        &self,
+        target: Target,
+        transaction_id: String,
+    ) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let output = self
+                .rds_data
+                .commit_transaction()
+                .resource_arn(target.resource_arn)
+                .secret_arn(target.secret_arn)
+                .transaction_id(transaction_id)
+                .send()
+                .await
+                .map_err(|e| anyhow!("Failed to commit transaction: {}", e))?;
+            info!("{:?}", output);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn rollback_transaction(
+        &self,
+        target: Target,
+        transaction_id: String,
+    ) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let output = self
+                .rds_data
+                .rollback_transaction()
+                .resource_arn(target.resource_arn)
+                .secret_arn(target.secret_arn)
+                .transaction_id(transaction_id)
+                .send()
+                .await
+                .map_err(|e| anyhow!("Failed to roll back transaction: {}", e))?;
+            info!("{:?}", output);
+            Ok(())
+        }
+        .boxed()
+    }
+}
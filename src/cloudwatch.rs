@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_cloudwatch::{
+    primitives::DateTime,
+    types::{MetricDatum, StandardUnit},
+    Client,
+};
+use std::time::{Duration, SystemTime};
+
+fn datum(name: &str, value: f64, unit: StandardUnit, timestamp: DateTime) -> MetricDatum {
+    MetricDatum::builder()
+        .metric_name(name)
+        .value(value)
+        .unit(unit)
+        .timestamp(timestamp)
+        .build()
+}
+
+/// Publish a run's Duration, in milliseconds, and its Errors, 0 or 1, to
+/// the namespace. A run without rows failed, so Rows is only published
+/// for runs that succeeded.
+pub async fn emit(
+    config: &SdkConfig,
+    namespace: &str,
+    duration: Duration,
+    rows: Option<u64>,
+) -> Result<()> {
+    let timestamp = DateTime::from(SystemTime::now());
+    let mut metric_data = vec![
+        datum(
+            "Duration",
+            duration.as_secs_f64() * 1000.0,
+            StandardUnit::Milliseconds,
+            timestamp,
+        ),
+        datum(
+            "Errors",
+            f64::from(u8::from(rows.is_none())),
+            StandardUnit::Count,
+            timestamp,
+        ),
+    ];
+    if let Some(rows) = rows {
+        metric_data.push(datum("Rows", rows as f64, StandardUnit::Count, timestamp));
+    }
+    Client::new(config)
+        .put_metric_data()
+        .namespace(namespace)
+        .set_metric_data(Some(metric_data))
+        .send()
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Failed to publish metrics to CloudWatch namespace \"{}\": {}",
+                namespace,
+                e
+            )
+        })?;
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use crate::{audit::AuditConfig, params::Param, policy::PolicyRule};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Settings read from the configuration file, by default
+/// `query-rds-data/config.toml` under the user's configuration directory.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    /// Named queries, runnable with `query-rds-data run <name>`.
+    #[serde(default)]
+    pub queries: BTreeMap<String, NamedQuery>,
+
+    /// Where, and how, to audit executed statements.
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Statements run before every query, in the same transaction, ahead
+    /// of any given with --init-sql. For example:
+    ///
+    /// ```toml
+    /// init_sql = ["SET search_path = analytics"]
+    /// ```
+    #[serde(default)]
+    pub init_sql: Vec<String>,
+
+    /// Which statements may run, by profile and cluster, checked before
+    /// each statement any subcommand sends. See `PolicyRule`.
+    #[serde(default)]
+    pub policy: Vec<PolicyRule>,
+
+    /// Named connections, selected with `--target <name>`.
+    #[serde(default)]
+    pub targets: BTreeMap<String, TargetAlias>,
+}
+
+/// A named connection, with defaults for its queries. For example:
+///
+/// ```toml
+/// [targets.prod-orders]
+/// profile = "prod"
+/// region = "us-east-1"
+/// cluster = "orders"
+/// user = "reporting"
+/// database = "orders"
+/// format = "table"
+/// read_only = true
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetAlias {
+    pub profile: Option<String>,
+    pub region: Option<String>,
+    pub cluster: Option<String>,
+    pub user: Option<String>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    /// Output format, unless --format is given.
+    pub format: Option<String>,
+    /// Refuse to run anything but queries, as with --read-only.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// A saved SQL statement. For example:
+///
+/// ```toml
+/// [queries.daily-active-users]
+/// sql = "select count(distinct user_id) from events where day = :day"
+/// params = { day = "2024-06-01" }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct NamedQuery {
+    pub sql: String,
+
+    /// Default parameter values, keyed as `name` or `name:type`.
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+impl NamedQuery {
+    pub fn default_params(&self) -> Result<Vec<Param>> {
+        self.params
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value).parse())
+            .collect()
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("query-rds-data").join("config.toml"))
+}
+
+/// Each `*.sql` file in the `queries` directory next to the configuration
+/// file becomes a named query, named for the file without its extension.
+fn load_queries_dir(dir: &Path) -> Result<BTreeMap<String, NamedQuery>> {
+    let mut queries = BTreeMap::new();
+    if !dir.is_dir() {
+        return Ok(queries);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "sql") {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                let sql = fs::read_to_string(&path)
+                    .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+                queries.insert(
+                    name.to_owned(),
+                    NamedQuery {
+                        sql,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+    Ok(queries)
+}
+
+impl Config {
+    /// Load the given configuration file, or the default one. A missing
+    /// default file is fine, a missing explicitly requested file is not.
+    pub fn load(requested_path: Option<&Path>) -> Result<Config> {
+        let path = match requested_path {
+            Some(path) => path.to_owned(),
+            None => match default_config_path() {
+                Some(path) => path,
+                None => return Ok(Config::default()),
+            },
+        };
+        let mut config: Config = match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?,
+            Err(e) if requested_path.is_some() => {
+                return Err(anyhow!("Failed to read {}: {}", path.display(), e))
+            }
+            Err(_) => Config::default(),
+        };
+        if let Some(dir) = path.parent() {
+            // Queries in the configuration file win over same-named files.
+            for (name, query) in load_queries_dir(&dir.join("queries"))? {
+                config.queries.entry(name).or_insert(query);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn target(&self, name: &str) -> Result<&TargetAlias> {
+        self.targets.get(name).ok_or_else(|| {
+            anyhow!(
+                "No target named \"{}\", available targets are {:?}",
+                name,
+                self.targets.keys().collect::<Vec<_>>(),
+            )
+        })
+    }
+
+    pub fn query(&self, name: &str) -> Result<&NamedQuery> {
+        self.queries.get(name).ok_or_else(|| {
+            anyhow!(
+                "No query named \"{}\", available queries are {:?}",
+                name,
+                self.queries.keys().collect::<Vec<_>>(),
+            )
+        })
+    }
+}
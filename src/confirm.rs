@@ -0,0 +1,48 @@
+use crate::{output_pattern::cluster_id, Request};
+use anyhow::{anyhow, Result};
+use std::io::{stderr, stdin, IsTerminal, Write};
+
+/// Ask on the terminal. Without one, only --yes will do.
+pub fn confirm(question: &str) -> Result<bool> {
+    if !stdin().is_terminal() {
+        return Err(anyhow!(
+            "Not asking \"{}\" without a terminal, use --yes to go ahead",
+            question
+        ));
+    }
+    eprint!("{} [y/N] ", question);
+    stderr().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+/// On a terminal, ask before running a destructive statement, naming each
+/// cluster and database it would run against. Elsewhere, as in scripts,
+/// statements run without asking.
+pub fn destructive_statement<'a>(requests: impl IntoIterator<Item = &'a Request>) -> Result<()> {
+    let requests: Vec<&Request> = requests.into_iter().collect();
+    let Some(first) = requests.first() else {
+        return Ok(());
+    };
+    let Some(destroys) = first.target.dialect().destructive(&first.sql) else {
+        return Ok(());
+    };
+    if !stdin().is_terminal() {
+        return Ok(());
+    }
+    eprintln!("This statement {}:", destroys);
+    eprintln!("  {}", first.sql.trim());
+    for request in &requests {
+        eprintln!(
+            "  against cluster {}, database {}",
+            cluster_id(&request.target.resource_arn),
+            request.target.database.as_deref().unwrap_or("(default)")
+        );
+    }
+    if confirm("Run it?")? {
+        Ok(())
+    } else {
+        Err(anyhow!("Not running the statement"))
+    }
+}
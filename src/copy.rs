@@ -0,0 +1,385 @@
+use crate::{
+    dialect::Dialect,
+    format_value,
+    guard::Guard,
+    import::{insert_sql, param_name, parse_rate_limit, ImportSummary, Loader, RateLimit},
+    schema::scope,
+    MyArgs, Request, Target,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::{
+    operation::execute_statement::ExecuteStatementOutput,
+    types::{ColumnMetadata, Field, SqlParameter, TypeHint},
+};
+use clap::Args;
+use futures::join;
+use tokio::sync::mpsc;
+use tracing::info;
+
+/// Copy a table's rows from one cluster to another.
+///
+/// Rows are read a page at a time from `--table` on the cluster selected
+/// as usual, in order of the key columns, and inserted into the `--to-*` cluster while the next page
+/// is being read.
+#[derive(Args, Clone, Debug)]
+pub struct CopyArgs {
+    /// Table to copy, optionally qualified by schema.
+    #[clap(long)]
+    table: String,
+
+    /// Table to insert into, when named differently.
+    #[clap(long)]
+    to_table: Option<String>,
+
+    /// Only copy rows matching this SQL condition.
+    #[clap(long = "where", value_name = "CONDITION")]
+    condition: Option<String>,
+
+    /// Columns that uniquely identify a row, to read pages in order of.
+    /// Each page starts after the last key of the page before, so that no
+    /// row is skipped or copied twice, even while the table changes.
+    /// Defaults to the table's primary key.
+    #[clap(long, value_delimiter = ',')]
+    key_columns: Vec<String>,
+
+    /// RDS cluster identifier to copy into.
+    #[clap(long = "to-db-cluster-identifier")]
+    to_cluster_id: Option<String>,
+
+    /// RDS user identifier to copy as.
+    #[clap(long = "to-db-user-identifier")]
+    to_user_id: Option<String>,
+
+    /// ARN of the cluster, or DB instance, to copy into, skipping discovery.
+    #[clap(conflicts_with = "to_cluster_id", long, requires = "to_secret_arn")]
+    to_resource_arn: Option<String>,
+
+    /// ARN of the secret to copy into --to-resource-arn with.
+    #[clap(long, requires = "to_resource_arn")]
+    to_secret_arn: Option<String>,
+
+    /// The engine of --to-resource-arn, as --engine is of --resource-arn.
+    #[clap(long, requires = "to_resource_arn")]
+    to_engine: Option<String>,
+
+    /// Database name to copy into. Defaults to the source database.
+    #[clap(long)]
+    to_database: Option<String>,
+
+    /// Database schema to copy into. Defaults to the source schema.
+    #[clap(long)]
+    to_schema: Option<String>,
+
+    /// Rows per page read, and per batch inserted.
+    #[clap(default_value_t = 1000, long, value_parser = clap::value_parser!(u32).range(1..))]
+    batch_size: u32,
+
+    /// How many times to retry a failed batch before rejecting its rows.
+    #[clap(default_value_t = 2, long)]
+    retries: u32,
+
+    /// Insert no faster than this, such as `500rows/s` or `2batches/s`.
+    /// A bare number is rows per second.
+    #[clap(long, value_name = "RATE", value_parser = parse_rate_limit)]
+    rate_limit: Option<RateLimit>,
+}
+
+/// Values come back as strings for some types that need a hint to be
+/// accepted as parameters, notably decimals (we ask for those as strings).
+fn type_hint(column: &ColumnMetadata) -> Option<TypeHint> {
+    match column.type_name.as_deref()?.to_lowercase().as_str() {
+        "decimal" | "numeric" => Some(TypeHint::Decimal),
+        "date" => Some(TypeHint::Date),
+        "time" => Some(TypeHint::Time),
+        "datetime" | "timestamp" => Some(TypeHint::Timestamp),
+        "json" | "jsonb" => Some(TypeHint::Json),
+        "uuid" => Some(TypeHint::Uuid),
+        _ => None,
+    }
+}
+
+/// The key value as SQL, to compare against.
+fn key_literal(dialect: Dialect, column: &str, value: &Field) -> Result<String> {
+    match value {
+        Field::LongValue(long) => Ok(long.to_string()),
+        Field::DoubleValue(double) => Ok(format!("{:?}", double)),
+        Field::BooleanValue(boolean) => Ok(boolean.to_string().to_uppercase()),
+        Field::StringValue(string) => Ok(dialect.quote_literal(string)),
+        Field::IsNull(_) => Err(anyhow!(
+            "Key column \"{}\" is NULL, so can not order pages",
+            column
+        )),
+        value => Err(anyhow!(
+            "Key column \"{}\" has a value {:?}, which can not order pages",
+            column,
+            value
+        )),
+    }
+}
+
+/// The statement reading the page after the row with the key values,
+/// when given, or else the first page.
+fn select_sql(
+    target: &Target,
+    copy_args: &CopyArgs,
+    key_columns: &[String],
+    after: Option<&[String]>,
+) -> String {
+    let dialect = target.dialect();
+    let mut sql = format!("SELECT * FROM {}", dialect.quote_table(&copy_args.table));
+    let quoted_keys: Vec<String> = key_columns
+        .iter()
+        .map(|column| dialect.quote_identifier(column))
+        .collect();
+    let mut conditions = Vec::new();
+    if let Some(condition) = &copy_args.condition {
+        conditions.push(format!("({})", condition));
+    }
+    if let Some(after) = after {
+        conditions.push(format!(
+            "({}) > ({})",
+            quoted_keys.join(", "),
+            after.join(", ")
+        ));
+    }
+    if !conditions.is_empty() {
+        sql += &format!(" WHERE {}", conditions.join(" AND "));
+    }
+    sql + &format!(
+        " ORDER BY {} LIMIT {}",
+        quoted_keys.join(", "),
+        copy_args.batch_size
+    )
+}
+
+/// The request looking up the primary key of the table, which may be
+/// qualified by schema.
+fn primary_key_request(target: &Target, table: &str) -> Request {
+    let dialect = target.dialect();
+    let (scope, name) = match table.rsplit_once('.') {
+        Some((schema, name)) => (dialect.quote_literal(schema), name),
+        None => (scope(target), table),
+    };
+    let sql = format!(
+        "SELECT kcu.column_name FROM information_schema.table_constraints tc \
+         JOIN information_schema.key_column_usage kcu \
+         ON kcu.constraint_schema = tc.constraint_schema \
+         AND kcu.constraint_name = tc.constraint_name AND kcu.table_name = tc.table_name \
+         WHERE tc.table_schema = {} AND tc.table_name = {} \
+         AND tc.constraint_type = 'PRIMARY KEY' ORDER BY kcu.ordinal_position",
+        scope,
+        dialect.quote_literal(name)
+    );
+    Request {
+        target: target.clone(),
+        params: Vec::new(),
+        sql,
+    }
+}
+
+/// The --key-columns, or else the table's primary key.
+async fn key_columns(
+    guard: &Guard,
+    config: &SdkConfig,
+    source: &Target,
+    copy_args: &CopyArgs,
+) -> Result<Vec<String>> {
+    if !copy_args.key_columns.is_empty() {
+        return Ok(copy_args.key_columns.clone());
+    }
+    let output = guard
+        .execute(config, primary_key_request(source, &copy_args.table))
+        .await?;
+    let key_columns: Vec<String> = output
+        .records
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|record| record.first().map(format_value))
+        .collect();
+    if key_columns.is_empty() {
+        return Err(anyhow!(
+            "Table {} has no primary key, give --key-columns to order pages by",
+            copy_args.table
+        ));
+    }
+    Ok(key_columns)
+}
+
+/// The first request a copy sends, for --dry-run: reading the first page,
+/// or without --key-columns, looking up the primary key to order pages by.
+pub fn dry_run_request(source: &Target, copy_args: &CopyArgs) -> Request {
+    if copy_args.key_columns.is_empty() {
+        return primary_key_request(source, &copy_args.table);
+    }
+    Request {
+        target: source.clone(),
+        params: Vec::new(),
+        sql: select_sql(source, copy_args, &copy_args.key_columns, None),
+    }
+}
+
+/// The key values of the page's last row, as SQL.
+fn last_key(
+    dialect: Dialect,
+    page: &ExecuteStatementOutput,
+    key_columns: &[String],
+) -> Result<Option<Vec<String>>> {
+    let Some(last) = page.records.as_ref().and_then(|records| records.last()) else {
+        return Ok(None);
+    };
+    let names: Vec<&str> = page
+        .column_metadata
+        .iter()
+        .flatten()
+        .map(|column| column.name.as_deref().unwrap_or_default())
+        .collect();
+    key_columns
+        .iter()
+        .map(|column| {
+            let index = names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(column))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "No column matched key column \"{}\", available columns are {:?}",
+                        column,
+                        names
+                    )
+                })?;
+            key_literal(dialect, column, &last[index])
+        })
+        .collect::<Result<_>>()
+        .map(Some)
+}
+
+/// The arguments selecting the destination: those of the source, with the
+/// cluster, credentials and engine replaced by the `--to-*` ones, and the
+/// database and schema when given. The destination is then found as any
+/// target is, honoring --offline-discovery.
+pub fn destination_args(args: &MyArgs, copy_args: &CopyArgs) -> MyArgs {
+    let mut destination = args.clone();
+    destination.cluster_id.clone_from(&copy_args.to_cluster_id);
+    destination.user_id.clone_from(&copy_args.to_user_id);
+    destination.instance_id = None;
+    destination
+        .resource_arn
+        .clone_from(&copy_args.to_resource_arn);
+    destination.secret_arn.clone_from(&copy_args.to_secret_arn);
+    destination.engine.clone_from(&copy_args.to_engine);
+    destination.resource_engine = None;
+    if copy_args.to_database.is_some() {
+        destination.database.clone_from(&copy_args.to_database);
+    }
+    if copy_args.to_schema.is_some() {
+        destination.schema.clone_from(&copy_args.to_schema);
+    }
+    destination
+}
+
+/// Read pages, in order of the key columns, until one comes back short,
+/// handing each to the writer.
+async fn read_pages(
+    guard: &Guard,
+    config: &SdkConfig,
+    source: &Target,
+    copy_args: &CopyArgs,
+    pages: mpsc::Sender<ExecuteStatementOutput>,
+) -> Result<()> {
+    let key_columns = key_columns(guard, config, source, copy_args).await?;
+    let mut after = None;
+    loop {
+        let request = Request {
+            target: source.clone(),
+            params: Vec::new(),
+            sql: select_sql(source, copy_args, &key_columns, after.as_deref()),
+        };
+        let page = guard.execute(config, request).await?;
+        let count = page.records.as_ref().map_or(0, Vec::len);
+        after = last_key(source.dialect(), &page, &key_columns)?;
+        if pages.send(page).await.is_err() {
+            // The writer gave up, and has its own error to report.
+            return Ok(());
+        }
+        if count < copy_args.batch_size as usize {
+            return Ok(());
+        }
+    }
+}
+
+async fn write_pages(
+    guard: &Guard,
+    config: &SdkConfig,
+    destination: &Target,
+    copy_args: &CopyArgs,
+    mut pages: mpsc::Receiver<ExecuteStatementOutput>,
+) -> Result<ImportSummary> {
+    let mut loader = None;
+    let mut type_hints = Vec::new();
+    while let Some(page) = pages.recv().await {
+        if loader.is_none() {
+            let columns = page.column_metadata.unwrap_or_default();
+            if columns.is_empty() {
+                return Err(anyhow!("No columns found in {}", copy_args.table));
+            }
+            let names: Vec<String> = columns
+                .iter()
+                .map(|column| column.name.clone().unwrap_or_default())
+                .collect();
+            let table = copy_args.to_table.as_ref().unwrap_or(&copy_args.table);
+            let sql = insert_sql(destination.dialect(), table, &names);
+            info!("{}", sql);
+            type_hints = columns.iter().map(type_hint).collect();
+            loader = Some(Loader::new(
+                guard,
+                config,
+                destination,
+                sql,
+                copy_args.batch_size,
+                copy_args.retries,
+                copy_args.rate_limit,
+            ));
+        }
+        if let Some(loader) = &mut loader {
+            for record in page.records.unwrap_or_default() {
+                let parameter_set = record
+                    .into_iter()
+                    .zip(&type_hints)
+                    .enumerate()
+                    .map(|(index, (value, type_hint))| {
+                        SqlParameter::builder()
+                            .name(param_name(index))
+                            .value(value)
+                            .set_type_hint(type_hint.clone())
+                            .build()
+                    })
+                    .collect();
+                loader.push(parameter_set).await?;
+            }
+        }
+    }
+    match loader {
+        Some(loader) => loader.finish().await,
+        None => Ok(ImportSummary {
+            imported: 0,
+            rejected: Vec::new(),
+        }),
+    }
+}
+
+pub async fn copy(
+    guard: &Guard,
+    config: &SdkConfig,
+    source: &Target,
+    destination: &Target,
+    copy_args: &CopyArgs,
+) -> Result<ImportSummary> {
+    // Reading one page ahead is enough to keep both sides busy.
+    let (sender, receiver) = mpsc::channel(1);
+    let (read_result, write_result) = join!(
+        read_pages(guard, config, source, copy_args, sender),
+        write_pages(guard, config, destination, copy_args, receiver),
+    );
+    read_result?;
+    write_result
+}
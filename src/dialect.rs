@@ -0,0 +1,412 @@
+use anyhow::{anyhow, Result};
+use sqlparser::{
+    ast::{Delete, Query, SetExpr, Statement, Update},
+    dialect::{MySqlDialect, PostgreSqlDialect},
+    parser::{Parser, ParserError},
+};
+
+/// Statement kinds that only read.
+pub const READ_ONLY: &[&str] = &["SELECT", "SHOW", "EXPLAIN", "DESCRIBE"];
+
+/// The SQL flavor spoken by the target cluster.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dialect {
+    MySql,
+    Postgres,
+}
+
+impl Dialect {
+    /// Pick a dialect from `DbCluster.engine`, for example "aurora-postgresql"
+    /// or "aurora-mysql". The original Data API only supported MySQL, so that
+    /// is what we assume when the engine is unknown.
+    pub fn from_engine(engine: Option<&str>) -> Dialect {
+        match engine {
+            Some(engine) if engine.contains("postgres") => Dialect::Postgres,
+            _ => Dialect::MySql,
+        }
+    }
+
+    pub fn quote_identifier(&self, identifier: &str) -> String {
+        match self {
+            Dialect::MySql => format!("`{}`", identifier.replace('`', "``")),
+            Dialect::Postgres => format!("\"{}\"", identifier.replace('"', "\"\"")),
+        }
+    }
+
+    /// Quote each part of a possibly schema qualified table name.
+    pub fn quote_table(&self, table: &str) -> String {
+        table
+            .split('.')
+            .map(|part| self.quote_identifier(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    pub fn quote_literal(&self, literal: &str) -> String {
+        match self {
+            // MySQL treats backslash as an escape character by default.
+            Dialect::MySql => format!("'{}'", literal.replace('\\', "\\\\").replace('\'', "''")),
+            Dialect::Postgres => format!("'{}'", literal.replace('\'', "''")),
+        }
+    }
+
+    fn parse(&self, sql: &str) -> Result<Vec<Statement>, ParserError> {
+        match self {
+            Dialect::MySql => Parser::parse_sql(&MySqlDialect {}, sql),
+            Dialect::Postgres => Parser::parse_sql(&PostgreSqlDialect {}, sql),
+        }
+    }
+
+    /// Parse the SQL locally, reporting any syntax error without a round trip.
+    pub fn check_syntax(&self, sql: &str) -> Result<()> {
+        self.parse(sql)
+            .map(|_statements| ())
+            .map_err(|e| anyhow!("SQL syntax check failed ({:?}): {}", self, e))
+    }
+
+    /// The kind of each statement in the SQL, named by its leading keyword,
+    /// such as SELECT, INSERT or DROP.
+    pub fn statement_kinds(&self, sql: &str) -> Result<Vec<String>> {
+        let statements = self
+            .parse(sql)
+            .map_err(|e| anyhow!("Failed to parse the SQL ({:?}): {}", self, e))?;
+        let mut kinds = Vec::new();
+        for statement in &statements {
+            statement_kinds(statement, &mut kinds);
+        }
+        Ok(kinds)
+    }
+
+    /// Whether the SQL's statements only read. SQL we cannot parse is
+    /// assumed to write.
+    pub fn reads_only(&self, sql: &str) -> bool {
+        self.statement_kinds(sql)
+            .is_ok_and(|kinds| kinds.iter().all(|kind| READ_ONLY.contains(&kind.as_str())))
+    }
+
+    /// What the SQL would destroy, if anything: it drops or truncates, or
+    /// deletes or updates without a WHERE clause, even under EXPLAIN
+    /// ANALYZE. SQL we cannot parse is judged by its leading keyword.
+    pub fn destructive(&self, sql: &str) -> Option<&'static str> {
+        let Ok(statements) = self.parse(sql) else {
+            let keyword = sql.split_whitespace().next().unwrap_or_default();
+            return match keyword.to_uppercase().as_str() {
+                "DROP" => Some("drops database objects"),
+                "TRUNCATE" => Some("empties tables"),
+                _ => None,
+            };
+        };
+        statements.iter().find_map(destroys)
+    }
+
+    /// Split a script into statements, on semicolons outside of quotes and
+    /// comments, including MySQL `#` comments and PostgreSQL `E'...'`
+    /// strings, with their backslash escapes. Pieces holding nothing but
+    /// comments are dropped.
+    pub fn split_statements(&self, sql: &str) -> Vec<String> {
+        let bytes = sql.as_bytes();
+        let mut statements = Vec::new();
+        let mut start = 0;
+        let mut has_code = false;
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b';' => {
+                    if has_code {
+                        statements.push(sql[start..i].trim().to_owned());
+                    }
+                    has_code = false;
+                    i += 1;
+                    start = i;
+                    continue;
+                }
+                b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                    i = sql[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                    continue;
+                }
+                b'#' if *self == Dialect::MySql => {
+                    i = sql[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                    continue;
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                    i = sql[i + 2..]
+                        .find("*/")
+                        .map_or(bytes.len(), |end| i + end + 4);
+                    continue;
+                }
+                b'E' | b'e'
+                    if *self == Dialect::Postgres
+                        && bytes.get(i + 1) == Some(&b'\'')
+                        && !(i > 0 && is_identifier_byte(bytes[i - 1])) =>
+                {
+                    i = skip_quoted(bytes, i + 1, b'\'', true);
+                }
+                quote @ (b'\'' | b'"' | b'`') => {
+                    let backslash_escapes = *self == Dialect::MySql && quote != b'`';
+                    i = skip_quoted(bytes, i, quote, backslash_escapes);
+                }
+                b'$' if *self == Dialect::Postgres => match dollar_tag(&sql[i..]) {
+                    Some(tag) => {
+                        let body = i + tag.len();
+                        i = sql[body..]
+                            .find(tag)
+                            .map_or(bytes.len(), |end| body + end + tag.len());
+                    }
+                    None => i += 1,
+                },
+                byte if byte.is_ascii_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                _ => i += 1,
+            }
+            has_code = true;
+        }
+        if has_code {
+            statements.push(sql[start..].trim().to_owned());
+        }
+        statements
+    }
+}
+
+/// The index just past the quoted text starting at `start`. A doubled
+/// quote needs no special handling: it closes and reopens the quote.
+fn skip_quoted(bytes: &[u8], start: usize, quote: u8, backslash_escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if backslash_escapes => i += 2,
+            byte if byte == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Whether the byte can be part of an unquoted identifier, so that a name
+/// ending in `e` just before a quote does not start an escape string.
+fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$'
+}
+
+/// The statement an EXPLAIN ANALYZE runs, as well as explaining it.
+fn analyzed(statement: &Statement) -> Option<&Statement> {
+    match statement {
+        Statement::Explain {
+            analyze,
+            options,
+            statement,
+            ..
+        } if *analyze
+            || options
+                .iter()
+                .flatten()
+                .any(|option| option.name.value.eq_ignore_ascii_case("ANALYZE")) =>
+        {
+            Some(statement)
+        }
+        _ => None,
+    }
+}
+
+fn destroys(statement: &Statement) -> Option<&'static str> {
+    match statement {
+        Statement::Drop { .. } => Some("drops database objects"),
+        Statement::Truncate(_) => Some("empties tables"),
+        Statement::Delete(Delete {
+            selection: None, ..
+        }) => Some("deletes every row"),
+        Statement::Update(Update {
+            selection: None, ..
+        }) => Some("updates every row"),
+        statement => analyzed(statement).and_then(destroys),
+    }
+}
+
+/// Add the kinds of the statement, and of any statements in its WITH
+/// clause, which PostgreSQL lets change data too. EXPLAIN ANALYZE runs the
+/// statement it explains, so that statement's kinds are added as well.
+fn statement_kinds(statement: &Statement, kinds: &mut Vec<String>) {
+    if let Some(analyzed) = analyzed(statement) {
+        kinds.push("EXPLAIN".to_owned());
+        return statement_kinds(analyzed, kinds);
+    }
+    match statement {
+        Statement::Query(query) => query_kinds(query, kinds),
+        statement => kinds.push(
+            statement
+                .to_string()
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_uppercase(),
+        ),
+    }
+}
+
+fn query_kinds(query: &Query, kinds: &mut Vec<String>) {
+    for cte in query.with.iter().flat_map(|with| &with.cte_tables) {
+        query_kinds(&cte.query, kinds);
+    }
+    match query.body.as_ref() {
+        SetExpr::Insert(statement)
+        | SetExpr::Update(statement)
+        | SetExpr::Delete(statement)
+        | SetExpr::Merge(statement) => statement_kinds(statement, kinds),
+        SetExpr::Query(query) => query_kinds(query, kinds),
+        _ => kinds.push("SELECT".to_owned()),
+    }
+}
+
+/// A PostgreSQL dollar quote opening the text, such as `$$` or `$body$`.
+/// `$1` is a positional parameter, not a quote.
+fn dollar_tag(text: &str) -> Option<&str> {
+    let end = text[1..].find('$')? + 2;
+    let tag = &text[1..end - 1];
+    let valid = tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !tag.starts_with(|c: char| c.is_ascii_digit());
+    valid.then_some(&text[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_semicolons_outside_quotes() {
+        assert_eq!(
+            Dialect::Postgres.split_statements("select 'a;b'; select \"c;d\"; select 1"),
+            ["select 'a;b'", "select \"c;d\"", "select 1"]
+        );
+        assert_eq!(
+            Dialect::MySql.split_statements("select 'it''s;' ; select `a;b`;"),
+            ["select 'it''s;'", "select `a;b`"]
+        );
+    }
+
+    #[test]
+    fn splits_around_backslash_escapes() {
+        assert_eq!(
+            Dialect::MySql.split_statements(r"select 'a\';b'; select 2"),
+            [r"select 'a\';b'", "select 2"]
+        );
+        assert_eq!(
+            Dialect::Postgres.split_statements(r"select E'a\';b'; select 2"),
+            [r"select E'a\';b'", "select 2"]
+        );
+        // Without E, a backslash is just a backslash in PostgreSQL.
+        assert_eq!(
+            Dialect::Postgres.split_statements(r"select 'a\'; select 2"),
+            [r"select 'a\'", "select 2"]
+        );
+    }
+
+    #[test]
+    fn splits_around_dollar_quotes() {
+        assert_eq!(
+            Dialect::Postgres.split_statements(
+                "create function f() returns int as $body$ select 1; $body$ language sql; select $1"
+            ),
+            [
+                "create function f() returns int as $body$ select 1; $body$ language sql",
+                "select $1"
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_around_comments() {
+        assert_eq!(
+            Dialect::Postgres.split_statements(
+                "-- first; still a comment\nselect 1; /* a; b */ select 2;\n-- only a comment;"
+            ),
+            ["-- first; still a comment\nselect 1", "/* a; b */ select 2"]
+        );
+        assert_eq!(
+            Dialect::MySql.split_statements("# a; comment\nselect 1;\n# another;"),
+            ["# a; comment\nselect 1"]
+        );
+    }
+
+    #[test]
+    fn keeps_a_trailing_statement_without_a_semicolon() {
+        assert_eq!(
+            Dialect::MySql.split_statements("select 1;\nselect 2\n"),
+            ["select 1", "select 2"]
+        );
+        assert!(Dialect::MySql.split_statements(" ; \n").is_empty());
+    }
+
+    #[test]
+    fn names_statement_kinds() {
+        let kinds = |sql| Dialect::Postgres.statement_kinds(sql).unwrap();
+        assert_eq!(
+            kinds("select 1; insert into t values (1)"),
+            ["SELECT", "INSERT"]
+        );
+        assert_eq!(
+            kinds("with gone as (delete from t returning *) select * from gone"),
+            ["DELETE", "SELECT"]
+        );
+        assert_eq!(kinds("explain select 1"), ["EXPLAIN"]);
+        assert_eq!(
+            kinds("explain analyze delete from t"),
+            ["EXPLAIN", "DELETE"]
+        );
+        assert_eq!(
+            kinds("explain (analyze, format json) update t set a = 1"),
+            ["EXPLAIN", "UPDATE"]
+        );
+        assert!(Dialect::Postgres.statement_kinds("selec 1").is_err());
+    }
+
+    #[test]
+    fn finds_destructive_statements() {
+        let destructive = |sql| Dialect::Postgres.destructive(sql);
+        assert_eq!(destructive("delete from t"), Some("deletes every row"));
+        assert_eq!(destructive("delete from t where id = 1"), None);
+        assert_eq!(destructive("update t set a = 1"), Some("updates every row"));
+        assert_eq!(destructive("update t set a = 1 where id = 1"), None);
+        assert_eq!(destructive("truncate t"), Some("empties tables"));
+        assert_eq!(destructive("drop table t"), Some("drops database objects"));
+        assert_eq!(
+            destructive("explain analyze delete from t"),
+            Some("deletes every row")
+        );
+        assert_eq!(destructive("explain delete from t"), None);
+        assert_eq!(destructive("select 1; truncate t"), Some("empties tables"));
+        assert_eq!(destructive("select 1"), None);
+    }
+
+    #[test]
+    fn judges_unparsable_sql_by_its_leading_keyword() {
+        assert_eq!(
+            Dialect::MySql.destructive("DROP TABLE ((("),
+            Some("drops database objects")
+        );
+        assert_eq!(
+            Dialect::MySql.destructive("truncate ((("),
+            Some("empties tables")
+        );
+        assert_eq!(Dialect::MySql.destructive("delete ((("), None);
+        assert!(!Dialect::MySql.reads_only("select ((("));
+    }
+
+    #[test]
+    fn reads_only_when_every_statement_reads() {
+        assert!(Dialect::Postgres.reads_only("select 1; show search_path; explain select 1"));
+        assert!(!Dialect::Postgres.reads_only("select 1; delete from t where id = 1"));
+        assert!(!Dialect::Postgres
+            .reads_only("with gone as (delete from t returning *) select * from gone"));
+        assert!(!Dialect::Postgres.reads_only("explain analyze delete from t"));
+    }
+
+    #[test]
+    fn quotes_identifiers_and_literals() {
+        assert_eq!(Dialect::Postgres.quote_identifier(r#"a"b`"#), r#""a""b`""#);
+        assert_eq!(Dialect::MySql.quote_identifier(r#"a"b`"#), r#"`a"b```"#);
+        assert_eq!(Dialect::Postgres.quote_table("s.t"), r#""s"."t""#);
+        assert_eq!(Dialect::Postgres.quote_literal(r"it's \"), r"'it''s \'");
+        assert_eq!(Dialect::MySql.quote_literal(r"it's \"), r"'it''s \\'");
+    }
+}
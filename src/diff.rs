@@ -0,0 +1,174 @@
+use crate::{format_header, format_rows};
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::operation::execute_statement::ExecuteStatementOutput;
+use clap::Args;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+};
+
+/// Run a query against two clusters and report how the results differ.
+///
+/// Differences are written as CSV, with a leading `diff` column of `-`
+/// for rows only in target A and `+` for rows only in target B. With
+/// `--key`, a changed row appears as a `-` row followed by a `+` row.
+/// Exits with an error when there are differences.
+#[derive(Args, Clone, Debug)]
+pub struct DiffArgs {
+    /// RDS cluster identifier of the first target.
+    #[clap(long)]
+    pub target_a: String,
+
+    /// RDS cluster identifier of the second target.
+    #[clap(long)]
+    pub target_b: String,
+
+    /// Columns identifying a row, so changed rows can be told apart from
+    /// added and removed ones. Without a key, whole rows are compared.
+    #[clap(long, value_delimiter = ',')]
+    pub key: Vec<String>,
+
+    /// SQL query.
+    pub query: String,
+}
+
+type Row = Vec<String>;
+
+fn rows(result: &ExecuteStatementOutput) -> Vec<Row> {
+    format_rows(result).map(Iterator::collect).collect()
+}
+
+#[derive(Default)]
+struct Differences {
+    lines: Vec<(&'static str, Row)>,
+    added: usize,
+    removed: usize,
+    changed: usize,
+}
+
+impl Differences {
+    fn added(&mut self, row: Row) {
+        self.added += 1;
+        self.lines.push(("+", row));
+    }
+
+    fn removed(&mut self, row: Row) {
+        self.removed += 1;
+        self.lines.push(("-", row));
+    }
+
+    fn changed(&mut self, old: Row, new: Row) {
+        self.changed += 1;
+        self.lines.push(("-", old));
+        self.lines.push(("+", new));
+    }
+}
+
+/// Rows are matched up as a multiset: duplicates must appear equally often.
+fn diff_rows(rows_a: Vec<Row>, rows_b: Vec<Row>) -> Differences {
+    let mut differences = Differences::default();
+    let mut counts_b: HashMap<&Row, usize> = HashMap::new();
+    for row in &rows_b {
+        *counts_b.entry(row).or_default() += 1;
+    }
+    let mut counts_a: HashMap<&Row, usize> = HashMap::new();
+    for row in &rows_a {
+        match counts_b.get_mut(row) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => differences.removed(row.clone()),
+        }
+        *counts_a.entry(row).or_default() += 1;
+    }
+    for row in &rows_b {
+        match counts_a.get_mut(row) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => differences.added(row.clone()),
+        }
+    }
+    differences
+}
+
+fn diff_keyed(rows_a: Vec<Row>, rows_b: Vec<Row>, key_indexes: &[usize]) -> Result<Differences> {
+    let key_of = |row: &Row| -> Row { key_indexes.iter().map(|&i| row[i].clone()).collect() };
+    let mut differences = Differences::default();
+    let mut keyed_b: BTreeMap<Row, Row> = BTreeMap::new();
+    let mut order_b = Vec::with_capacity(rows_b.len());
+    for row in rows_b {
+        let key = key_of(&row);
+        if keyed_b.insert(key.clone(), row).is_some() {
+            return Err(anyhow!("Key {:?} appears more than once in target B", key));
+        }
+        order_b.push(key);
+    }
+    let mut seen_a = BTreeSet::new();
+    for row in rows_a {
+        let key = key_of(&row);
+        if !seen_a.insert(key.clone()) {
+            return Err(anyhow!("Key {:?} appears more than once in target A", key));
+        }
+        match keyed_b.remove(&key) {
+            Some(row_b) if row_b == row => {}
+            Some(row_b) => differences.changed(row, row_b),
+            None => differences.removed(row),
+        }
+    }
+    for key in order_b {
+        if let Some(row) = keyed_b.remove(&key) {
+            differences.added(row);
+        }
+    }
+    Ok(differences)
+}
+
+pub fn diff(
+    result_a: &ExecuteStatementOutput,
+    result_b: &ExecuteStatementOutput,
+    key: &[String],
+    writer: impl Write,
+) -> Result<()> {
+    let header: Vec<&str> = format_header(result_a).collect();
+    let header_b: Vec<&str> = format_header(result_b).collect();
+    if header != header_b {
+        return Err(anyhow!(
+            "The targets returned different columns: {:?} and {:?}",
+            header,
+            header_b,
+        ));
+    }
+    let key_indexes = key
+        .iter()
+        .map(|column| {
+            header
+                .iter()
+                .position(|name| name == column)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "No column matched key \"{}\", available columns are {:?}",
+                        column,
+                        header,
+                    )
+                })
+        })
+        .collect::<Result<Vec<usize>>>()?;
+    let differences = if key_indexes.is_empty() {
+        diff_rows(rows(result_a), rows(result_b))
+    } else {
+        diff_keyed(rows(result_a), rows(result_b), &key_indexes)?
+    };
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(std::iter::once("diff").chain(header.iter().copied()))?;
+    for (marker, row) in &differences.lines {
+        wtr.write_record(std::iter::once(*marker).chain(row.iter().map(String::as_str)))?;
+    }
+    wtr.flush()?;
+    if differences.lines.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} added, {} removed, {} changed",
+            differences.added,
+            differences.removed,
+            differences.changed,
+        ))
+    }
+}
@@ -0,0 +1,335 @@
+use crate::{
+    dialect::Dialect,
+    guard::Guard,
+    transaction::{Isolation, Transaction},
+    AwsClient, Client, Request,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::{
+    operation::execute_statement::ExecuteStatementOutput,
+    types::{ColumnMetadata, Field},
+};
+use clap::ValueEnum;
+use futures::prelude::*;
+use serde::Deserialize;
+use sqlx::{
+    mysql::{MySqlConnectOptions, MySqlConnection, MySqlQueryResult},
+    postgres::{PgConnectOptions, PgConnection, PgQueryResult},
+    AssertSqlSafe, Column, Connection, Database, Decode, Either, Executor, Row, TypeInfo,
+};
+use tracing::{info, warn};
+
+/// How statements reach the database.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Driver {
+    /// The RDS Data API, over HTTPS.
+    DataApi,
+    /// Connect directly to the cluster endpoint, as PostgreSQL, with the
+    /// credentials from the secret.
+    Postgres,
+    /// Connect directly to the cluster endpoint, as MySQL, with the
+    /// credentials from the secret.
+    Mysql,
+}
+
+/// The parts of an RDS credentials secret we use. Secrets made by RDS
+/// include the endpoint; for others, it is looked up on the cluster.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Credentials {
+    username: String,
+    password: String,
+    host: Option<String>,
+    port: Option<u16>,
+    dbname: Option<String>,
+}
+
+struct Endpoint {
+    host: String,
+    port: Option<u16>,
+    database: Option<String>,
+}
+
+async fn credentials(config: &SdkConfig, secret_arn: &str) -> Result<Credentials> {
+    let output = aws_sdk_secretsmanager::Client::new(config)
+        .get_secret_value()
+        .secret_id(secret_arn)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to read secret {}: {}", secret_arn, e))?;
+    let secret_string = output
+        .secret_string
+        .ok_or_else(|| anyhow!("Secret {} has no secret string", secret_arn))?;
+    serde_json::from_str(&secret_string).map_err(|e| {
+        anyhow!(
+            "Secret {} does not hold database credentials: {}",
+            secret_arn,
+            e
+        )
+    })
+}
+
+async fn endpoint(
+    config: &SdkConfig,
+    resource_arn: &str,
+    credentials: &Credentials,
+) -> Result<Endpoint> {
+    if let Some(host) = &credentials.host {
+        return Ok(Endpoint {
+            host: host.clone(),
+            port: credentials.port,
+            database: credentials.dbname.clone(),
+        });
+    }
+    let output = aws_sdk_rds::Client::new(config)
+        .describe_db_clusters()
+        .db_cluster_identifier(resource_arn)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to lookup cluster {}: {}", resource_arn, e))?;
+    let db_cluster = output
+        .db_clusters
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No cluster matched \"{}\"", resource_arn))?;
+    Ok(Endpoint {
+        host: db_cluster
+            .endpoint
+            .ok_or_else(|| anyhow!("Cluster {} has no endpoint", resource_arn))?,
+        port: db_cluster.port.and_then(|port| u16::try_from(port).ok()),
+        database: credentials.dbname.clone().or(db_cluster.database_name),
+    })
+}
+
+/// The Data API's typing of a value, from its text form.
+fn field(type_name: &str, text: Option<String>) -> Field {
+    let Some(text) = text else {
+        return Field::IsNull(true);
+    };
+    let type_name = type_name.trim_end_matches(" UNSIGNED");
+    let parsed = match type_name {
+        "BOOL" => Some(Field::BooleanValue(text == "t")),
+        "BOOLEAN" => Some(Field::BooleanValue(text != "0")),
+        "INT2" | "INT4" | "INT8" | "OID" | "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT"
+        | "BIGINT" | "YEAR" => text.parse().ok().map(Field::LongValue),
+        "FLOAT4" | "FLOAT8" | "FLOAT" | "DOUBLE" => text.parse().ok().map(Field::DoubleValue),
+        _ => None,
+    };
+    // Decimals, dates and anything else stay strings, as from the Data API.
+    parsed.unwrap_or(Field::StringValue(text))
+}
+
+/// Run the SQL over the simple text protocol, reshaping the results to
+/// look like the Data API's, so every output format works unchanged.
+async fn run<DB>(
+    connection: &mut DB::Connection,
+    sql: String,
+    rows_affected: fn(&DB::QueryResult) -> u64,
+) -> Result<ExecuteStatementOutput>
+where
+    DB: Database,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'r> Option<String>: Decode<'r, DB>,
+    usize: sqlx::ColumnIndex<DB::Row>,
+{
+    let mut results = sqlx::raw_sql(AssertSqlSafe(sql)).fetch_many(connection);
+    let mut columns = None;
+    let mut records = Vec::new();
+    let mut updated = 0;
+    while let Some(result) = results.try_next().await? {
+        match result {
+            Either::Left(query_result) => updated += rows_affected(&query_result),
+            Either::Right(row) => {
+                let row_columns = row.columns();
+                if columns.is_none() {
+                    columns = Some(
+                        row_columns
+                            .iter()
+                            .map(|column| {
+                                ColumnMetadata::builder()
+                                    .name(column.name())
+                                    .label(column.name())
+                                    .type_name(column.type_info().name().to_lowercase())
+                                    .build()
+                            })
+                            .collect(),
+                    );
+                }
+                let record = row_columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, column)| {
+                        Ok(field(
+                            column.type_info().name(),
+                            row.try_get_unchecked(index)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                records.push(record);
+            }
+        }
+    }
+    Ok(ExecuteStatementOutput::builder()
+        .set_column_metadata(columns)
+        .set_records(Some(records))
+        .number_of_records_updated(i64::try_from(updated).unwrap_or(i64::MAX))
+        .build())
+}
+
+async fn postgres(
+    config: &SdkConfig,
+    guard: &Guard,
+    init: &[Request],
+    request: Request,
+    credentials: Credentials,
+    endpoint: Endpoint,
+) -> Result<ExecuteStatementOutput> {
+    let mut options = PgConnectOptions::new()
+        .host(&endpoint.host)
+        .port(endpoint.port.unwrap_or(5432))
+        .username(&credentials.username)
+        .password(&credentials.password);
+    if let Some(database) = &endpoint.database {
+        options = options.database(database);
+    }
+    let mut connection = PgConnection::connect_with(&options)
+        .await
+        .map_err(|e| anyhow!("Failed to connect to {}: {}", endpoint.host, e))?;
+    let rows_affected = |result: &PgQueryResult| result.rows_affected();
+    if let Some(schema) = &request.target.schema {
+        let search_path = format!(
+            "SET search_path TO {}",
+            Dialect::Postgres.quote_identifier(schema)
+        );
+        run::<sqlx::Postgres>(&mut connection, search_path, rows_affected).await?;
+    }
+    for setup in init {
+        let execution = run::<sqlx::Postgres>(&mut connection, setup.sql.clone(), rows_affected);
+        guard.run(config, setup, execution).await?;
+    }
+    let output = run::<sqlx::Postgres>(&mut connection, request.sql, rows_affected).await;
+    let _result = connection.close().await;
+    output
+}
+
+async fn mysql(
+    config: &SdkConfig,
+    guard: &Guard,
+    init: &[Request],
+    request: Request,
+    credentials: Credentials,
+    endpoint: Endpoint,
+) -> Result<ExecuteStatementOutput> {
+    let mut options = MySqlConnectOptions::new()
+        .host(&endpoint.host)
+        .port(endpoint.port.unwrap_or(3306))
+        .username(&credentials.username)
+        .password(&credentials.password);
+    if let Some(database) = &endpoint.database {
+        options = options.database(database);
+    }
+    let mut connection = MySqlConnection::connect_with(&options)
+        .await
+        .map_err(|e| anyhow!("Failed to connect to {}: {}", endpoint.host, e))?;
+    let rows_affected = |result: &MySqlQueryResult| result.rows_affected();
+    for setup in init {
+        let execution = run::<sqlx::MySql>(&mut connection, setup.sql.clone(), rows_affected);
+        guard.run(config, setup, execution).await?;
+    }
+    let output = run::<sqlx::MySql>(&mut connection, request.sql, rows_affected).await;
+    let _result = connection.close().await;
+    output
+}
+
+/// Run the setup statements, then the request, in one Data API
+/// transaction. Otherwise, each statement may get a different connection.
+async fn data_api_session(
+    config: &SdkConfig,
+    guard: &Guard,
+    client: &AwsClient,
+    init: &[Request],
+    isolation: Option<Isolation>,
+    request: Request,
+) -> Result<ExecuteStatementOutput> {
+    let transaction = Transaction::begin(config, &request.target, isolation).await?;
+    let transaction_id = transaction.id().to_owned();
+    let result = async {
+        for setup in init {
+            info!("{}", setup.sql);
+            let execution = client.execute_statement(setup.clone(), Some(transaction_id.clone()));
+            guard
+                .run(config, setup, execution)
+                .await
+                .map_err(|e| anyhow!("Setup statement \"{}\" failed: {}", setup.sql, e))?;
+        }
+        client
+            .execute_statement(request, Some(transaction_id))
+            .await
+    }
+    .await;
+    match result {
+        Ok(output) => {
+            transaction.commit().await?;
+            Ok(output)
+        }
+        Err(e) => {
+            if let Err(rollback_error) = transaction.rollback().await {
+                warn!("{}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Execute the request with the driver, after the setup statements, in the
+/// same session. Each setup statement goes through the guard; the request
+/// is left to the caller. Direct connections use the credentials in the
+/// request's secret.
+pub async fn execute(
+    config: &SdkConfig,
+    guard: &Guard,
+    driver: Driver,
+    transient_retries: u32,
+    init: &[Request],
+    isolation: Option<Isolation>,
+    request: Request,
+) -> Result<ExecuteStatementOutput> {
+    if driver == Driver::DataApi {
+        let client = AwsClient::new(config).with_transient_retries(transient_retries);
+        if init.is_empty() && isolation.is_none() {
+            return client.execute_statement(request, None).await;
+        }
+        return data_api_session(config, guard, &client, init, isolation, request).await;
+    }
+    if isolation.is_some() {
+        return Err(anyhow!(
+            "--isolation can only be used with --driver {:?}",
+            "data-api"
+        ));
+    }
+    if !request.params.is_empty() {
+        return Err(anyhow!(
+            "--param can only be used with --driver {:?}",
+            "data-api"
+        ));
+    }
+    let target = &request.target;
+    let credentials = credentials(config, &target.secret_arn).await?;
+    let mut endpoint = endpoint(config, &target.resource_arn, &credentials).await?;
+    if target.database.is_some() {
+        endpoint.database = target.database.clone();
+    }
+    info!(
+        "Connecting to {} as {}",
+        endpoint.host, credentials.username
+    );
+    let output = if driver == Driver::Postgres {
+        postgres(config, guard, init, request, credentials, endpoint).await?
+    } else {
+        mysql(config, guard, init, request, credentials, endpoint).await?
+    };
+    info!("{:?}", output);
+    Ok(output)
+}
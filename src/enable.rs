@@ -0,0 +1,103 @@
+use crate::{cache::parse_duration, confirm::confirm};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rds::{types::DbCluster, Client};
+use clap::Args;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How long to wait between checks of the cluster.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Enable the Data API on a cluster.
+///
+/// Switches on the cluster's HTTP endpoint, after asking for confirmation
+/// (unless given --yes), and waits until the cluster is available again.
+/// When a query is given, it is then run.
+#[derive(Args, Clone, Debug)]
+pub struct EnableDataApiArgs {
+    /// Give up waiting for the cluster after this long, for example 90s,
+    /// 15m or 1h.
+    #[clap(default_value = "15m", long, value_name = "DURATION", value_parser = parse_duration)]
+    pub timeout: Duration,
+
+    /// SQL query to run once the Data API is enabled.
+    pub query: Option<String>,
+}
+
+fn cluster_id(db_cluster: &DbCluster) -> &str {
+    db_cluster
+        .db_cluster_identifier
+        .as_deref()
+        .unwrap_or_default()
+}
+
+async fn describe(client: &Client, cluster_id: &str) -> Result<DbCluster> {
+    let output = client
+        .describe_db_clusters()
+        .db_cluster_identifier(cluster_id)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to lookup cluster \"{}\": {}", cluster_id, e))?;
+    info!("{:?}", output);
+    output
+        .db_clusters
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No cluster matched \"{}\"", cluster_id))
+}
+
+/// Enable the HTTP endpoint, unless it already is, and wait for the
+/// cluster to be available with it enabled. Returns whether anything was
+/// changed; with `dry_run`, nothing ever is.
+pub async fn enable_data_api(
+    config: &SdkConfig,
+    db_cluster: &DbCluster,
+    args: &EnableDataApiArgs,
+    yes: bool,
+    dry_run: bool,
+) -> Result<bool> {
+    let cluster_id = cluster_id(db_cluster);
+    if db_cluster.http_endpoint_enabled == Some(true) {
+        eprintln!("The Data API is already enabled for {}", cluster_id);
+        return Ok(false);
+    }
+    if dry_run {
+        println!("enable_http_endpoint: {}", cluster_id);
+        return Ok(false);
+    }
+    let question = format!("Enable the Data API for {}?", cluster_id);
+    if !yes && !confirm(&question)? {
+        return Err(anyhow!("Not enabling the Data API for \"{}\"", cluster_id));
+    }
+    let client = Client::new(config);
+    let output = client
+        .modify_db_cluster()
+        .db_cluster_identifier(cluster_id)
+        .enable_http_endpoint(true)
+        .apply_immediately(true)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to modify cluster \"{}\": {}", cluster_id, e))?;
+    info!("{:?}", output);
+    let started = Instant::now();
+    loop {
+        let db_cluster = describe(&client, cluster_id).await?;
+        let status = db_cluster.status.as_deref().unwrap_or_default();
+        if status == "available" && db_cluster.http_endpoint_enabled == Some(true) {
+            eprintln!("The Data API is enabled for {}", cluster_id);
+            return Ok(true);
+        }
+        if started.elapsed() >= args.timeout {
+            return Err(anyhow!(
+                "Cluster \"{}\" is still {} after {:?}",
+                cluster_id,
+                status,
+                args.timeout
+            ));
+        }
+        info!("Cluster {} is {}", cluster_id, status);
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,85 @@
+use crate::audit::now_timestamp;
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_secretsmanager::{types::Tag, Client};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Tagged on every secret we create, so any left behind by a run that
+/// was killed can be found and cleaned up.
+pub const EPHEMERAL_TAG: &str = "query-rds-data:ephemeral";
+
+/// A Secrets Manager secret holding credentials given on the command line,
+/// for the Data API to use. Always `delete` it once finished.
+pub struct EphemeralSecret {
+    client: Client,
+    arn: String,
+}
+
+impl EphemeralSecret {
+    pub async fn create(
+        config: &SdkConfig,
+        cluster_id: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<EphemeralSecret> {
+        let client = Client::new(config);
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let name = format!(
+            "query-rds-data/ephemeral/{}/{}-{}",
+            cluster_id,
+            seconds,
+            std::process::id()
+        );
+        let secret_string = serde_json::json!({
+            "username": username,
+            "password": password,
+        });
+        let output = client
+            .create_secret()
+            .name(&name)
+            .description("Temporary credentials for query-rds-data, deleted after use")
+            .secret_string(secret_string.to_string())
+            .tags(Tag::builder().key(EPHEMERAL_TAG).value("true").build())
+            .tags(
+                Tag::builder()
+                    .key("query-rds-data:created")
+                    .value(now_timestamp())
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to create secret \"{}\": {}", name, e))?;
+        let arn = output
+            .arn
+            .ok_or_else(|| anyhow!("Secrets Manager returned no ARN for \"{}\"", name))?;
+        info!("Created secret {}", arn);
+        Ok(EphemeralSecret { client, arn })
+    }
+
+    pub fn arn(&self) -> &str {
+        &self.arn
+    }
+
+    /// Delete the secret at once, without the usual recovery window.
+    /// Failing to do so is logged, since the run itself is finished.
+    pub async fn delete(self) {
+        let deleted = self
+            .client
+            .delete_secret()
+            .secret_id(&self.arn)
+            .force_delete_without_recovery(true)
+            .send()
+            .await;
+        match deleted {
+            Ok(output) => info!("{:?}", output),
+            Err(e) => warn!(
+                "Failed to delete secret {}, it is tagged {}: {}",
+                self.arn, EPHEMERAL_TAG, e
+            ),
+        }
+    }
+}
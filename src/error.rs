@@ -0,0 +1,47 @@
+use std::fmt;
+
+/// Failures that callers may want to tell apart, and that deserve more
+/// explanation than the SDK gives. Everything else is a plain `anyhow`
+/// error; these can be found with `downcast_ref`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The cluster's HTTP endpoint, which the Data API goes through, is off.
+    DataApiNotEnabled { resource_arn: String },
+    /// A statement updated other than the expected number of records, or
+    /// fewer than the `minimum`.
+    UnexpectedUpdates {
+        expected: i64,
+        minimum: bool,
+        updated: i64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DataApiNotEnabled { resource_arn } => {
+                let cluster_id = resource_arn.rsplit(':').next().unwrap_or(resource_arn);
+                write!(
+                    f,
+                    "The Data API is not enabled for {}. Enable its HTTP endpoint with:\n  \
+                     aws rds modify-db-cluster --db-cluster-identifier {} \
+                     --enable-http-endpoint --apply-immediately",
+                    resource_arn, cluster_id
+                )
+            }
+            Error::UnexpectedUpdates {
+                expected,
+                minimum,
+                updated,
+            } => write!(
+                f,
+                "Expected {}{} records updated, but {} were",
+                if *minimum { "at least " } else { "" },
+                expected,
+                updated
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,81 @@
+use crate::{fanout, format_value, transform::column_indexes};
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use tokio::process::Command;
+
+/// A part of an `--exec` command: text as given, or a column's value.
+enum Piece<'a> {
+    Text(&'a str),
+    Column(usize),
+}
+
+fn parse<'a>(command: &'a str, output: &ExecuteStatementOutput) -> Result<Vec<Piece<'a>>> {
+    let mut pieces = Vec::new();
+    let mut rest = command;
+    while let Some(start) = rest.find('{') {
+        pieces.push(Piece::Text(&rest[..start]));
+        let after_open = &rest[start + 1..];
+        let end = after_open
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed \"{{\" in command \"{}\"", command))?;
+        let index = column_indexes(output, &[&after_open[..end]])?[0];
+        pieces.push(Piece::Column(index));
+        rest = &after_open[end + 1..];
+    }
+    pieces.push(Piece::Text(rest));
+    Ok(pieces)
+}
+
+/// Quote the text as a single shell word.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn render(pieces: &[Piece], record: &[Field]) -> String {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => (*text).to_owned(),
+            Piece::Column(index) => {
+                shell_quote(&record.get(*index).map(format_value).unwrap_or_default())
+            }
+        })
+        .collect()
+}
+
+/// Run the command with `sh`, reporting how it failed, if it did.
+async fn run(command: String) -> bool {
+    match Command::new("sh").arg("-c").arg(&command).status().await {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            eprintln!("{}: {}", command, status);
+            false
+        }
+        Err(e) => {
+            eprintln!("Failed to run {}: {}", command, e);
+            false
+        }
+    }
+}
+
+/// Run the command once for each row, with each `{column}` replaced by that
+/// row's value, quoted for the shell. Every row is tried, even after a
+/// command fails.
+pub async fn each_row(
+    command: &str,
+    concurrency: u32,
+    output: &ExecuteStatementOutput,
+) -> Result<()> {
+    let pieces = parse(command, output)?;
+    let records = output.records.as_deref().unwrap_or(&[]);
+    let succeeded = fanout::concurrently(
+        concurrency,
+        records.iter().map(|record| run(render(&pieces, record))),
+    )
+    .await;
+    let failed = succeeded.iter().filter(|succeeded| !**succeeded).count();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} commands failed", failed, succeeded.len()));
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::{
+    client::{AwsClient, Client},
+    field_value, format_header, get_arns_with,
+    params::Param,
+    Request, Target,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::operation::execute_statement::ExecuteStatementOutput;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// Which database to connect to. As on the command line, the cluster and
+/// user may be left out when there is only one to choose from.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub sdk_config: SdkConfig,
+    pub cluster_id: Option<String>,
+    pub user_id: Option<String>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+}
+
+impl Config {
+    pub fn new(sdk_config: SdkConfig) -> Config {
+        Config {
+            sdk_config,
+            cluster_id: None,
+            user_id: None,
+            database: None,
+            schema: None,
+        }
+    }
+}
+
+/// The result of a statement, with each value converted to JSON.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    pub number_of_records_updated: i64,
+}
+
+impl ResultSet {
+    /// Each row as a `T`, with columns matched to fields by label. When a
+    /// label is repeated, the last column with it wins.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let object: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().cloned())
+                    .collect();
+                serde_json::from_value(Value::Object(object))
+                    .map_err(|e| anyhow!("Failed to deserialize row {}: {}", index + 1, e))
+            })
+            .collect()
+    }
+}
+
+impl From<&ExecuteStatementOutput> for ResultSet {
+    fn from(output: &ExecuteStatementOutput) -> Self {
+        ResultSet {
+            columns: format_header(output).map(str::to_owned).collect(),
+            rows: output
+                .records
+                .iter()
+                .flatten()
+                .map(|record| record.iter().map(field_value).collect())
+                .collect(),
+            number_of_records_updated: output.number_of_records_updated,
+        }
+    }
+}
+
+/// Runs statements against one database, found once when connecting.
+#[derive(Clone)]
+pub struct QueryExecutor {
+    client: Arc<dyn Client>,
+    target: Target,
+}
+
+impl QueryExecutor {
+    /// Find the cluster and its credentials secret.
+    pub async fn connect(config: Config) -> Result<QueryExecutor> {
+        let client = Arc::new(AwsClient::new(&config.sdk_config));
+        QueryExecutor::connect_with(client, config).await
+    }
+
+    /// Connect through the given client, in place of one built from
+    /// `config.sdk_config`.
+    pub async fn connect_with(client: Arc<dyn Client>, config: Config) -> Result<QueryExecutor> {
+        let arns = get_arns_with(&*client, &config.cluster_id, &config.user_id).await?;
+        Ok(QueryExecutor {
+            client,
+            target: Target::new(arns, config.database, config.schema),
+        })
+    }
+
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+
+    pub async fn execute(&self, sql: &str, params: &[Param]) -> Result<ResultSet> {
+        let request = Request {
+            target: self.target.clone(),
+            params: params.to_vec(),
+            sql: sql.to_owned(),
+        };
+        let output = self.client.execute_statement(request, None).await?;
+        Ok(ResultSet::from(&output))
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{anyhow, Result};
+use similar::TextDiff;
+use std::{fs, path::Path};
+
+/// Compare output with what is expected in the file. When they differ,
+/// write a unified diff from the expected output to the actual output, and
+/// fail.
+pub fn compare(path: &Path, actual: &[u8]) -> Result<()> {
+    let expected =
+        fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    if expected == actual {
+        return Ok(());
+    }
+    print_diff(
+        path,
+        &String::from_utf8_lossy(&expected),
+        &String::from_utf8_lossy(actual),
+    );
+    Err(anyhow!("Output differs from {}", path.display()))
+}
+
+/// Write a unified diff from the expected text, read from `path`, to the
+/// actual text.
+pub fn print_diff(path: &Path, expected: &str, actual: &str) {
+    let expected_name = path.display().to_string();
+    print!(
+        "{}",
+        TextDiff::from_lines(expected, actual)
+            .unified_diff()
+            .header(&expected_name, "actual")
+    );
+}
@@ -0,0 +1,264 @@
+use crate::{
+    dialect::Dialect,
+    format_rows,
+    guard::Guard,
+    pretty,
+    transaction::{Isolation, Transaction},
+    Request, Style,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use clap::Args;
+use serde_json::{Map, Value};
+use std::io::Write;
+
+/// How many of the slowest plan nodes to list after an analyzed plan.
+const SLOWEST: usize = 3;
+
+/// Show how the database would run a query.
+///
+/// On PostgreSQL, the plan is drawn as an indented tree of nodes, with
+/// their estimated costs and rows. On MySQL, it is a table, one row per
+/// table accessed.
+#[derive(Args, Clone, Debug)]
+pub struct ExplainArgs {
+    /// Run the query, to show actual times and rows, and list the slowest
+    /// plan nodes. It runs in a transaction that is rolled back, so that
+    /// data changes are not kept, although MySQL commits DDL regardless.
+    #[clap(long)]
+    pub analyze: bool,
+
+    /// SQL query to explain.
+    pub query: String,
+}
+
+/// The statement that asks the database for its plan.
+pub fn explain_sql(dialect: Dialect, analyze: bool, sql: &str) -> String {
+    match (dialect, analyze) {
+        (Dialect::MySql, false) => format!("EXPLAIN {}", sql),
+        (Dialect::MySql, true) => format!("EXPLAIN ANALYZE {}", sql),
+        (Dialect::Postgres, false) => format!("EXPLAIN (FORMAT JSON) {}", sql),
+        (Dialect::Postgres, true) => format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", sql),
+    }
+}
+
+fn number(plan: &Map<String, Value>, key: &str) -> String {
+    plan.get(key)
+        .map_or_else(|| "?".to_owned(), Value::to_string)
+}
+
+/// What a node does, and to what.
+fn name(plan: &Map<String, Value>) -> String {
+    let text = |key: &str| plan.get(key).and_then(Value::as_str);
+    let mut line = text("Node Type").unwrap_or("?").to_owned();
+    if let Some(strategy) = text("Strategy").filter(|strategy| *strategy != "Plain") {
+        line = format!("{} {}", strategy, line);
+    }
+    if let Some(index) = text("Index Name") {
+        line.push_str(&format!(" using {}", index));
+    }
+    if let Some(relation) = text("Relation Name") {
+        line.push_str(&format!(" on {}", relation));
+        if let Some(alias) = text("Alias").filter(|alias| *alias != relation) {
+            line.push_str(&format!(" {}", alias));
+        }
+    }
+    line
+}
+
+/// One line for a node: its name, estimated cost and, once analyzed,
+/// what it actually took.
+fn describe(plan: &Map<String, Value>) -> String {
+    let mut line = name(plan);
+    line.push_str(&format!(
+        "  (cost={}..{} rows={} width={})",
+        number(plan, "Startup Cost"),
+        number(plan, "Total Cost"),
+        number(plan, "Plan Rows"),
+        number(plan, "Plan Width"),
+    ));
+    if plan.contains_key("Actual Total Time") {
+        line.push_str(&format!(
+            " (actual time={}..{} rows={} loops={})",
+            number(plan, "Actual Startup Time"),
+            number(plan, "Actual Total Time"),
+            number(plan, "Actual Rows"),
+            number(plan, "Actual Loops"),
+        ));
+    }
+    line
+}
+
+fn children(plan: &Map<String, Value>) -> impl Iterator<Item = &Map<String, Value>> {
+    plan.get("Plans")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object)
+}
+
+/// Time spent in a node over every loop, in milliseconds.
+fn total_time(plan: &Map<String, Value>) -> f64 {
+    let field = |key: &str| plan.get(key).and_then(Value::as_f64).unwrap_or_default();
+    field("Actual Total Time") * field("Actual Loops").max(1.0)
+}
+
+/// Each node's own time, leaving out the time of the nodes beneath it.
+fn self_times(plan: &Map<String, Value>, times: &mut Vec<(f64, String)>) {
+    let children_time: f64 = children(plan).map(total_time).sum();
+    times.push(((total_time(plan) - children_time).max(0.0), name(plan)));
+    for child in children(plan) {
+        self_times(child, times);
+    }
+}
+
+fn write_slowest(
+    mut times: Vec<(f64, String)>,
+    style: Style,
+    writer: &mut impl Write,
+) -> Result<()> {
+    times.sort_by(|a, b| b.0.total_cmp(&a.0));
+    writeln!(writer, "{}", style.header("Slowest nodes:"))?;
+    for (time, name) in times.iter().take(SLOWEST) {
+        writeln!(writer, "  {:>10.3} ms  {}", time, name)?;
+    }
+    Ok(())
+}
+
+/// Conditions worth showing beneath a node.
+const CONDITIONS: &[&str] = &[
+    "Index Cond",
+    "Recheck Cond",
+    "Hash Cond",
+    "Merge Cond",
+    "Join Filter",
+    "Filter",
+];
+
+fn write_plan(plan: &Map<String, Value>, depth: usize, writer: &mut impl Write) -> Result<()> {
+    let indent = "      ".repeat(depth);
+    let arrow = if depth == 0 { "" } else { "->  " };
+    writeln!(writer, "{}{}{}", indent, arrow, describe(plan))?;
+    let detail_indent = format!("{}{}", indent, if depth == 0 { "  " } else { "      " });
+    for condition in CONDITIONS {
+        if let Some(value) = plan.get(*condition).and_then(Value::as_str) {
+            writeln!(writer, "{}{}: {}", detail_indent, condition, value)?;
+        }
+    }
+    for child in children(plan) {
+        write_plan(child, depth + 1, writer)?;
+    }
+    Ok(())
+}
+
+/// Draw PostgreSQL's JSON plan, which arrives as one value of one row.
+pub fn postgres_plan(json: &str, style: Style, writer: &mut impl Write) -> Result<()> {
+    let explained: Value =
+        serde_json::from_str(json).map_err(|e| anyhow!("Failed to parse the query plan: {}", e))?;
+    for statement in explained.as_array().into_iter().flatten() {
+        let Some(Value::Object(plan)) = statement.get("Plan") else {
+            return Err(anyhow!("The query plan has no \"Plan\""));
+        };
+        write_plan(plan, 0, writer)?;
+        if let Some(planning_time) = statement.get("Planning Time") {
+            writeln!(writer, "Planning Time: {} ms", planning_time)?;
+        }
+        if let Some(execution_time) = statement.get("Execution Time") {
+            writeln!(writer, "Execution Time: {} ms", execution_time)?;
+            let mut times = Vec::new();
+            self_times(plan, &mut times);
+            write_slowest(times, style, writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// The time of the last loop, and the number of loops, from a line of
+/// MySQL's EXPLAIN ANALYZE tree, like
+/// `-> Table scan on t  (cost=1.2 rows=10) (actual time=0.02..0.05 rows=10 loops=1)`.
+fn mysql_total_time(line: &str) -> Option<f64> {
+    let actual = &line[line.find("(actual time=")? + "(actual time=".len()..];
+    let (_startup, rest) = actual.split_once("..")?;
+    let (total, rest) = rest.split_once(' ')?;
+    let loops = rest
+        .split_once("loops=")
+        .and_then(|(_rows, loops)| loops.trim_end_matches(')').parse::<f64>().ok())
+        .unwrap_or(1.0);
+    Some(total.parse::<f64>().ok()? * loops.max(1.0))
+}
+
+/// Write MySQL's EXPLAIN ANALYZE tree as it is, followed by its slowest
+/// nodes. Each node's time includes the nodes beneath it, which are
+/// indented further.
+fn mysql_analyzed_plan(tree: &str, style: Style, writer: &mut impl Write) -> Result<()> {
+    writeln!(writer, "{}", tree.trim_end())?;
+    let nodes: Vec<(usize, f64, &str)> = tree
+        .lines()
+        .filter_map(|line| {
+            let depth = line.len() - line.trim_start().len();
+            let name = line.trim_start().trim_start_matches("-> ");
+            let name = name.split("  (").next().unwrap_or(name);
+            Some((depth, mysql_total_time(line)?, name))
+        })
+        .collect();
+    let times = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, (depth, time, name))| {
+            let mut descendants = nodes[index + 1..]
+                .iter()
+                .take_while(|(descendant_depth, _time, _name)| descendant_depth > depth)
+                .peekable();
+            let child_depth = descendants
+                .peek()
+                .map(|(child_depth, _time, _name)| *child_depth);
+            let children_time: f64 = descendants
+                .filter(|(descendant_depth, _time, _name)| Some(*descendant_depth) == child_depth)
+                .map(|(_depth, time, _name)| time)
+                .sum();
+            ((time - children_time).max(0.0), (*name).to_owned())
+        })
+        .collect();
+    write_slowest(times, style, writer)
+}
+
+/// Explain the request's SQL, writing the plan out. An analyzed query
+/// runs in a transaction, which is always rolled back.
+pub async fn explain(
+    guard: &Guard,
+    config: &SdkConfig,
+    request: Request,
+    explain_args: &ExplainArgs,
+    isolation: Option<Isolation>,
+    style: Style,
+    mut writer: impl Write,
+) -> Result<()> {
+    let dialect = request.target.dialect();
+    let analyze = explain_args.analyze;
+    let request = Request {
+        sql: explain_sql(dialect, analyze, &request.sql),
+        ..request
+    };
+    let output = if analyze {
+        guard.check(&[(config, &request)], None).await?;
+        let transaction = Transaction::begin(config, &request.target, isolation).await?;
+        let output = guard
+            .execute_in_transaction(config, request, Some(transaction.id()))
+            .await;
+        transaction.rollback().await?;
+        output?
+    } else {
+        guard.execute(config, request).await?
+    };
+    match dialect {
+        Dialect::MySql if analyze => {
+            let tree: String = format_rows(&output).flatten().collect();
+            mysql_analyzed_plan(&tree, style, &mut writer)
+        }
+        Dialect::MySql => pretty::table_output(&output, style, writer),
+        Dialect::Postgres => {
+            let json: String = format_rows(&output).flatten().collect();
+            postgres_plan(&json, style, &mut writer)
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::{
+    aws_sdk_config, cluster_arns, dialect::Dialect, discover, format_header, guard::Guard, MyArgs,
+    Request, Target,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rds::types::DbCluster;
+use aws_sdk_rdsdata::{
+    operation::execute_statement::ExecuteStatementOutput,
+    types::{ColumnMetadata, Field},
+};
+use futures::{future::join_all, prelude::*, stream};
+use tracing::{info, warn};
+
+/// Parse a `--cluster-tag key=value` filter.
+pub fn parse_tag(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(anyhow!("Tag \"{}\" is not of the form key=value", s)),
+    }
+}
+
+fn cluster_matches(db_cluster: &DbCluster, args: &MyArgs) -> bool {
+    let engine_matches = match &args.cluster_engine {
+        Some(engine) => db_cluster.engine.as_ref() == Some(engine),
+        None => true,
+    };
+    let tags = db_cluster.tag_list.as_deref().unwrap_or(&[]);
+    engine_matches
+        && args.cluster_tags.iter().all(|(key, value)| {
+            tags.iter().any(|tag| {
+                tag.key.as_ref() == Some(key) && tag.value.as_deref().unwrap_or("") == value
+            })
+        })
+}
+
+/// Whether the query runs in more than one region.
+pub fn multi_region(args: &MyArgs) -> bool {
+    args.all_regions || args.region.len() > 1
+}
+
+/// Regions where RDS is available, as listed by the default region.
+async fn all_regions(config: &SdkConfig) -> Result<Vec<String>> {
+    let rds_client = aws_sdk_rds::Client::new(config);
+    let source_regions = rds_client
+        .describe_source_regions()
+        .into_paginator()
+        .items()
+        .send()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("Failed to lookup regions: {}", e))?;
+    let mut regions: Vec<String> = config
+        .region()
+        .map(ToString::to_string)
+        .into_iter()
+        .collect();
+    for source_region in source_regions {
+        if source_region.status.as_deref() != Some("available") {
+            continue;
+        }
+        if let Some(region_name) = source_region.region_name {
+            if !regions.contains(&region_name) {
+                regions.push(region_name);
+            }
+        }
+    }
+    info!("Regions {:?}", regions);
+    Ok(regions)
+}
+
+/// One SDK configuration per region to run in.
+pub async fn region_configs(args: &MyArgs, config: &SdkConfig) -> Result<Vec<SdkConfig>> {
+    let regions = if args.all_regions {
+        all_regions(config).await?
+    } else {
+        args.region.clone()
+    };
+    Ok(join_all(
+        regions
+            .iter()
+            .map(|region| aws_sdk_config(args, Some(region))),
+    )
+    .await)
+}
+
+/// Every cluster passing the filters, labelled by cluster identifier.
+/// Clusters without a usable secret are skipped with a warning.
+pub async fn all_cluster_targets(
+    args: &MyArgs,
+    config: &SdkConfig,
+) -> Result<Vec<(String, Target)>> {
+    let (db_clusters, secret_list) = discover(config).await?;
+    let mut targets = Vec::new();
+    for db_cluster in db_clusters {
+        if !cluster_matches(&db_cluster, args) {
+            continue;
+        }
+        let cluster_id = db_cluster.db_cluster_identifier.clone().unwrap_or_default();
+        match cluster_arns(db_cluster, &args.user_id, &secret_list) {
+            Ok(arns) => targets.push((
+                cluster_id,
+                Target::new(arns, args.database.clone(), args.schema.clone()),
+            )),
+            Err(e) => warn!("Skipping cluster {}: {}", cluster_id, e),
+        }
+    }
+    if targets.is_empty() {
+        return Err(anyhow!("No clusters matched"));
+    }
+    Ok(targets)
+}
+
+/// Whether the query runs in more than one database of each cluster.
+pub fn multi_database(args: &MyArgs) -> bool {
+    args.all_databases || !args.databases.is_empty()
+}
+
+/// The databases users made on the target's cluster, leaving out those
+/// the engine keeps for itself.
+async fn all_databases(guard: &Guard, config: &SdkConfig, target: &Target) -> Result<Vec<String>> {
+    let sql = match target.dialect() {
+        Dialect::Postgres => {
+            "SELECT datname FROM pg_database \
+             WHERE datallowconn AND NOT datistemplate ORDER BY datname"
+        }
+        Dialect::MySql => {
+            "SELECT schema_name FROM information_schema.schemata \
+             WHERE schema_name NOT IN ('information_schema', 'mysql', 'performance_schema', 'sys') \
+             ORDER BY schema_name"
+        }
+    };
+    let request = Request {
+        target: target.clone(),
+        params: Vec::new(),
+        sql: sql.to_owned(),
+    };
+    let output = guard
+        .execute(config, request)
+        .await
+        .map_err(|e| anyhow!("Failed to list databases: {}", e))?;
+    let databases: Vec<String> = output
+        .records
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|record| match record.into_iter().next() {
+            Some(Field::StringValue(database)) => Some(database),
+            _ => None,
+        })
+        .collect();
+    info!("Databases {:?}", databases);
+    Ok(databases)
+}
+
+/// The targets again, once for each database to run in.
+pub async fn database_targets(
+    args: &MyArgs,
+    guard: &Guard,
+    config: &SdkConfig,
+    targets: Vec<(String, Target)>,
+) -> Result<Vec<(String, Target)>> {
+    let mut database_targets = Vec::new();
+    for (cluster_id, target) in targets {
+        let databases = if args.all_databases {
+            all_databases(guard, config, &target).await?
+        } else {
+            args.databases.clone()
+        };
+        for database in databases {
+            database_targets.push((
+                cluster_id.clone(),
+                Target {
+                    database: Some(database),
+                    ..target.clone()
+                },
+            ));
+        }
+    }
+    if database_targets.is_empty() {
+        return Err(anyhow!("No databases to run in"));
+    }
+    Ok(database_targets)
+}
+
+/// Await every future, at most `concurrency` at a time, returning their
+/// outputs in the order the futures were given.
+pub async fn concurrently<F: Future>(
+    concurrency: u32,
+    futures: impl IntoIterator<Item = F>,
+) -> Vec<F::Output> {
+    let mut outputs: Vec<(usize, F::Output)> = stream::iter(futures.into_iter().enumerate())
+        .map(|(index, future)| future.map(move |output| (index, output)))
+        .buffer_unordered(concurrency as usize)
+        .collect()
+        .await;
+    outputs.sort_by_key(|(index, _output)| *index);
+    outputs.into_iter().map(|(_index, output)| output).collect()
+}
+
+/// Combine results from several runs of one query into a single result,
+/// with leading columns holding each row's labels. Every run must have
+/// returned the same columns.
+pub fn merge_results(
+    columns: &[&str],
+    results: Vec<(Vec<String>, ExecuteStatementOutput)>,
+) -> Result<ExecuteStatementOutput> {
+    let mut header: Option<Vec<String>> = None;
+    let mut column_metadata = None;
+    let mut records = Vec::new();
+    let mut number_of_records_updated = 0;
+    for (labels, result) in results {
+        let result_header: Vec<String> = format_header(&result).map(str::to_owned).collect();
+        match &header {
+            None => {
+                column_metadata = result.column_metadata.as_ref().map(|result_columns| {
+                    columns
+                        .iter()
+                        .map(|column| {
+                            ColumnMetadata::builder()
+                                .name(*column)
+                                .label(*column)
+                                .type_name("varchar")
+                                .build()
+                        })
+                        .chain(result_columns.iter().cloned())
+                        .collect::<Vec<_>>()
+                });
+                header = Some(result_header);
+            }
+            Some(known) if *known == result_header => {}
+            Some(known) => {
+                return Err(anyhow!(
+                    "{} returned columns {:?}, unlike {:?}",
+                    labels.join("/"),
+                    result_header,
+                    known,
+                ))
+            }
+        }
+        number_of_records_updated += result.number_of_records_updated;
+        for record in result.records.unwrap_or_default() {
+            records.push(
+                labels
+                    .iter()
+                    .map(|label| Field::StringValue(label.clone()))
+                    .chain(record)
+                    .collect(),
+            );
+        }
+    }
+    Ok(ExecuteStatementOutput::builder()
+        .set_column_metadata(column_metadata)
+        .set_records(Some(records))
+        .number_of_records_updated(number_of_records_updated)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_rds::types::Tag;
+    use clap::Parser;
+    #[test]
+    fn parses_tags() {
+        assert_eq!(
+            parse_tag("env=prod").unwrap(),
+            ("env".to_owned(), "prod".to_owned())
+        );
+        assert_eq!(
+            parse_tag("note=a=b").unwrap(),
+            ("note".to_owned(), "a=b".to_owned())
+        );
+        assert_eq!(
+            parse_tag("empty=").unwrap(),
+            ("empty".to_owned(), String::new())
+        );
+        for bad in ["env", "=prod"] {
+            assert_eq!(
+                parse_tag(bad).unwrap_err().to_string(),
+                format!("Tag \"{}\" is not of the form key=value", bad)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_clusters_by_engine_and_tags() {
+        let args = MyArgs::parse_from([
+            "query-rds-data",
+            "--all-clusters",
+            "--cluster-engine",
+            "aurora-postgresql",
+            "--cluster-tag",
+            "env=prod",
+            "select 1",
+        ]);
+        let cluster = |engine: &str, tags: &[(&str, &str)]| {
+            DbCluster::builder()
+                .engine(engine)
+                .set_tag_list(Some(
+                    tags.iter()
+                        .map(|(key, value)| Tag::builder().key(*key).value(*value).build())
+                        .collect(),
+                ))
+                .build()
+        };
+        assert!(cluster_matches(
+            &cluster("aurora-postgresql", &[("team", "a"), ("env", "prod")]),
+            &args
+        ));
+        assert!(!cluster_matches(
+            &cluster("aurora-mysql", &[("env", "prod")]),
+            &args
+        ));
+        assert!(!cluster_matches(
+            &cluster("aurora-postgresql", &[("env", "dev")]),
+            &args
+        ));
+        assert!(!cluster_matches(&cluster("aurora-postgresql", &[]), &args));
+    }
+}
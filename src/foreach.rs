@@ -0,0 +1,64 @@
+use crate::{
+    import::open_input,
+    params::{Param, ParamType},
+};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::path::Path;
+
+/// The parameter sets for --foreach, one JSON object per line, with the
+/// parameter names, from the first object's keys. Every object must have
+/// the same keys, since each becomes a column of the results, in the
+/// order of their names.
+pub fn read(path: &Path) -> Result<(Vec<String>, Vec<Vec<Param>>)> {
+    let values = serde_json::Deserializer::from_reader(open_input(path)?).into_iter::<Value>();
+    let mut names: Option<Vec<String>> = None;
+    let mut parameter_sets = Vec::new();
+    for (index, value) in values.enumerate() {
+        let row = index + 1;
+        let value = value.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let Value::Object(object) = value else {
+            return Err(anyhow!("Row {} is not a JSON object", row));
+        };
+        let keys: Vec<String> = object.keys().cloned().collect();
+        match &names {
+            None => names = Some(keys),
+            Some(names) if *names == keys => {}
+            Some(names) => {
+                return Err(anyhow!(
+                    "Row {} has parameters {:?}, unlike {:?}",
+                    row,
+                    keys,
+                    names
+                ))
+            }
+        }
+        let parameter_set = object
+            .into_iter()
+            .map(|(name, value)| {
+                let (param_type, value) = match value {
+                    Value::String(string) => (ParamType::String, string),
+                    Value::Bool(boolean) => (ParamType::Boolean, boolean.to_string()),
+                    Value::Number(number) if number.is_i64() => {
+                        (ParamType::Long, number.to_string())
+                    }
+                    Value::Number(number) => (ParamType::Double, number.to_string()),
+                    Value::Null => {
+                        return Err(anyhow!("Row {} parameter \"{}\" is null", row, name))
+                    }
+                    nested => (ParamType::Json, nested.to_string()),
+                };
+                Ok(Param {
+                    name,
+                    param_type,
+                    value,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        parameter_sets.push(parameter_set);
+    }
+    match names {
+        Some(names) => Ok((names, parameter_sets)),
+        None => Err(anyhow!("No parameter sets found in {}", path.display())),
+    }
+}
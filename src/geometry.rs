@@ -0,0 +1,347 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde_json::{json, Number, Value};
+
+/// How to write geometry values.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Geometry {
+    /// Well-known text, like `POINT(1 2)`.
+    Wkt,
+    /// A GeoJSON geometry object.
+    Geojson,
+}
+
+/// Whether a column of this type holds PostGIS geometries, which arrive as
+/// hex WKB.
+pub fn is_geometry(type_name: &str) -> bool {
+    ["geometry", "geography"].contains(&type_name.to_lowercase().as_str())
+}
+
+/// A geometry read from WKB: its kind, and whatever that holds.
+enum Shape {
+    Point(Vec<f64>),
+    LineString(Vec<Vec<f64>>),
+    Polygon(Vec<Vec<Vec<f64>>>),
+    MultiPoint(Vec<Shape>),
+    MultiLineString(Vec<Shape>),
+    MultiPolygon(Vec<Shape>),
+    GeometryCollection(Vec<Shape>),
+}
+
+/// Reads WKB, or the EWKB PostGIS returns, which may carry an SRID.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (taken, rest) = self.bytes.split_first_chunk::<N>()?;
+        self.bytes = rest;
+        Some(*taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.take()?;
+        Some(match self.little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        let bytes = self.take()?;
+        Some(match self.little_endian {
+            true => f64::from_le_bytes(bytes),
+            false => f64::from_be_bytes(bytes),
+        })
+    }
+
+    fn point(&mut self, dimensions: usize) -> Option<Vec<f64>> {
+        (0..dimensions).map(|_dimension| self.f64()).collect()
+    }
+
+    fn points(&mut self, dimensions: usize) -> Option<Vec<Vec<f64>>> {
+        (0..self.u32()?)
+            .map(|_point| self.point(dimensions))
+            .collect()
+    }
+
+    fn shapes(&mut self) -> Option<Vec<Shape>> {
+        (0..self.u32()?).map(|_shape| self.shape()).collect()
+    }
+
+    /// A geometry, and the `Z`, `M` or `ZM` its coordinates have.
+    fn shape_with_dimensions(&mut self) -> Option<(Shape, &'static str)> {
+        let [byte_order] = self.take()?;
+        self.little_endian = byte_order == 1;
+        let code = self.u32()?;
+        if code & 0x2000_0000 != 0 {
+            let _srid = self.u32()?;
+        }
+        let kind = code & 0x0fff_ffff;
+        // EWKB flags Z and M in the high bits, ISO WKB adds 1000s to the type.
+        let (z, m) = match kind / 1000 {
+            1 => (true, false),
+            2 => (false, true),
+            3 => (true, true),
+            _ => (code & 0x8000_0000 != 0, code & 0x4000_0000 != 0),
+        };
+        let dimensions = 2 + usize::from(z) + usize::from(m);
+        let shape = match kind % 1000 {
+            1 => Shape::Point(self.point(dimensions)?),
+            2 => Shape::LineString(self.points(dimensions)?),
+            3 => Shape::Polygon(
+                (0..self.u32()?)
+                    .map(|_ring| self.points(dimensions))
+                    .collect::<Option<_>>()?,
+            ),
+            4 => Shape::MultiPoint(self.shapes()?),
+            5 => Shape::MultiLineString(self.shapes()?),
+            6 => Shape::MultiPolygon(self.shapes()?),
+            7 => Shape::GeometryCollection(self.shapes()?),
+            _ => return None,
+        };
+        let tag = match (z, m) {
+            (true, true) => "ZM",
+            (true, false) => "Z",
+            (false, true) => "M",
+            (false, false) => "",
+        };
+        Some((shape, tag))
+    }
+
+    fn shape(&mut self) -> Option<Shape> {
+        self.shape_with_dimensions().map(|(shape, _tag)| shape)
+    }
+}
+
+/// An empty point has NaN coordinates.
+fn is_empty_point(point: &[f64]) -> bool {
+    point.iter().all(|coordinate| coordinate.is_nan())
+}
+
+fn wkt_point(point: &[f64]) -> String {
+    let coordinates: Vec<String> = point.iter().map(f64::to_string).collect();
+    coordinates.join(" ")
+}
+
+fn wkt_list<T>(items: &[T], item: impl Fn(&T) -> String) -> String {
+    match items {
+        [] => "EMPTY".to_owned(),
+        items => {
+            let items: Vec<String> = items.iter().map(item).collect();
+            format!("({})", items.join(","))
+        }
+    }
+}
+
+fn wkt_points(points: &[Vec<f64>]) -> String {
+    wkt_list(points, |point| wkt_point(point))
+}
+
+/// The WKT of a shape's contents, after its type name.
+fn wkt_body(shape: &Shape, tag: &str) -> String {
+    match shape {
+        Shape::Point(point) if is_empty_point(point) => "EMPTY".to_owned(),
+        Shape::Point(point) => format!("({})", wkt_point(point)),
+        Shape::LineString(points) => wkt_points(points),
+        Shape::Polygon(rings) => wkt_list(rings, |ring| wkt_points(ring)),
+        Shape::MultiPoint(shapes)
+        | Shape::MultiLineString(shapes)
+        | Shape::MultiPolygon(shapes) => wkt_list(shapes, |shape| wkt_body(shape, tag)),
+        Shape::GeometryCollection(shapes) => wkt_list(shapes, |shape| wkt(shape, tag)),
+    }
+}
+
+fn wkt(shape: &Shape, tag: &str) -> String {
+    let name = match shape {
+        Shape::Point(_) => "POINT",
+        Shape::LineString(_) => "LINESTRING",
+        Shape::Polygon(_) => "POLYGON",
+        Shape::MultiPoint(_) => "MULTIPOINT",
+        Shape::MultiLineString(_) => "MULTILINESTRING",
+        Shape::MultiPolygon(_) => "MULTIPOLYGON",
+        Shape::GeometryCollection(_) => "GEOMETRYCOLLECTION",
+    };
+    let body = wkt_body(shape, tag);
+    match tag {
+        // As PostGIS writes them: `POINT(1 2)`, but `POINT EMPTY`.
+        "" if body.starts_with('(') => format!("{}{}", name, body),
+        "" => format!("{} {}", name, body),
+        tag => format!("{} {} {}", name, tag, body),
+    }
+}
+
+/// GeoJSON positions have no M, so only x, y and any z are kept.
+fn geojson_point(point: &[f64], has_z: bool) -> Value {
+    if is_empty_point(point) {
+        return json!([]);
+    }
+    let kept = if has_z { 3 } else { 2 };
+    point
+        .iter()
+        .take(kept)
+        .map(|coordinate| Number::from_f64(*coordinate).map_or(Value::Null, Value::Number))
+        .collect()
+}
+
+fn geojson_points(points: &[Vec<f64>], has_z: bool) -> Value {
+    points
+        .iter()
+        .map(|point| geojson_point(point, has_z))
+        .collect()
+}
+
+/// A shape's GeoJSON coordinates.
+fn geojson_coordinates(shape: &Shape, has_z: bool) -> Value {
+    match shape {
+        Shape::Point(point) => geojson_point(point, has_z),
+        Shape::LineString(points) => geojson_points(points, has_z),
+        Shape::Polygon(rings) => rings
+            .iter()
+            .map(|ring| geojson_points(ring, has_z))
+            .collect(),
+        Shape::MultiPoint(shapes)
+        | Shape::MultiLineString(shapes)
+        | Shape::MultiPolygon(shapes)
+        | Shape::GeometryCollection(shapes) => shapes
+            .iter()
+            .map(|shape| geojson_coordinates(shape, has_z))
+            .collect(),
+    }
+}
+
+fn geojson(shape: &Shape, has_z: bool) -> Value {
+    let kind = match shape {
+        Shape::Point(_) => "Point",
+        Shape::LineString(_) => "LineString",
+        Shape::Polygon(_) => "Polygon",
+        Shape::MultiPoint(_) => "MultiPoint",
+        Shape::MultiLineString(_) => "MultiLineString",
+        Shape::MultiPolygon(_) => "MultiPolygon",
+        Shape::GeometryCollection(shapes) => {
+            let geometries: Vec<Value> = shapes.iter().map(|shape| geojson(shape, has_z)).collect();
+            return json!({"type": "GeometryCollection", "geometries": geometries});
+        }
+    };
+    json!({"type": kind, "coordinates": geojson_coordinates(shape, has_z)})
+}
+
+/// Convert a geometry from hex WKB into WKT, or GeoJSON text.
+pub fn convert(hex: &str, geometry: Geometry) -> Result<String> {
+    let invalid = || anyhow!("Geometry value \"{}\" is not hex WKB", hex);
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid)?;
+    let mut reader = Reader {
+        bytes: &bytes,
+        little_endian: true,
+    };
+    let (shape, tag) = reader.shape_with_dimensions().ok_or_else(invalid)?;
+    if !reader.bytes.is_empty() {
+        return Err(invalid());
+    }
+    Ok(match geometry {
+        Geometry::Wkt => wkt(&shape, tag),
+        Geometry::Geojson => geojson(&shape, tag.starts_with('Z')).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: &str = "0101000000000000000000F03F0000000000000040";
+
+    fn both(hex: &str) -> (String, String) {
+        (
+            convert(hex, Geometry::Wkt).unwrap(),
+            convert(hex, Geometry::Geojson).unwrap(),
+        )
+    }
+
+    #[test]
+    fn converts_points() {
+        let point = (
+            "POINT(1 2)".to_owned(),
+            r#"{"type":"Point","coordinates":[1.0,2.0]}"#.to_owned(),
+        );
+        assert_eq!(both(POINT), point);
+        // With an SRID, as PostGIS sends, and big-endian.
+        assert_eq!(
+            both("0101000020E6100000000000000000F03F0000000000000040"),
+            point
+        );
+        assert_eq!(both("00000000013FF00000000000004000000000000000"), point);
+        assert_eq!(
+            both("01E9030000000000000000F03F00000000000000400000000000000840"),
+            (
+                "POINT Z (1 2 3)".to_owned(),
+                r#"{"type":"Point","coordinates":[1.0,2.0,3.0]}"#.to_owned()
+            )
+        );
+        assert_eq!(
+            both("0101000000000000000000F87F000000000000F87F"),
+            (
+                "POINT EMPTY".to_owned(),
+                r#"{"type":"Point","coordinates":[]}"#.to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn converts_collections() {
+        assert_eq!(
+            convert(
+                "01020000000200000000000000000000000000000000000000000000000000F03F000000000000F03F",
+                Geometry::Wkt
+            )
+            .unwrap(),
+            "LINESTRING(0 0,1 1)"
+        );
+        assert_eq!(
+            convert(&format!("010400000001000000{}", POINT), Geometry::Wkt).unwrap(),
+            "MULTIPOINT((1 2))"
+        );
+        assert_eq!(
+            convert(
+                &format!(
+                    "010700000002000000{}01020000000200000000000000000000000000000000000000000000000000F03F000000000000F03F",
+                    POINT
+                ),
+                Geometry::Geojson
+            )
+            .unwrap(),
+            r#"{"type":"GeometryCollection","geometries":[{"type":"Point","coordinates":[1.0,2.0]},{"type":"LineString","coordinates":[[0.0,0.0],[1.0,1.0]]}]}"#
+        );
+    }
+
+    #[test]
+    fn refuses_what_is_not_hex_wkb() {
+        for hex in [
+            "zz",
+            "010",
+            &POINT[..20],
+            &format!("{}00", POINT),
+            "0108000000",
+        ] {
+            assert_eq!(
+                convert(hex, Geometry::Wkt).unwrap_err().to_string(),
+                format!("Geometry value \"{}\" is not hex WKB", hex)
+            );
+        }
+    }
+
+    #[test]
+    fn recognizes_geometry_types() {
+        assert!(is_geometry("geometry"));
+        assert!(is_geometry("GEOGRAPHY"));
+        assert!(!is_geometry("text"));
+    }
+}
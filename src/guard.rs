@@ -0,0 +1,195 @@
+use crate::{
+    audit::{self, AuditEntry, AuditLog},
+    confirm, execute_in_transaction, fanout,
+    policy::{self, PolicyRule},
+    progress::Progress,
+    Param, Request,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::operation::{
+    batch_execute_statement::BatchExecuteStatementOutput, execute_statement::ExecuteStatementOutput,
+};
+use futures::Future;
+use std::{collections::HashSet, sync::Mutex, time::Instant};
+
+/// Where every statement passes on its way to the database, whichever
+/// subcommand sent it. Nothing runs that the policy refuses, or, unless
+/// --yes, that destroys data without asking, and whatever runs is written
+/// to the audit log.
+pub struct Guard {
+    rules: Vec<PolicyRule>,
+    profile: Option<String>,
+    concurrency: u32,
+    ask: bool,
+    audit_log: Option<Mutex<AuditLog>>,
+    /// The cluster, database and SQL of each request already checked.
+    checked: Mutex<HashSet<(String, Option<String>, String)>>,
+}
+
+/// What an execution did, for its audit log entry.
+pub trait Outcome {
+    fn note(&self, audit_entry: &mut AuditEntry);
+}
+
+impl Outcome for ExecuteStatementOutput {
+    fn note(&self, audit_entry: &mut AuditEntry) {
+        audit_entry.records_updated = Some(self.number_of_records_updated);
+        audit_entry.rows = self.records.as_ref().map(Vec::len);
+    }
+}
+
+impl Outcome for BatchExecuteStatementOutput {
+    /// The Data API does not say how many records a batch updated.
+    fn note(&self, _audit_entry: &mut AuditEntry) {}
+}
+
+fn key(request: &Request) -> (String, Option<String>, String) {
+    (
+        request.target.resource_arn.clone(),
+        request.target.database.clone(),
+        request.sql.clone(),
+    )
+}
+
+impl Guard {
+    /// Check statements against the policy rules, as the given profile,
+    /// looking up at most `concurrency` clusters' tags at once, `ask`
+    /// before destructive ones, and write those executed to the audit log,
+    /// if any.
+    pub fn new(
+        rules: Vec<PolicyRule>,
+        profile: Option<String>,
+        concurrency: u32,
+        ask: bool,
+        audit_log: Option<AuditLog>,
+    ) -> Guard {
+        Guard {
+            rules,
+            profile,
+            concurrency,
+            ask,
+            audit_log: audit_log.map(Mutex::new),
+            checked: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn is_checked(&self, request: &Request) -> bool {
+        self.checked
+            .lock()
+            .is_ok_and(|checked| checked.contains(&key(request)))
+    }
+
+    /// Check the requests against the policy, each with the configuration
+    /// of its region, before any of them runs, then ask about those that
+    /// are destructive, once for each statement, hiding the progress
+    /// spinner meanwhile. Requests already checked are not checked again.
+    pub async fn check(
+        &self,
+        requests: &[(&SdkConfig, &Request)],
+        progress: Option<&Progress>,
+    ) -> Result<()> {
+        let unchecked: Vec<&(&SdkConfig, &Request)> = requests
+            .iter()
+            .filter(|(_config, request)| !self.is_checked(request))
+            .collect();
+        if unchecked.is_empty() {
+            return Ok(());
+        }
+        if !self.rules.is_empty() {
+            fanout::concurrently(
+                self.concurrency,
+                unchecked.iter().map(|(config, request)| {
+                    policy::check(config, &self.rules, self.profile.as_deref(), request)
+                }),
+            )
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        }
+        if self.ask {
+            let mut statements: Vec<Vec<&Request>> = Vec::new();
+            for (_config, request) in &unchecked {
+                match statements
+                    .iter_mut()
+                    .find(|same| same[0].sql == request.sql)
+                {
+                    Some(same) => same.push(request),
+                    None => statements.push(vec![request]),
+                }
+            }
+            let confirm = || {
+                statements
+                    .into_iter()
+                    .try_for_each(confirm::destructive_statement)
+            };
+            match progress {
+                Some(progress) => progress.suspend(confirm)?,
+                None => confirm()?,
+            }
+        }
+        let mut checked = self
+            .checked
+            .lock()
+            .map_err(|_e| anyhow!("A statement check panicked"))?;
+        checked.extend(unchecked.iter().map(|(_config, request)| key(request)));
+        Ok(())
+    }
+
+    /// Check the request, and only then await its execution, noting what
+    /// happened in the audit log.
+    pub async fn run<T: Outcome>(
+        &self,
+        config: &SdkConfig,
+        request: &Request,
+        execution: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        self.check(&[(config, request)], None).await?;
+        let Some(audit_log) = &self.audit_log else {
+            return execution.await;
+        };
+        let mut audit_entry = AuditEntry {
+            timestamp: audit::now_timestamp(),
+            profile: self.profile.clone(),
+            region: config.region().map(|region| region.to_string()),
+            cluster: request.target.resource_arn.clone(),
+            secret: request.target.secret_arn.clone(),
+            engine: request.target.engine.clone(),
+            database: request.target.database.clone(),
+            schema: request.target.schema.clone(),
+            sql: Some(request.sql.clone()),
+            params: request.params.iter().map(Param::to_string).collect(),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let result = execution.await;
+        audit_entry.duration_seconds = started.elapsed().as_secs_f64();
+        match &result {
+            Ok(output) => output.note(&mut audit_entry),
+            Err(e) => audit_entry.error = Some(e.to_string()),
+        }
+        audit_log
+            .lock()
+            .map_err(|_e| anyhow!("Writing the audit log panicked"))?
+            .record(audit_entry)?;
+        result
+    }
+
+    pub async fn execute(
+        &self,
+        config: &SdkConfig,
+        request: Request,
+    ) -> Result<ExecuteStatementOutput> {
+        self.execute_in_transaction(config, request, None).await
+    }
+
+    pub async fn execute_in_transaction(
+        &self,
+        config: &SdkConfig,
+        request: Request,
+        transaction_id: Option<&str>,
+    ) -> Result<ExecuteStatementOutput> {
+        let execution = execute_in_transaction(config, request.clone(), transaction_id);
+        self.run(config, &request, execution).await
+    }
+}
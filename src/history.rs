@@ -0,0 +1,114 @@
+use crate::{audit::AuditEntry, output_pattern::cluster_id};
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
+use std::io::Write;
+
+/// List previously executed statements, from the audit log.
+///
+/// Statements are numbered from the start of the log, oldest first, and
+/// listed as CSV. Needs --audit-log, or `[audit]` in the configuration
+/// file.
+#[derive(Args, Clone, Debug)]
+pub struct HistoryArgs {
+    /// List at most this many of the most recent statements.
+    #[clap(default_value_t = 20, long, value_name = "N")]
+    limit: usize,
+
+    /// Only list statements whose SQL contains this text, ignoring case.
+    #[clap(long, value_name = "PATTERN")]
+    grep: Option<String>,
+
+    #[clap(subcommand)]
+    action: Option<HistoryAction>,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum HistoryAction {
+    /// Print a past statement's SQL, for re-use.
+    Show {
+        /// Number of the statement, as listed.
+        id: usize,
+    },
+}
+
+/// The SQL as logged, or its hash when the SQL was not kept.
+fn logged_sql(entry: &AuditEntry) -> String {
+    match (&entry.sql, &entry.sql_sha256) {
+        (Some(sql), _) => sql.clone(),
+        (None, Some(sha256)) => format!("sha256:{}", sha256),
+        (None, None) => String::new(),
+    }
+}
+
+fn list(history_args: &HistoryArgs, entries: &[AuditEntry], writer: impl Write) -> Result<()> {
+    let grep = history_args.grep.as_ref().map(|grep| grep.to_lowercase());
+    let matching: Vec<(usize, &AuditEntry)> = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| (index + 1, entry))
+        .filter(|(_id, entry)| {
+            grep.as_ref().is_none_or(|grep| {
+                entry
+                    .sql
+                    .as_ref()
+                    .is_some_and(|sql| sql.to_lowercase().contains(grep))
+            })
+        })
+        .collect();
+    let recent = &matching[matching.len().saturating_sub(history_args.limit)..];
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record([
+        "id",
+        "timestamp",
+        "cluster",
+        "database",
+        "duration_seconds",
+        "error",
+        "sql",
+    ])?;
+    for (id, entry) in recent {
+        wtr.write_record([
+            id.to_string().as_str(),
+            &entry.timestamp,
+            cluster_id(&entry.cluster),
+            entry.database.as_deref().unwrap_or_default(),
+            &format!("{:.3}", entry.duration_seconds),
+            entry.error.as_deref().unwrap_or_default(),
+            &logged_sql(entry),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// The entry with this number, counting from 1.
+pub fn entry(entries: &[AuditEntry], id: usize) -> Result<&AuditEntry> {
+    id.checked_sub(1)
+        .and_then(|index| entries.get(index))
+        .ok_or_else(|| {
+            anyhow!(
+                "No statement numbered {}, the audit log holds {}",
+                id,
+                entries.len()
+            )
+        })
+}
+
+pub fn history(
+    history_args: &HistoryArgs,
+    entries: &[AuditEntry],
+    mut writer: impl Write,
+) -> Result<()> {
+    match history_args.action {
+        None => list(history_args, entries, writer),
+        Some(HistoryAction::Show { id }) => {
+            let entry = entry(entries, id)?;
+            let sql = entry
+                .sql
+                .as_ref()
+                .ok_or_else(|| anyhow!("Statement {} was logged without its SQL", id))?;
+            writeln!(writer, "{}", sql)?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use aws_smithy_http_client::tls::{self, TlsContext, TrustStore};
+use aws_smithy_runtime_api::client::{
+    http::{
+        HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpClient,
+        SharedHttpConnector,
+    },
+    orchestrator::HttpRequest,
+    runtime_components::RuntimeComponents,
+};
+use rustls_pki_types::{pem::PemObject, CertificateDer};
+use std::{fs, time::Instant};
+use tracing::{debug, warn};
+
+/// The certificate authorities to trust, instead of the system's, read
+/// from a PEM file by `--ca-bundle`.
+#[derive(Clone, Debug)]
+pub struct CaBundle(TlsContext);
+
+/// Read a `--ca-bundle`, checking it now, since a bad certificate would
+/// otherwise only show up as a panic within the SDK.
+pub fn read_ca_bundle(path: &str) -> Result<CaBundle> {
+    let pem = fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+    let certificates = CertificateDer::pem_slice_iter(&pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("{} is not a bundle of PEM certificates: {}", path, e))?;
+    if certificates.is_empty() {
+        return Err(anyhow!("{} has no PEM certificates", path));
+    }
+    let tls_context = TlsContext::builder()
+        .with_trust_store(TrustStore::empty().with_pem_certificate(pem))
+        .build()?;
+    Ok(CaBundle(tls_context))
+}
+
+/// The HTTPS client the SDK would pick for itself, trusting the bundle's
+/// certificate authorities instead, if given.
+pub fn default_client(ca_bundle: Option<&CaBundle>) -> SharedHttpClient {
+    let builder = aws_smithy_http_client::Builder::new().tls_provider(tls::Provider::Rustls(
+        tls::rustls_provider::CryptoMode::AwsLc,
+    ));
+    match ca_bundle {
+        Some(CaBundle(tls_context)) => builder.tls_context(tls_context.clone()).build_https(),
+        None => builder.build_https(),
+    }
+}
+
+/// Where Lambda, and X-Ray instrumented environments, put the trace
+/// header of the work we are part of.
+const TRACE_ID_ENV: &str = "_X_AMZN_TRACE_ID";
+
+const TRACE_HEADER: &str = "x-amzn-trace-id";
+
+/// The X-Ray trace header to send, from the environment, such as
+/// `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+pub fn trace_header() -> Option<String> {
+    std::env::var(TRACE_ID_ENV)
+        .ok()
+        .filter(|trace_header| !trace_header.is_empty())
+}
+
+/// An HTTP client adding the X-Ray trace header to each request, so that
+/// our calls appear in the service map of the trace we are part of. The
+/// SDK only does this itself within Lambda. The header is not signed, so
+/// adding it after signing is fine.
+#[derive(Debug)]
+pub struct TraceHttpClient {
+    inner: SharedHttpClient,
+    trace_header: String,
+}
+
+impl TraceHttpClient {
+    pub fn new(inner: SharedHttpClient, trace_header: String) -> TraceHttpClient {
+        TraceHttpClient {
+            inner,
+            trace_header,
+        }
+    }
+}
+
+impl HttpClient for TraceHttpClient {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(TraceHttpConnector {
+            inner: self.inner.http_connector(settings, components),
+            trace_header: self.trace_header.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TraceHttpConnector {
+    inner: SharedHttpConnector,
+    trace_header: String,
+}
+
+impl HttpConnector for TraceHttpConnector {
+    fn call(&self, mut request: HttpRequest) -> HttpConnectorFuture {
+        if !request.headers().contains_key(TRACE_HEADER) {
+            if let Err(e) = request
+                .headers_mut()
+                .try_insert(TRACE_HEADER, self.trace_header.clone())
+            {
+                warn!("Not sending trace header \"{}\": {}", self.trace_header, e);
+            }
+        }
+        self.inner.call(request)
+    }
+}
+
+/// An HTTP client logging one line per request: method, URI, status,
+/// latency and the AWS request id. Headers are left out, since they
+/// carry credentials.
+#[derive(Debug)]
+pub struct DebugHttpClient {
+    inner: SharedHttpClient,
+}
+
+impl DebugHttpClient {
+    pub fn new(inner: SharedHttpClient) -> DebugHttpClient {
+        DebugHttpClient { inner }
+    }
+}
+
+impl HttpClient for DebugHttpClient {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(DebugHttpConnector {
+            inner: self.inner.http_connector(settings, components),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct DebugHttpConnector {
+    inner: SharedHttpConnector,
+}
+
+impl HttpConnector for DebugHttpConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let method = request.method().to_owned();
+        let uri = request.uri().to_owned();
+        let started = Instant::now();
+        let response = self.inner.call(request);
+        HttpConnectorFuture::new(async move {
+            let result = response.await;
+            let latency = started.elapsed();
+            match &result {
+                Ok(response) => {
+                    let headers = response.headers();
+                    let request_id = headers
+                        .get("x-amzn-requestid")
+                        .or_else(|| headers.get("x-amz-request-id"))
+                        .unwrap_or("-");
+                    debug!(
+                        "{} {} {} {:?} request_id={}",
+                        method,
+                        uri,
+                        response.status().as_u16(),
+                        latency,
+                        request_id
+                    );
+                }
+                Err(e) => debug!("{} {} failed after {:?}: {}", method, uri, latency, e),
+            }
+            result
+        })
+    }
+}
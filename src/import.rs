@@ -0,0 +1,798 @@
+use crate::{
+    checkpoint::Checkpoint, dialect::Dialect, guard::Guard, mapping::Mapping,
+    transaction::Transaction, validate, Request, Target,
+};
+use anyhow::{anyhow, Error, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::{
+    error::SdkError,
+    operation::batch_execute_statement::{BatchExecuteStatementError, BatchExecuteStatementOutput},
+    types::{Field, SqlParameter},
+    Client,
+};
+use clap::{Args, ValueEnum};
+use query_rds_data::client;
+use serde_json::Value;
+use std::{
+    fs::File,
+    io::{stdin, Read},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+/// The Data API rejects requests over 4 MiB. Stay well under that, since
+/// `estimated_size` ignores JSON escaping and the like.
+const MAX_BATCH_BYTES: usize = 3 * 1024 * 1024;
+
+/// Load a CSV, JSON or NDJSON file into a table.
+///
+/// The CSV header line, or JSON object keys, name the columns. Each row
+/// becomes one set of parameters to an INSERT statement, sent in batches
+/// through `batch_execute_statement`. CSV values are sent as strings,
+/// JSON values as the matching type.
+#[derive(Args, Clone, Debug)]
+pub struct ImportArgs {
+    /// Table to insert into, optionally qualified by schema.
+    #[clap(long)]
+    table: String,
+
+    /// Input format. Defaults from the file extension, else CSV.
+    #[clap(long, value_enum)]
+    input_format: Option<InputFormat>,
+
+    /// Load input keys into differently named columns.
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_name = "KEY=COLUMN",
+        value_parser = parse_column_mapping
+    )]
+    columns: Vec<(String, String)>,
+
+    /// Fill the columns as this TOML file describes, loading only those
+    /// listed. Each `[columns.NAME]` table may give the input key to read
+    /// `from`, a constant `value`, a `transform` list of `trim`,
+    /// `lowercase` and `uppercase`, and a `date_format` such as
+    /// `[day]/[month]/[year]` to load dates from.
+    #[clap(conflicts_with = "columns", long, value_name = "FILE")]
+    mapping: Option<PathBuf>,
+
+    /// Load string values equal to this as NULL, such as `NULL` or `\N`.
+    /// May be given more than once.
+    #[clap(long, value_name = "TEXT")]
+    null_as: Vec<String>,
+
+    /// Load empty strings, including empty CSV cells, as NULL. Otherwise
+    /// they are loaded as empty strings.
+    #[clap(long)]
+    empty_as_null: bool,
+
+    /// What to do with rows whose keys are already in the table, for
+    /// reloads that are safe to repeat.
+    #[clap(long, value_enum)]
+    on_conflict: Option<OnConflict>,
+
+    /// Columns identifying a row, required to --on-conflict update. On
+    /// MySQL, any unique key conflicting counts; these only choose which
+    /// columns are not updated.
+    #[clap(
+        long,
+        required_if_eq("on_conflict", "update"),
+        requires = "on_conflict",
+        value_delimiter = ','
+    )]
+    key_columns: Vec<String>,
+
+    /// Most rows to send per batch. Batches are also split to stay under
+    /// the Data API request size limit.
+    #[clap(default_value_t = 1000, long, value_parser = clap::value_parser!(u32).range(1..))]
+    batch_size: u32,
+
+    /// How many times to retry a batch that failed because the database
+    /// went away for a moment, as in a failover, before rejecting its rows.
+    /// Each batch runs in a transaction, so a failed batch inserts nothing.
+    #[clap(default_value_t = 2, long)]
+    retries: u32,
+
+    /// Send no faster than this, such as `500rows/s` or `2batches/s`.
+    /// A bare number is rows per second.
+    #[clap(long, value_name = "RATE", value_parser = parse_rate_limit)]
+    rate_limit: Option<RateLimit>,
+
+    /// Save the number of rows inserted to this file after each batch, so
+    /// that an interrupted import can be resumed. A batch that fails even
+    /// after retries stops the import.
+    #[clap(conflicts_with = "resume", long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+
+    /// Skip the rows that this checkpoint file, from an earlier import of
+    /// the same file, records as inserted, then carry on updating it.
+    #[clap(long, value_name = "FILE")]
+    resume: Option<PathBuf>,
+
+    /// Before loading, check every value against the type of its column,
+    /// reporting each mismatch by row and column. Reads the file twice.
+    #[clap(long)]
+    validate: bool,
+
+    /// Only check the values, as --validate does, without loading them.
+    #[clap(conflicts_with_all = ["validate", "checkpoint", "resume"], long)]
+    pub validate_only: bool,
+
+    /// File to read, or `-` for standard input.
+    file: PathBuf,
+}
+
+/// The most rows, or batches, to send per second.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RateLimit {
+    Rows(f64),
+    Batches(f64),
+}
+
+/// Parse a rate such as `500`, `500rows/s` or `2batches/s`.
+pub fn parse_rate_limit(s: &str) -> Result<RateLimit> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(index) => s.split_at(index),
+        None => (s, "rows/s"),
+    };
+    let rate = number.parse::<f64>().ok().filter(|rate| *rate > 0.0);
+    match (rate, unit) {
+        (Some(rate), "rows/s") => Ok(RateLimit::Rows(rate)),
+        (Some(rate), "batches/s") => Ok(RateLimit::Batches(rate)),
+        _ => Err(anyhow!(
+            "Rate limit \"{}\" is not a positive number, or a number followed by one of {:?}",
+            s,
+            ["rows/s", "batches/s"],
+        )),
+    }
+}
+
+/// How an import treats rows that conflict with rows already there.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum OnConflict {
+    /// Update the existing row to the imported values.
+    Update,
+    /// Keep the existing row, skipping the imported one.
+    Ignore,
+}
+
+/// Parameters are named by position, since column names need not be
+/// valid parameter names.
+pub fn param_name(index: usize) -> String {
+    format!("c{}", index + 1)
+}
+
+pub fn insert_sql(dialect: Dialect, table: &str, columns: &[String]) -> String {
+    let column_list: Vec<String> = columns
+        .iter()
+        .map(|column| dialect.quote_identifier(column))
+        .collect();
+    let value_list: Vec<String> = (0..columns.len())
+        .map(|index| format!(":{}", param_name(index)))
+        .collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        dialect.quote_table(table),
+        column_list.join(", "),
+        value_list.join(", "),
+    )
+}
+
+/// The clause after an INSERT that handles conflicting rows.
+fn on_conflict_clause(
+    dialect: Dialect,
+    columns: &[String],
+    on_conflict: OnConflict,
+    key_columns: &[String],
+) -> String {
+    let quoted_keys: Vec<String> = key_columns
+        .iter()
+        .map(|column| dialect.quote_identifier(column))
+        .collect();
+    let updated: Vec<String> = match on_conflict {
+        OnConflict::Update => columns
+            .iter()
+            .filter(|column| !key_columns.contains(column))
+            .map(|column| dialect.quote_identifier(column))
+            .collect(),
+        OnConflict::Ignore => Vec::new(),
+    };
+    match dialect {
+        Dialect::Postgres => {
+            let target = if quoted_keys.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", quoted_keys.join(", "))
+            };
+            if updated.is_empty() {
+                format!(" ON CONFLICT{} DO NOTHING", target)
+            } else {
+                let assignments: Vec<String> = updated
+                    .iter()
+                    .map(|column| format!("{} = EXCLUDED.{}", column, column))
+                    .collect();
+                format!(
+                    " ON CONFLICT{} DO UPDATE SET {}",
+                    target,
+                    assignments.join(", ")
+                )
+            }
+        }
+        Dialect::MySql => {
+            // Assigning a column to itself leaves the row as it was, without
+            // the side effects of INSERT IGNORE on other errors.
+            let assignments: Vec<String> = if updated.is_empty() {
+                let column = quoted_keys
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| dialect.quote_identifier(&columns[0]));
+                vec![format!("{} = {}", column, column)]
+            } else {
+                updated
+                    .iter()
+                    .map(|column| format!("{} = VALUES({})", column, column))
+                    .collect()
+            };
+            format!(" ON DUPLICATE KEY UPDATE {}", assignments.join(", "))
+        }
+    }
+}
+
+fn parameter_set(values: impl Iterator<Item = Field>) -> Vec<SqlParameter> {
+    values
+        .enumerate()
+        .map(|(index, value)| {
+            SqlParameter::builder()
+                .name(param_name(index))
+                .value(value)
+                .build()
+        })
+        .collect()
+}
+
+/// A rough guess at how much a parameter set adds to the request.
+fn estimated_size(parameter_set: &[SqlParameter]) -> usize {
+    parameter_set
+        .iter()
+        .map(|parameter| {
+            let value_size = match &parameter.value {
+                Some(Field::StringValue(string)) => string.len(),
+                // Blobs travel base64 encoded.
+                Some(Field::BlobValue(blob)) => blob.as_ref().len() * 4 / 3,
+                _ => 16,
+            };
+            // Allow for the JSON punctuation around each parameter.
+            parameter.name.as_ref().map_or(0, String::len) + value_size + 48
+        })
+        .sum()
+}
+
+/// A run of rows that could not be inserted.
+pub struct Rejected {
+    first_row: usize,
+    last_row: usize,
+    error: String,
+}
+
+pub struct ImportSummary {
+    pub imported: usize,
+    pub rejected: Vec<Rejected>,
+}
+
+impl ImportSummary {
+    /// Report any rejected rows on stderr, and fail if there were some.
+    pub fn result(&self) -> Result<()> {
+        if self.rejected.is_empty() {
+            return Ok(());
+        }
+        let mut count = 0;
+        for rejected in &self.rejected {
+            eprintln!(
+                "Rejected rows {}-{}: {}",
+                rejected.first_row, rejected.last_row, rejected.error
+            );
+            count += rejected.last_row - rejected.first_row + 1;
+        }
+        Err(anyhow!("{} rows were rejected", count))
+    }
+}
+
+/// Gathers rows into batches, sends them, and keeps count.
+pub struct Loader<'a> {
+    guard: &'a Guard,
+    config: &'a SdkConfig,
+    /// The statement, without parameters, as each batch is sent.
+    request: Request,
+    batch_size: usize,
+    retries: u32,
+    rate_limit: Option<RateLimit>,
+    /// When the first batch was sent, and how many rows and batches have
+    /// been sent since, for pacing under the rate limit.
+    started: Option<Instant>,
+    rows_sent: usize,
+    batches_sent: usize,
+    checkpoint: Option<Checkpoint>,
+    batch: Vec<Vec<SqlParameter>>,
+    batch_bytes: usize,
+    /// The row number of the first row in the batch.
+    first_row: usize,
+    summary: ImportSummary,
+}
+
+impl<'a> Loader<'a> {
+    pub fn new(
+        guard: &'a Guard,
+        config: &'a SdkConfig,
+        target: &'a Target,
+        sql: String,
+        batch_size: u32,
+        retries: u32,
+        rate_limit: Option<RateLimit>,
+    ) -> Loader<'a> {
+        Loader {
+            guard,
+            config,
+            request: Request {
+                target: target.clone(),
+                params: Vec::new(),
+                sql,
+            },
+            batch_size: batch_size as usize,
+            retries,
+            rate_limit,
+            started: None,
+            rows_sent: 0,
+            batches_sent: 0,
+            checkpoint: None,
+            batch: Vec::new(),
+            batch_bytes: 0,
+            first_row: 1,
+            summary: ImportSummary {
+                imported: 0,
+                rejected: Vec::new(),
+            },
+        }
+    }
+
+    /// Save progress to the checkpoint after each batch, starting after
+    /// the rows it already records.
+    pub fn set_checkpoint(&mut self, checkpoint: Checkpoint) {
+        self.first_row = checkpoint.rows + 1;
+        self.checkpoint = Some(checkpoint);
+    }
+
+    /// Add a row, sending the batch once full. Only fails when
+    /// checkpointing, which stops at the first rejected batch.
+    pub async fn push(&mut self, parameter_set: Vec<SqlParameter>) -> Result<()> {
+        let size = estimated_size(&parameter_set);
+        if !self.batch.is_empty() && self.batch_bytes + size > MAX_BATCH_BYTES {
+            self.flush().await?;
+        }
+        self.batch.push(parameter_set);
+        self.batch_bytes += size;
+        if self.batch.len() >= self.batch_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Send any remaining rows.
+    pub async fn finish(mut self) -> Result<ImportSummary> {
+        self.flush().await?;
+        Ok(self.summary)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        // Refused statements fail the load, rather than rejecting rows.
+        self.guard
+            .check(&[(self.config, &self.request)], None)
+            .await?;
+        let batch = std::mem::take(&mut self.batch);
+        let count = batch.len();
+        self.batch_bytes = 0;
+        self.pace().await;
+        self.rows_sent += count;
+        self.batches_sent += 1;
+        let last_row = self.first_row + count - 1;
+        match (self.send(batch).await, &mut self.checkpoint) {
+            (Ok(()), Some(checkpoint)) => {
+                self.summary.imported += count;
+                checkpoint.save(last_row)?;
+            }
+            (Ok(()), None) => self.summary.imported += count,
+            (Err(e), Some(checkpoint)) => {
+                return Err(anyhow!(
+                    "Rows {}-{} failed, stopping with {} rows imported: {}",
+                    self.first_row,
+                    last_row,
+                    checkpoint.rows,
+                    e
+                ))
+            }
+            (Err(e), None) => self.summary.rejected.push(Rejected {
+                first_row: self.first_row,
+                last_row,
+                error: e.to_string(),
+            }),
+        }
+        self.first_row += count;
+        Ok(())
+    }
+
+    /// Wait until sending what has been sent so far would have kept
+    /// under the rate limit.
+    async fn pace(&mut self) {
+        let Some(rate_limit) = self.rate_limit else {
+            return;
+        };
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let seconds = match rate_limit {
+            RateLimit::Rows(rate) => self.rows_sent as f64 / rate,
+            RateLimit::Batches(rate) => self.batches_sent as f64 / rate,
+        };
+        let due = started + Duration::from_secs_f64(seconds);
+        let now = Instant::now();
+        if due > now {
+            info!("Waiting {:?} for the rate limit", due - now);
+            sleep(due - now).await;
+        }
+    }
+
+    /// Send the batch, retrying it as a whole, so that it has one entry in
+    /// the audit log.
+    async fn send(&self, batch: Vec<Vec<SqlParameter>>) -> Result<()> {
+        let execution = self.send_with_retries(batch);
+        self.guard
+            .run(self.config, &self.request, execution)
+            .await
+            .map(|_output| ())
+    }
+
+    async fn send_with_retries(
+        &self,
+        batch: Vec<Vec<SqlParameter>>,
+    ) -> Result<BatchExecuteStatementOutput> {
+        let client = Client::new(self.config);
+        let mut attempt = 0;
+        loop {
+            match self.send_in_transaction(&client, &batch).await {
+                Ok(output) => {
+                    info!("{:?}", output);
+                    return Ok(output);
+                }
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    warn!(
+                        "Batch starting at row {} failed, retrying: {}",
+                        self.first_row, e
+                    );
+                    sleep(Duration::from_millis(500 << attempt.min(6))).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send the batch in its own transaction, so that it is inserted whole
+    /// or not at all, and can be sent again.
+    async fn send_in_transaction(
+        &self,
+        client: &Client,
+        batch: &[Vec<SqlParameter>],
+    ) -> Result<BatchExecuteStatementOutput> {
+        let target = &self.request.target;
+        let transaction = Transaction::begin(self.config, target, None).await?;
+        let result = client
+            .batch_execute_statement()
+            .resource_arn(&target.resource_arn)
+            .secret_arn(&target.secret_arn)
+            .set_database(target.database.clone())
+            .set_schema(target.schema.clone())
+            .sql(&self.request.sql)
+            .set_parameter_sets(Some(batch.to_vec()))
+            .transaction_id(transaction.id())
+            .send()
+            .await;
+        match result {
+            Ok(output) => {
+                transaction.commit().await?;
+                Ok(output)
+            }
+            Err(e) => {
+                if let Err(rollback_error) = transaction.rollback().await {
+                    warn!("{}", rollback_error);
+                }
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Whether the batch failed only because the database went away for a
+/// moment. Failures to begin or commit its transaction are not retried.
+fn is_transient(e: &Error) -> bool {
+    e.downcast_ref::<SdkError<BatchExecuteStatementError>>()
+        .is_some_and(client::is_transient)
+}
+
+pub fn open_input(path: &Path) -> Result<Box<dyn Read>> {
+    if path == Path::new("-") {
+        Ok(Box::new(stdin()))
+    } else {
+        let file =
+            File::open(path).map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+        Ok(Box::new(file))
+    }
+}
+
+/// The shape of the file being imported.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum InputFormat {
+    /// CSV, with a header line naming the columns.
+    Csv,
+    /// A JSON array of objects, keyed by column name.
+    Json,
+    /// One JSON object per line, keyed by column name.
+    Ndjson,
+}
+
+impl InputFormat {
+    /// Guess from the file extension, falling back to CSV.
+    fn from_path(path: &Path) -> InputFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => InputFormat::Json,
+            Some("ndjson") | Some("jsonl") => InputFormat::Ndjson,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+/// Parse a `--columns key=column` mapping.
+fn parse_column_mapping(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, column)) if !key.is_empty() && !column.is_empty() => {
+            Ok((key.to_owned(), column.to_owned()))
+        }
+        _ => Err(anyhow!(
+            "Column mapping \"{}\" is not of the form key=column",
+            s
+        )),
+    }
+}
+
+/// The input keys (CSV header names or JSON object keys), and an iterator
+/// over the rows, each with one field per key.
+pub type Rows = (Vec<String>, Box<dyn Iterator<Item = Result<Vec<Field>>>>);
+
+fn csv_rows(input: Box<dyn Read>) -> Result<Rows> {
+    let mut reader = csv::Reader::from_reader(input);
+    let keys: Vec<String> = reader.headers()?.iter().map(str::to_owned).collect();
+    let rows = reader.into_records().map(|record| {
+        Ok(record?
+            .iter()
+            .map(|value| Field::StringValue(value.to_owned()))
+            .collect())
+    });
+    Ok((keys, Box::new(rows)))
+}
+
+/// Choose the `Field` variant from the JSON type. Nested arrays and objects
+/// are sent as JSON text.
+pub fn json_field(value: Value) -> Field {
+    match value {
+        Value::Null => Field::IsNull(true),
+        Value::Bool(boolean) => Field::BooleanValue(boolean),
+        Value::Number(number) => match number.as_i64() {
+            Some(long) => Field::LongValue(long),
+            None => Field::DoubleValue(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(string) => Field::StringValue(string),
+        nested => Field::StringValue(nested.to_string()),
+    }
+}
+
+/// The first object's keys determine the columns. Later objects may omit
+/// keys, which become NULL, but may not add new ones.
+fn json_rows(mut values: Box<dyn Iterator<Item = Result<Value>>>) -> Result<Rows> {
+    let first = match values.next() {
+        Some(value) => value?,
+        None => return Ok((Vec::new(), Box::new(std::iter::empty()))),
+    };
+    let keys: Vec<String> = match &first {
+        Value::Object(object) => object.keys().cloned().collect(),
+        _ => return Err(anyhow!("Row 1 is not a JSON object")),
+    };
+    let row_keys = keys.clone();
+    let rows = std::iter::once(Ok(first))
+        .chain(values)
+        .enumerate()
+        .map(move |(index, value)| match value? {
+            Value::Object(mut object) => {
+                let fields = row_keys
+                    .iter()
+                    .map(|key| json_field(object.remove(key).unwrap_or(Value::Null)))
+                    .collect();
+                match object.keys().next() {
+                    Some(extra) => Err(anyhow!(
+                        "Row {} has key \"{}\", which is not in row 1",
+                        index + 1,
+                        extra
+                    )),
+                    None => Ok(fields),
+                }
+            }
+            _ => Err(anyhow!("Row {} is not a JSON object", index + 1)),
+        });
+    Ok((keys, Box::new(rows)))
+}
+
+fn read_rows(input: Box<dyn Read>, format: InputFormat) -> Result<Rows> {
+    match format {
+        InputFormat::Csv => csv_rows(input),
+        InputFormat::Json => {
+            let values: Vec<Value> = serde_json::from_reader(input)?;
+            json_rows(Box::new(values.into_iter().map(Ok)))
+        }
+        InputFormat::Ndjson => json_rows(Box::new(
+            serde_json::Deserializer::from_reader(input)
+                .into_iter::<Value>()
+                .map(|value| value.map_err(Error::from)),
+        )),
+    }
+}
+
+/// The table columns to load, after any mapping, and the file's rows.
+fn input(import_args: &ImportArgs) -> Result<Rows> {
+    let path = &import_args.file;
+    let format = import_args
+        .input_format
+        .unwrap_or_else(|| InputFormat::from_path(path));
+    let (keys, records) = read_rows(open_input(path)?, format)?;
+    if keys.is_empty() {
+        return Err(anyhow!("No columns found in {}", path.display()));
+    }
+    let mut null_as = import_args.null_as.clone();
+    if import_args.empty_as_null {
+        null_as.push(String::new());
+    }
+    let records: Box<dyn Iterator<Item = Result<Vec<Field>>>> = if null_as.is_empty() {
+        records
+    } else {
+        Box::new(records.map(move |record| {
+            Ok(record?
+                .into_iter()
+                .map(|value| match value {
+                    Field::StringValue(string) if null_as.contains(&string) => Field::IsNull(true),
+                    value => value,
+                })
+                .collect())
+        }))
+    };
+    if let Some(mapping) = &import_args.mapping {
+        return Mapping::load(mapping)?.apply((keys, records));
+    }
+    let column_mappings = &import_args.columns;
+    for (key, _column) in column_mappings {
+        if !keys.contains(key) {
+            return Err(anyhow!(
+                "Column mapping for \"{}\" matches nothing, available keys are {:?}",
+                key,
+                keys,
+            ));
+        }
+    }
+    let columns: Vec<String> = keys
+        .iter()
+        .map(|key| {
+            column_mappings
+                .iter()
+                .find(|(mapped_key, _column)| mapped_key == key)
+                .map_or(key, |(_key, column)| column)
+                .to_owned()
+        })
+        .collect();
+    Ok((columns, records))
+}
+
+/// The statement inserting each row into the table.
+fn import_sql(target: &Target, import_args: &ImportArgs, columns: &[String]) -> Result<String> {
+    let mut sql = insert_sql(target.dialect(), &import_args.table, columns);
+    if let Some(on_conflict) = import_args.on_conflict {
+        for key_column in &import_args.key_columns {
+            if !columns.contains(key_column) {
+                return Err(anyhow!(
+                    "Key column \"{}\" is not being imported, available columns are {:?}",
+                    key_column,
+                    columns,
+                ));
+            }
+        }
+        sql.push_str(&on_conflict_clause(
+            target.dialect(),
+            columns,
+            on_conflict,
+            &import_args.key_columns,
+        ));
+    }
+    Ok(sql)
+}
+
+/// The request an import would send for each batch, for --dry-run. Only
+/// the file's columns are read.
+pub fn dry_run_request(target: &Target, import_args: &ImportArgs) -> Result<Request> {
+    let (columns, _records) = input(import_args)?;
+    Ok(Request {
+        target: target.clone(),
+        params: Vec::new(),
+        sql: import_sql(target, import_args, &columns)?,
+    })
+}
+
+/// Check every row of the file against the types of the table's columns,
+/// returning the number of rows.
+pub async fn validate(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    import_args: &ImportArgs,
+) -> Result<usize> {
+    let table = &import_args.table;
+    let table_columns = validate::table_columns(guard, config, target, table).await?;
+    let (columns, records) = input(import_args)?;
+    validate::check(table, &table_columns, &columns, records)
+}
+
+/// Insert every row of the file into the table. Rows are numbered from 1,
+/// not counting any header line.
+pub async fn import(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    import_args: &ImportArgs,
+) -> Result<ImportSummary> {
+    let path = &import_args.file;
+    if import_args.validate {
+        if path == Path::new("-") {
+            return Err(anyhow!(
+                "--validate reads the file twice, so can not read standard input"
+            ));
+        }
+        validate(guard, config, target, import_args).await?;
+    }
+    let (columns, records) = input(import_args)?;
+    let sql = import_sql(target, import_args, &columns)?;
+    info!("{}", sql);
+    let mut loader = Loader::new(
+        guard,
+        config,
+        target,
+        sql,
+        import_args.batch_size,
+        import_args.retries,
+        import_args.rate_limit,
+    );
+    let table = &import_args.table;
+    let checkpoint = match (&import_args.checkpoint, &import_args.resume) {
+        (_, Some(resume)) => Some(Checkpoint::resume(resume, path, table)?),
+        (Some(checkpoint), None) => Some(Checkpoint::start(checkpoint, path, table)),
+        (None, None) => None,
+    };
+    let mut skip = 0;
+    if let Some(checkpoint) = checkpoint {
+        skip = checkpoint.rows;
+        if skip > 0 {
+            info!("Resuming after row {}", skip);
+        }
+        loader.set_checkpoint(checkpoint);
+    }
+    for record in records.skip(skip) {
+        loader.push(parameter_set(record?.into_iter())).await?;
+    }
+    loader.finish().await
+}
@@ -0,0 +1,100 @@
+use clap::ValueEnum;
+
+/// How to write column labels as JSON keys.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum KeyCase {
+    /// Like `userId`.
+    Camel,
+    /// Like `user_id`.
+    Snake,
+    /// Like `user-id`.
+    Kebab,
+}
+
+/// The words of a label, split at anything but letters and digits, and
+/// where case changes, so `userID`, `user_id` and `User ID` all give
+/// `user` and `ID`.
+fn words(label: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let chars: Vec<char> = label.chars().collect();
+    for (index, c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            continue;
+        }
+        let previous = index.checked_sub(1).map(|previous| chars[previous]);
+        let next = chars.get(index + 1);
+        // A new word starts at `Id` in `userId`, and at `Server` in `HTTPServer`.
+        let starts_word = c.is_uppercase()
+            && previous.is_some_and(|previous| {
+                previous.is_lowercase()
+                    || previous.is_ascii_digit()
+                    || (previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase()))
+            });
+        if starts_word && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(*c);
+    }
+    words.extend((!word.is_empty()).then_some(word));
+    words
+}
+
+impl KeyCase {
+    /// The label in this case. Labels without letters or digits are kept.
+    pub fn convert(self, label: &str) -> String {
+        let words: Vec<String> = words(label)
+            .iter()
+            .map(|word| word.to_lowercase())
+            .collect();
+        if words.is_empty() {
+            return label.to_owned();
+        }
+        match self {
+            KeyCase::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(index, word)| match index {
+                    0 => word.clone(),
+                    _ => {
+                        let mut chars = word.chars();
+                        chars
+                            .next()
+                            .map(|first| first.to_uppercase().chain(chars).collect())
+                            .unwrap_or_default()
+                    }
+                })
+                .collect(),
+            KeyCase::Snake => words.join("_"),
+            KeyCase::Kebab => words.join("-"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_words() {
+        assert_eq!(words("userID"), ["user", "ID"]);
+        assert_eq!(words("user_id"), ["user", "id"]);
+        assert_eq!(words("User ID"), ["User", "ID"]);
+        assert_eq!(words("HTTPServer2fa"), ["HTTP", "Server2fa"]);
+        assert_eq!(words("version2Name"), ["version2", "Name"]);
+        assert!(words("__").is_empty());
+    }
+
+    #[test]
+    fn converts_labels() {
+        assert_eq!(KeyCase::Camel.convert("user_id"), "userId");
+        assert_eq!(KeyCase::Camel.convert("HTTP server"), "httpServer");
+        assert_eq!(KeyCase::Snake.convert("userID"), "user_id");
+        assert_eq!(KeyCase::Kebab.convert("User ID"), "user-id");
+        assert_eq!(KeyCase::Snake.convert("count(*)"), "count");
+        // Labels without letters or digits are kept as they are.
+        assert_eq!(KeyCase::Camel.convert("?column?"), "column");
+        assert_eq!(KeyCase::Camel.convert("?"), "?");
+    }
+}
@@ -0,0 +1,352 @@
+//! The library behind `query-rds-data`: finding an Aurora cluster and its
+//! credentials, and running statements through the RDS Data API.
+//! [`QueryExecutor`] wraps all of that, for use from Rust code such as
+//! AWS Lambda functions.
+
+pub mod client;
+pub mod dialect;
+pub mod error;
+pub mod executor;
+pub mod params;
+
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rds::types::DbCluster;
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use aws_sdk_secretsmanager::types::SecretListEntry;
+use client::{AwsClient, Client};
+use dialect::Dialect;
+use futures::join;
+use params::Param;
+use serde_json::Value;
+use tracing::{info_span, Instrument};
+
+pub use error::Error;
+pub use executor::{Config, QueryExecutor, ResultSet};
+
+#[derive(Clone, Debug)]
+pub struct MyArns {
+    pub aws_secret_store_arn: String,
+    pub db_cluster_or_instance_arn: String,
+    pub engine: Option<String>,
+}
+
+/// Everything needed for one `execute_statement` call.
+#[derive(Clone)]
+pub struct Request {
+    pub target: Target,
+    pub params: Vec<Param>,
+    pub sql: String,
+}
+
+/// The resolved database that statements are sent to.
+#[derive(Clone, Debug)]
+pub struct Target {
+    pub resource_arn: String,
+    pub secret_arn: String,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    pub engine: Option<String>,
+}
+
+impl Target {
+    pub fn new(arns: MyArns, database: Option<String>, schema: Option<String>) -> Target {
+        Target {
+            resource_arn: arns.db_cluster_or_instance_arn,
+            secret_arn: arns.aws_secret_store_arn,
+            database,
+            schema,
+            engine: arns.engine,
+        }
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        Dialect::from_engine(self.engine.as_deref())
+    }
+}
+
+/// Extract a name for each column
+pub fn format_header<'a>(result: &'a ExecuteStatementOutput) -> impl Iterator<Item = &'a str> {
+    // This seems pretty crazed...
+    result
+        .column_metadata
+        .as_ref()
+        .map_or(&[][..], |x| &**x)
+        .iter()
+        .map::<&'a str, _>(|column| {
+            if let Some(ref label) = column.label {
+                label
+            } else if let Some(ref name) = column.name {
+                name
+            } else {
+                "?"
+            }
+        })
+}
+
+pub fn format_value(value: &Field) -> String {
+    match value {
+        Field::ArrayValue(inner) => format!("{:?}", *inner),
+        Field::BlobValue(inner) => format!("{:?}", *inner),
+        Field::BooleanValue(inner) => format!("{:?}", *inner),
+        Field::DoubleValue(inner) => format!("{:?}", *inner),
+        Field::IsNull(_) => "NULL".to_owned(),
+        Field::LongValue(inner) => format!("{:?}", *inner),
+        Field::StringValue(inner) => inner.to_owned(),
+        _ => "UNKNOWN".to_owned(), // punt!!
+    }
+}
+
+/// Whether `format_value` knows this kind of field, rather than writing
+/// `UNKNOWN`. `field_value` knows fewer: it also writes arrays and blobs
+/// as `null`.
+pub fn is_supported(value: &Field) -> bool {
+    matches!(
+        value,
+        Field::ArrayValue(_)
+            | Field::BlobValue(_)
+            | Field::BooleanValue(_)
+            | Field::DoubleValue(_)
+            | Field::IsNull(_)
+            | Field::LongValue(_)
+            | Field::StringValue(_)
+    )
+}
+
+fn one_row(values: &[Field]) -> impl Iterator<Item = String> + '_ {
+    values.iter().map(format_value)
+}
+
+/// Return an iterator of iterators of strings
+pub fn format_rows(
+    result: &ExecuteStatementOutput,
+) -> impl Iterator<Item = impl Iterator<Item = String> + '_> {
+    // This seems pretty crazed...
+    result
+        .records
+        .as_ref()
+        .map_or(&[][..], |x| &**x)
+        .iter()
+        .map(|record| one_row(record))
+}
+
+fn cluster_ids(db_clusters: &[DbCluster]) -> Vec<String> {
+    db_clusters
+        .iter()
+        .map(|db_cluster| {
+            db_cluster
+                .db_cluster_identifier
+                .as_ref()
+                .unwrap_or(&"".to_string())
+                .to_owned()
+        })
+        .collect()
+}
+
+pub fn my_cluster(
+    requested_db_cluster_identifier: &Option<String>,
+    db_clusters: &[DbCluster],
+) -> Result<DbCluster> {
+    match requested_db_cluster_identifier {
+        Some(requested_db_cluster_identifier) => {
+            for db_cluster in db_clusters {
+                if let Some(ref db_cluster_identifier) = db_cluster.db_cluster_identifier {
+                    // Since this is an exact match, we assume there is only one.
+                    if requested_db_cluster_identifier == db_cluster_identifier {
+                        return Ok(db_cluster.to_owned());
+                    }
+                }
+            }
+            Err(anyhow!(
+                "No cluster matched \"{}\", available ids are {:?}",
+                requested_db_cluster_identifier.to_owned(),
+                cluster_ids(db_clusters),
+            ))
+        }
+        None => {
+            match db_clusters.len() {
+                // There is exactly one: go ahead and use it.
+                1 => Ok(db_clusters[0].to_owned()),
+                0 => Err(anyhow!("No clusters found")),
+                _ => Err(anyhow!(
+                    "Multiple clusters found, please specify one of {:?}",
+                    cluster_ids(db_clusters)
+                )),
+            }
+        }
+    }
+}
+
+fn secrets_for_db<'a>(
+    requested_db_cluster_resource_id: &str,
+    secret_list: &'a [SecretListEntry],
+) -> Vec<&'a SecretListEntry> {
+    // I don't know if this is a universal naming standard for secrets.
+    // If not, this code is badly wrong.
+    let name_starts_with =
+        "rds-db-credentials/".to_string() + requested_db_cluster_resource_id + "/";
+    secret_list
+        .iter()
+        .filter(|secret_list_entry| match secret_list_entry.name {
+            Some(ref name) => name.starts_with(&name_starts_with).to_owned(),
+            None => false,
+        })
+        .collect()
+}
+
+fn user_id_from_secret(secret_list_entry: &SecretListEntry) -> String {
+    match secret_list_entry.name {
+        Some(ref name) => name.splitn(3, '/').last().unwrap_or("").to_string(),
+        None => "".to_string(),
+    }
+}
+
+fn user_names(secret_list: &[&SecretListEntry]) -> Vec<String> {
+    secret_list
+        .iter()
+        .map(|entry| user_id_from_secret(entry))
+        .collect()
+}
+
+fn my_secret(
+    requested_db_cluster_resource_id: &str,
+    requested_db_user_id: &Option<String>,
+    secret_list: &[SecretListEntry],
+) -> Result<SecretListEntry> {
+    let db_secrets = secrets_for_db(requested_db_cluster_resource_id, secret_list);
+
+    match requested_db_user_id {
+        Some(requested_db_user_id) => {
+            for secret_list_entry in &db_secrets {
+                if let Some(ref name) = secret_list_entry.name {
+                    if name.ends_with(requested_db_user_id) {
+                        // Since this is an exact match, we assume there is only one.
+                        return Ok((*secret_list_entry).to_owned());
+                    }
+                }
+            }
+            Err(anyhow!(
+                "No cluster user matched \"{}\", available users are {:?}",
+                requested_db_user_id.to_owned(),
+                user_names(&db_secrets),
+            ))
+        }
+        None => {
+            match db_secrets.len() {
+                // There is exactly one: go ahead and use it.
+                1 => Ok(db_secrets[0].to_owned()),
+                0 => Err(anyhow!("No cluster user secrets found")),
+                _ => Err(anyhow!(
+                    "Multiple cluster users found, please specify one of {:?}",
+                    user_names(&db_secrets),
+                )),
+            }
+        }
+    }
+}
+
+/// Every cluster and secret visible to us.
+pub async fn discover(
+    aws_sdk_config: &SdkConfig,
+) -> Result<(Vec<DbCluster>, Vec<SecretListEntry>)> {
+    discover_with(&AwsClient::new(aws_sdk_config)).await
+}
+
+/// Every cluster and secret visible through the client.
+pub async fn discover_with(client: &dyn Client) -> Result<(Vec<DbCluster>, Vec<SecretListEntry>)> {
+    let (db_clusters, secret_list) =
+        async { join!(client.describe_db_clusters(), client.list_secrets()) }
+            .instrument(info_span!("discover"))
+            .await;
+    Ok((db_clusters?, secret_list?))
+}
+
+/// The identifier of the cluster a DB instance belongs to.
+pub async fn instance_cluster_id(
+    aws_sdk_config: &SdkConfig,
+    db_instance_identifier: &str,
+) -> Result<String> {
+    instance_cluster_id_with(&AwsClient::new(aws_sdk_config), db_instance_identifier).await
+}
+
+pub async fn instance_cluster_id_with(
+    client: &dyn Client,
+    db_instance_identifier: &str,
+) -> Result<String> {
+    let db_instance = client
+        .describe_db_instance(db_instance_identifier.to_owned())
+        .await?;
+    db_instance.db_cluster_identifier.ok_or_else(|| {
+        anyhow!(
+            "DB instance \"{}\" is not part of a cluster",
+            db_instance_identifier
+        )
+    })
+}
+
+pub fn cluster_arns(
+    db_cluster: DbCluster,
+    requested_user_id: &Option<String>,
+    secret_list: &[SecretListEntry],
+) -> Result<MyArns> {
+    let secret_list_entry = my_secret(
+        &db_cluster.db_cluster_resource_id.unwrap(),
+        requested_user_id,
+        secret_list,
+    )?;
+    Ok(MyArns {
+        aws_secret_store_arn: secret_list_entry.arn.unwrap(),
+        db_cluster_or_instance_arn: db_cluster.db_cluster_arn.unwrap(),
+        engine: db_cluster.engine,
+    })
+}
+
+pub async fn get_arns(
+    aws_sdk_config: &SdkConfig,
+    requested_db_cluster_identifier: &Option<String>,
+    requested_user_id: &Option<String>,
+) -> Result<MyArns> {
+    get_arns_with(
+        &AwsClient::new(aws_sdk_config),
+        requested_db_cluster_identifier,
+        requested_user_id,
+    )
+    .await
+}
+
+pub async fn get_arns_with(
+    client: &dyn Client,
+    requested_db_cluster_identifier: &Option<String>,
+    requested_user_id: &Option<String>,
+) -> Result<MyArns> {
+    let (db_clusters, secret_list) = discover_with(client).await?;
+    let db_cluster = my_cluster(requested_db_cluster_identifier, &db_clusters)?;
+    cluster_arns(db_cluster, requested_user_id, &secret_list)
+}
+
+pub fn field_value(field: &Field) -> Value {
+    match field {
+        Field::ArrayValue(_array_value) => Value::Null, // punt!!
+        Field::BlobValue(_blob_value) => Value::Null,   // punt!!
+        Field::BooleanValue(boolean_value) => Value::from(*boolean_value),
+        Field::DoubleValue(double_value) => Value::from(*double_value),
+        Field::IsNull(_) => Value::Null,
+        Field::LongValue(long_value) => Value::from(*long_value),
+        Field::StringValue(string_value) => Value::from(string_value.clone()),
+        _ => Value::Null, // punt!!
+    }
+}
+
+pub async fn execute(config: &SdkConfig, request: Request) -> Result<ExecuteStatementOutput> {
+    execute_in_transaction(config, request, None).await
+}
+
+pub async fn execute_in_transaction(
+    config: &SdkConfig,
+    request: Request,
+    transaction_id: Option<&str>,
+) -> Result<ExecuteStatementOutput> {
+    AwsClient::new(config)
+        .execute_statement(request, transaction_id.map(str::to_owned))
+        .await
+}
@@ -1,22 +1,119 @@
+mod arn_cache;
+mod audit;
+mod batch;
+mod blobs;
+mod cache;
+mod cancel;
+mod checkpoint;
+mod checksum;
+mod cloudwatch;
+mod config;
+mod confirm;
+mod copy;
+mod diff;
+mod driver;
+mod enable;
+mod ephemeral;
+mod exec;
+mod expect;
+mod explain;
+mod fanout;
+mod foreach;
+mod geometry;
+mod guard;
+mod history;
+mod http;
+mod import;
+mod key_case;
+mod mapping;
+mod metrics;
+mod migrate;
+mod otlp;
+mod output_pattern;
+mod pager;
+mod parquet_output;
+mod partition;
+mod pg_array;
+mod policy;
+mod pretty;
+mod progress;
+mod s3;
+mod schedule;
+mod schema;
+mod script;
+mod snapshot;
+mod summary;
+mod template;
+mod transaction;
+mod transform;
+mod validate;
+
 use anyhow::{anyhow, Result};
-use aws_config::{identity::IdentityCache, BehaviorVersion, SdkConfig};
-use aws_sdk_rds::types::DbCluster;
+use arn_cache::ArnCache;
+use audit::{AuditEntry, AuditLog};
+use aws_config::{identity::IdentityCache, timeout::TimeoutConfig, BehaviorVersion, SdkConfig};
 use aws_sdk_rdsdata::{
-    operation::execute_statement::ExecuteStatementOutput,
-    types::{DecimalReturnType, Field, ResultSetOptions},
+    operation::{execute_statement::ExecuteStatementOutput, RequestId},
+    types::{ColumnMetadata, Field},
 };
-use aws_sdk_secretsmanager::types::SecretListEntry;
+use aws_smithy_runtime_api::client::http::SharedHttpClient;
 use aws_types::region::Region;
-use clap::{Parser, ValueEnum};
-use futures::{join, prelude::*};
-use serde::{ser::SerializeMap, Serialize, Serializer};
+use batch::BatchExecuteArgs;
+use cache::Cache;
+use cancel::CancelArgs;
+use clap::{
+    parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use config::{Config, TargetAlias};
+use copy::CopyArgs;
+use diff::DiffArgs;
+use driver::Driver;
+use enable::EnableDataApiArgs;
+use ephemeral::EphemeralSecret;
+use explain::ExplainArgs;
+use futures::join;
+use geometry::Geometry;
+use guard::Guard;
+use history::HistoryArgs;
+use import::ImportArgs;
+use key_case::KeyCase;
+use metrics::Metrics;
+use migrate::MigrateArgs;
+use pretty::{ColorChoice, Style};
+use progress::Progress;
+use query_rds_data::{
+    client::{AwsClient, Client, DEFAULT_TRANSIENT_RETRIES},
+    cluster_arns, dialect, discover,
+    error::Error,
+    execute_in_transaction, field_value, format_header, format_rows, format_value, get_arns,
+    instance_cluster_id, is_supported, my_cluster, params,
+    params::{merge_params, sql_parameters, Param},
+    MyArns, Request, Target,
+};
+use s3::{S3Options, S3Writer};
+use schedule::Schedule;
+use schema::{SchemaAction, SchemaArgs};
+use script::ScriptArgs;
+use serde::{
+    ser::{Error as _, SerializeMap, SerializeSeq},
+    Serialize, Serializer,
+};
 use serde_json::Value;
+use snapshot::SnapshotArgs;
 use std::{
-    io::{stdout, Write},
-    time::Duration,
+    io::{stdout, BufWriter, IsTerminal, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
 };
-use tracing::info;
+use summary::Summary;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{info_span, warn};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use transaction::Isolation;
+use transform::{DedupeColumns, SortKey};
 
 #[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
 enum Format {
@@ -28,6 +125,36 @@ enum Format {
     ///               "name": "bruce",
     ///               "amount": 0.05}]}
     Json,
+    /// One JSON object per line, for each record.
+    Ndjson,
+    /// Apache Parquet, with column types taken from the returned values.
+    Parquet,
+    /// Aligned columns, for reading in a terminal.
+    Table,
+}
+
+impl Format {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::Json => "application/json",
+            Format::Ndjson => "application/x-ndjson",
+            Format::Parquet => "application/vnd.apache.parquet",
+            Format::Table => "text/plain",
+        }
+    }
+}
+
+/// How to write NaN and infinite doubles, which JSON has no numbers for.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+enum NonFinite {
+    /// As `null`.
+    Null,
+    /// As the strings `"NaN"`, `"Infinity"` and `"-Infinity"`, which
+    /// JavaScript's `Number()` reads back.
+    String,
+    /// Fail, naming the column and row.
+    Error,
 }
 
 /// Query AWS RDS Data from the command line
@@ -35,257 +162,652 @@ enum Format {
 /// You can set the environment variable `RUST_LOG` to adjust
 /// logging, for example `RUST_LOG=trace query-rds-data`.
 #[derive(Clone, Debug, Parser)]
-#[command(about, author, version)]
+#[command(about, author, version, subcommand_negates_reqs = true)]
 struct MyArgs {
+    /// Named connection from the configuration file, under
+    /// `[targets.<name>]`. Its settings take the place of the matching
+    /// environment variables, but not of options given.
+    #[clap(
+        env = "QUERY_RDS_DATA_TARGET",
+        global = true,
+        long,
+        value_name = "NAME"
+    )]
+    target: Option<String>,
+
+    /// Refuse to run anything but queries: SELECT, SHOW, EXPLAIN and
+    /// DESCRIBE statements.
+    #[clap(global = true, long)]
+    read_only: bool,
+
     /// AWS source profile to use. This name references an entry in ~/.aws/config
-    #[clap(env = "AWS_PROFILE", long, short)]
+    #[clap(env = "AWS_PROFILE", global = true, long, short)]
     profile: Option<String>,
 
-    /// AWS region to target.
-    #[clap(env = "AWS_REGION", long, short)]
-    region: Option<String>,
+    /// AWS region to target. When repeated, the query runs in every region
+    /// given, concurrently, adding a leading region column to the results.
+    #[clap(env = "AWS_REGION", global = true, long, short)]
+    region: Vec<String>,
+
+    /// Run the query in every region where RDS is available, as listed by
+    /// the first --region (or the default region).
+    #[clap(global = true, long)]
+    all_regions: bool,
 
     /// RDS cluster identifier.
-    #[clap(env = "AWS_RDS_CLUSTER", long = "db-cluster-identifier", short)]
+    #[clap(
+        env = "AWS_RDS_CLUSTER",
+        global = true,
+        long = "db-cluster-identifier",
+        short
+    )]
     cluster_id: Option<String>,
 
+    /// RDS DB instance identifier, used to find its cluster in place of
+    /// --db-cluster-identifier.
+    #[clap(
+        conflicts_with = "all_clusters",
+        env = "AWS_RDS_INSTANCE",
+        global = true,
+        long = "db-instance-identifier"
+    )]
+    instance_id: Option<String>,
+
+    /// ARN of the cluster, used as given, without looking up clusters.
+    /// Requires --secret-arn.
+    #[clap(
+        conflicts_with = "all_clusters",
+        env = "AWS_RDS_RESOURCE_ARN",
+        global = true,
+        long,
+        requires = "secret_arn"
+    )]
+    resource_arn: Option<String>,
+
+    /// ARN of the Secrets Manager secret holding the database credentials,
+    /// used as given, without looking up secrets. Requires --resource-arn.
+    #[clap(
+        env = "AWS_RDS_SECRET_ARN",
+        global = true,
+        long,
+        requires = "resource_arn"
+    )]
+    secret_arn: Option<String>,
+
+    /// Never call RDS or Secrets Manager to find the cluster and secret:
+    /// use --resource-arn and --secret-arn, or the ARNs cached when the
+    /// same target was last found, and fail without either. For locked
+    /// down roles, and faster cold starts in Lambda.
+    #[clap(conflicts_with_all = ["all_clusters", "username"], global = true, long)]
+    offline_discovery: bool,
+
+    /// The engine of --resource-arn, such as `aurora-postgresql` or
+    /// `aurora-mysql`, for parsing and quoting its SQL. Without it, the
+    /// engine is looked up, except with --offline-discovery, which needs it.
+    #[clap(env = "AWS_RDS_ENGINE", global = true, long, requires = "resource_arn")]
+    engine: Option<String>,
+
+    /// The engine of --resource-arn, when we looked the cluster up.
+    #[clap(skip)]
+    resource_engine: Option<String>,
+
+    /// Database user name. With --password, the credentials are kept in a
+    /// temporary Secrets Manager secret, deleted after the run, instead of
+    /// looking up an existing secret.
+    #[clap(
+        conflicts_with_all = ["all_clusters", "resource_arn"],
+        env = "AWS_RDS_USERNAME",
+        global = true,
+        long,
+        requires = "password"
+    )]
+    username: Option<String>,
+
+    /// Database password, for --username.
+    #[clap(
+        env = "AWS_RDS_PASSWORD",
+        global = true,
+        hide_env_values = true,
+        long,
+        requires = "username"
+    )]
+    password: Option<String>,
+
     /// RDS user identifier (really the AWS secret identifier).
-    #[clap(env = "AWS_RDS_USER", long = "db-user-identifier", short)]
+    #[clap(
+        env = "AWS_RDS_USER",
+        global = true,
+        long = "db-user-identifier",
+        short
+    )]
     user_id: Option<String>,
 
     /// Output format.
-    #[clap(value_enum, default_value = "csv", long, short)]
+    #[clap(value_enum, default_value = "csv", global = true, long, short)]
     format: Format,
 
+    /// When to color CSV and table output.
+    #[clap(value_enum, default_value = "auto", global = true, long)]
+    color: ColorChoice,
+
+    /// In table output, cut cells longer than this short, with an ellipsis.
+    #[clap(
+        global = true,
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    max_col_width: Option<u32>,
+
+    /// Start CSV output with a UTF-8 byte order mark, so that Excel reads
+    /// it as UTF-8.
+    #[clap(global = true, long)]
+    bom: bool,
+
+    /// How statements reach the database. The Data API needs the cluster's
+    /// HTTP endpoint enabled; the others connect to the cluster directly,
+    /// with the credentials in its secret, and do not support --param.
+    #[clap(value_enum, default_value = "data-api", global = true, long)]
+    driver: Driver,
+
+    /// How many times to retry a query when the Data API loses its
+    /// database connection, as when Aurora scales or fails over. Statements
+    /// that write are not retried, since they may already have committed.
+    #[clap(default_value_t = DEFAULT_TRANSIENT_RETRIES, global = true, long, value_name = "N")]
+    transient_retries: u32,
+
+    /// Log each AWS request to stderr: method, URI, status, latency and
+    /// request id.
+    #[clap(global = true, long)]
+    debug_http: bool,
+
+    /// Give up connecting to AWS after this long, such as 5s, instead of
+    /// the SDK's default of 3.1 seconds.
+    #[clap(global = true, long, value_name = "DURATION", value_parser = cache::parse_duration)]
+    connect_timeout: Option<Duration>,
+
+    /// Give up on each AWS call after this long in all, including retries,
+    /// such as 2m, instead of waiting as long as the network lets it take.
+    #[clap(global = true, long, value_name = "DURATION", value_parser = cache::parse_duration)]
+    operation_timeout: Option<Duration>,
+
+    /// Trust the certificate authorities in this PEM file, instead of the
+    /// system's, when calling AWS, as behind a TLS-intercepting proxy.
+    #[clap(env = "AWS_CA_BUNDLE", global = true, long, value_name = "PATH", value_parser = http::read_ca_bundle)]
+    ca_bundle: Option<http::CaBundle>,
+
+    /// Export spans covering discovery, each statement, with its AWS
+    /// request id, and formatting, to the OTLP/HTTP collector at this URL,
+    /// such as `http://localhost:4318`.
+    #[clap(
+        env = "OTEL_EXPORTER_OTLP_ENDPOINT",
+        global = true,
+        long,
+        value_name = "URL"
+    )]
+    otlp_endpoint: Option<String>,
+
+    /// Never pipe output through $PAGER. Otherwise, output to a terminal
+    /// that is taller than it is paged, with `less -S` by default.
+    #[clap(global = true, long)]
+    no_pager: bool,
+
     /// Database name.
-    #[clap(env = "AWS_RDS_DATABASE", long, short)]
+    #[clap(env = "AWS_RDS_DATABASE", global = true, long, short)]
     database: Option<String>,
 
+    /// Database schema.
+    #[clap(env = "AWS_RDS_SCHEMA", global = true, long)]
+    schema: Option<String>,
+
+    /// Configuration file. Defaults to query-rds-data/config.toml under
+    /// your configuration directory, for example ~/.config
+    #[clap(env = "QUERY_RDS_DATA_CONFIG", global = true, long)]
+    config: Option<PathBuf>,
+
+    /// SQL parameter, referenced in the SQL as `:name`. May be repeated.
+    /// The type is one of string (the default), long, double, boolean, uuid,
+    /// json, date, time or timestamp. A json value of `@path` is read from
+    /// that file.
+    #[clap(global = true, long = "param", value_name = "NAME[:TYPE]=VALUE")]
+    params: Vec<Param>,
+
+    /// Template variable. When any are given, `{{name}}` in the SQL is
+    /// replaced by the value quoted as a string literal, and `{{name|ident}}`
+    /// by the value quoted as an identifier. May be repeated.
+    #[clap(global = true, long = "var", value_name = "NAME=VALUE", value_parser = template::parse_var)]
+    vars: Vec<(String, String)>,
+
+    /// Expand `${VAR}` references in the SQL from the environment.
+    #[clap(global = true, long)]
+    env_substitute: bool,
+
+    /// Check the SQL syntax locally, for the cluster's engine, before sending it.
+    #[clap(global = true, long)]
+    check: bool,
+
+    /// Do not ask for confirmation, before destructive statements or
+    /// enabling the Data API.
+    #[clap(global = true, long, short)]
+    yes: bool,
+
+    /// Resolve the cluster, secret and SQL, print them, and exit without
+    /// executing anything.
+    #[clap(global = true, long)]
+    dry_run: bool,
+
+    /// Do not print how many rows a query returned, how long it took, and
+    /// where it ran, on stderr after the results.
+    #[clap(global = true, long, short)]
+    quiet: bool,
+
+    /// Fail, with exit status 3, unless the statement updated exactly this
+    /// many records. Catches an UPDATE matching no rows.
+    #[clap(conflicts_with = "min_updates", global = true, long, value_name = "N")]
+    expect_updates: Option<i64>,
+
+    /// Fail, with exit status 3, unless the statement updated at least this
+    /// many records.
+    #[clap(global = true, long, value_name = "N")]
+    min_updates: Option<i64>,
+
+    /// After a query runs, write how many rows it returned or updated, how
+    /// long it took, where it ran and the Data API's request IDs to this
+    /// file, as JSON.
+    #[clap(global = true, long, value_name = "PATH")]
+    summary_json: Option<PathBuf>,
+
+    /// Statement to run before the query, in the same transaction, for
+    /// example `SET search_path = analytics`. May be repeated.
+    #[clap(global = true, long, value_name = "SQL")]
+    init_sql: Vec<String>,
+
+    /// Run the query, with any --init-sql, in a transaction at this
+    /// isolation level. Also applies to explain --analyze. PostgreSQL only.
+    #[clap(value_enum, global = true, long)]
+    isolation: Option<Isolation>,
+
+    /// Append a JSON line describing each executed statement to this file.
+    #[clap(env = "QUERY_RDS_DATA_AUDIT_LOG", global = true, long)]
+    audit_log: Option<PathBuf>,
+
+    /// Record a SHA-256 of the SQL in the audit log, instead of the SQL itself.
+    #[clap(global = true, long)]
+    audit_hash_sql: bool,
+
+    /// Reuse a locally cached result of the same query, against the same
+    /// target with the same parameters, if it is younger than this, for
+    /// example 90s, 15m or 1h.
+    #[clap(global = true, long, value_name = "DURATION", value_parser = cache::parse_duration)]
+    cache: Option<Duration>,
+
+    /// Keep running, and run the query on this cron schedule of minute,
+    /// hour, day of month, month and day of week, in UTC, for example
+    /// `*/5 * * * *`. A failed run is reported, and does not stop later
+    /// runs. Use `{time}` in --output-pattern to keep each run's results.
+    #[clap(global = true, long, value_name = "CRON")]
+    schedule: Option<Schedule>,
+
+    /// With --schedule, serve Prometheus metrics at `/metrics` on this
+    /// address, such as `127.0.0.1:9187`: run counts and durations, rows
+    /// returned, and the numeric values of the last results, labelled by
+    /// their other columns, for alerting on values in the database.
+    #[clap(global = true, long, requires = "schedule", value_name = "ADDRESS")]
+    metrics_address: Option<SocketAddr>,
+
+    /// Publish each run's duration, rows returned and errors to CloudWatch,
+    /// as the metrics Duration, Rows and Errors in this namespace, for
+    /// monitoring scheduled jobs.
+    #[clap(global = true, long, value_name = "NAMESPACE")]
+    emit_metrics: Option<String>,
+
+    /// Run the query against every cluster with a matching user secret,
+    /// concurrently, adding a leading cluster_id column to the results.
+    #[clap(conflicts_with = "cluster_id", global = true, long)]
+    all_clusters: bool,
+
+    /// Run the query in each of these databases, on the same cluster,
+    /// adding a database column to the results. May be repeated, or given
+    /// as a comma separated list.
+    #[clap(global = true, long, value_delimiter = ',', value_name = "DATABASES")]
+    databases: Vec<String>,
+
+    /// Run the query in every database on the cluster, other than those
+    /// the engine keeps for itself, adding a database column to the results.
+    #[clap(conflicts_with = "databases", global = true, long)]
+    all_databases: bool,
+
+    /// Run the query once for each parameter set in this file of JSON
+    /// objects, one per line, or `-` for standard input, adding a column for
+    /// each parameter to the results. For checks to run per tenant, say;
+    /// batch-execute suits DML better.
+    #[clap(global = true, long, value_name = "FILE")]
+    foreach: Option<PathBuf>,
+
+    /// With --all-clusters, only use clusters running this engine,
+    /// for example aurora-postgresql.
+    #[clap(global = true, long, requires = "all_clusters")]
+    cluster_engine: Option<String>,
+
+    /// With --all-clusters, only use clusters with this tag. May be repeated.
+    #[clap(
+        global = true,
+        long = "cluster-tag",
+        requires = "all_clusters",
+        value_name = "KEY=VALUE",
+        value_parser = fanout::parse_tag
+    )]
+    cluster_tags: Vec<(String, String)>,
+
+    /// With --all-clusters or several regions, how many statements to run
+    /// at once, and with --exec, how many commands. Large fleets can
+    /// otherwise hit API throttling.
+    #[clap(
+        default_value_t = 10,
+        global = true,
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    concurrency: u32,
+
+    /// Write each result set to its own file, named by this pattern, instead
+    /// of merging them onto stdout. `{index}` (counting from 1), `{region}`,
+    /// `{cluster}` and `{time}` (when the run started, in UTC) are replaced,
+    /// for example `out/{index}-{cluster}.csv`.
+    #[clap(global = true, long, value_name = "PATTERN")]
+    output_pattern: Option<String>,
+
+    /// With --output-pattern, write the rows to a file for each value of
+    /// this column, named by the pattern's `{value}`, for example
+    /// `out/{value}.csv`, and with `{index}` counting the files.
+    #[clap(
+        conflicts_with = "partition_by",
+        global = true,
+        long,
+        requires = "output_pattern",
+        value_name = "COLUMN"
+    )]
+    partition_output: Option<String>,
+
+    /// Instead of writing out the results, run this shell command once for
+    /// each row, with `{column}` replaced by the row's value of that column,
+    /// quoted for the shell. For example `--exec 'requeue-job {id}'`.
+    #[clap(
+        conflicts_with = "output_pattern",
+        global = true,
+        long,
+        value_name = "COMMAND"
+    )]
+    exec: Option<String>,
+
+    /// Instead of writing out the results, print a hash of them, so that
+    /// scheduled jobs can cheaply tell whether the data changed.
+    #[clap(
+        conflicts_with_all = ["exec", "output_pattern"],
+        global = true,
+        long,
+        value_enum
+    )]
+    checksum: Option<checksum::Algorithm>,
+
+    /// Instead of writing out the results, compare them, as formatted, with
+    /// this file, failing with a unified diff when they differ.
+    #[clap(
+        conflicts_with_all = ["checksum", "exec", "output_pattern"],
+        global = true,
+        long,
+        value_name = "FILE"
+    )]
+    expect_file: Option<PathBuf>,
+
+    /// With --format parquet, write a `part-0.parquet` for each value of
+    /// this column, in a directory like `date=2024-06-01`, as Athena and
+    /// Glue expect. May be repeated, nesting the directories in order.
+    /// Writes under --output-dir, or with export, under the S3 URI.
+    #[clap(
+        conflicts_with_all = ["checksum", "exec", "expect_file", "output_pattern"],
+        global = true,
+        long,
+        value_name = "COLUMN"
+    )]
+    partition_by: Vec<String>,
+
+    /// With --partition-by, also write a `manifest.json` listing each part
+    /// file with its size, row count and SHA-256, as Redshift's COPY
+    /// accepts, so that loaders can check they have every part. Only
+    /// partitioned output gets a manifest, with one part per partition;
+    /// files written by --output-pattern or --partition-output do not.
+    #[clap(global = true, long, requires = "partition_by")]
+    manifest: bool,
+
+    /// Directory to write partitions into, with --partition-by.
+    #[clap(global = true, long, requires = "partition_by", value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// With --checksum, hash the rows regardless of their order.
+    #[clap(global = true, long, requires = "checksum")]
+    checksum_sorted: bool,
+
+    /// Expand each column holding JSON objects into a column for each of
+    /// their top-level keys, like `meta.user_id` and `meta.plan`, as
+    /// spreadsheets want.
+    #[clap(global = true, long)]
+    flatten: bool,
+
+    /// In JSON and NDJSON output, write the JSON text held in these
+    /// columns as nested JSON, rather than as strings. May be repeated, or
+    /// given as a comma separated list.
+    #[clap(global = true, long, value_delimiter = ',', value_name = "COLUMNS")]
+    parse_json_columns: Vec<String>,
+
+    /// In JSON and NDJSON output, write the values of `json` and `jsonb`
+    /// columns as nested JSON, rather than as strings.
+    #[clap(global = true, long)]
+    parse_json: bool,
+
+    /// Write the values of Postgres array columns, which arrive as text
+    /// like `{a,b,c}`, as JSON arrays: nested in JSON and NDJSON output,
+    /// and as text like `["a","b","c"]` otherwise.
+    #[clap(global = true, long)]
+    parse_arrays: bool,
+
+    /// Write the values of PostGIS geometry and geography columns, which
+    /// arrive as hex WKB, in this form. GeoJSON is nested in JSON and NDJSON
+    /// output.
+    #[clap(global = true, long, value_enum)]
+    geometry: Option<Geometry>,
+
+    /// In JSON and NDJSON output, write column labels as object keys in
+    /// this case, so `user_id` and `UserID` can both become `userId`.
+    #[clap(global = true, long, value_enum)]
+    key_case: Option<KeyCase>,
+
+    /// In JSON output, also list the `columns`, with the name, label, type,
+    /// nullability, precision and scale of each, so that consumers can build
+    /// a typed schema.
+    #[clap(global = true, long)]
+    include_metadata: bool,
+
+    /// Write each blob value to a file under this directory, in a
+    /// directory named for its column, and write the file's path in its
+    /// place.
+    #[clap(global = true, long, value_name = "DIR")]
+    blob_dir: Option<PathBuf>,
+
+    /// With --blob-dir, name each file by the row's value of this column,
+    /// instead of the row number.
+    #[clap(global = true, long, requires = "blob_dir", value_name = "COLUMN")]
+    blob_key: Option<String>,
+
+    /// Rename a column of the results, for loaders expecting other names
+    /// than the SQL gives. Other options then name the column by its new
+    /// name. May be repeated.
+    #[clap(
+        global = true,
+        long,
+        value_name = "OLD=NEW",
+        value_parser = transform::parse_rename
+    )]
+    rename: Vec<(String, String)>,
+
+    /// Tell apart columns of the results with the same label, which JSON
+    /// output otherwise repeats as keys many parsers mishandle. Applies
+    /// after --rename.
+    #[clap(global = true, long, value_enum)]
+    dedupe_columns: Option<DedupeColumns>,
+
+    /// Fail on any value this tool can't write, naming its column and row,
+    /// instead of writing it as `UNKNOWN`, or as `null` in JSON.
+    #[clap(global = true, long)]
+    strict: bool,
+
+    /// In JSON and NDJSON output, write integers as strings, since
+    /// JavaScript numbers lose precision past 2^53, as bigint ids can be.
+    #[clap(global = true, long)]
+    int64_as_string: bool,
+
+    /// In JSON and NDJSON output, write decimal and numeric values, which
+    /// the Data API returns as text, as JSON numbers, with every digit.
+    #[clap(global = true, long)]
+    numeric_decimals: bool,
+
+    /// In JSON and NDJSON output, how to write NaN and infinite doubles.
+    #[clap(value_enum, default_value = "null", global = true, long)]
+    non_finite: NonFinite,
+
+    /// Write JSON output on one line, rather than indented for reading,
+    /// for large extractions and log ingestion.
+    #[clap(global = true, long)]
+    compact: bool,
+
+    /// Keep each row of the results with this probability, for example
+    /// 0.01, to look over a huge table.
+    #[clap(global = true, long, value_name = "FRACTION", value_parser = transform::parse_fraction)]
+    sample: Option<f64>,
+
+    /// Keep this many rows of the results, chosen at random.
+    #[clap(conflicts_with = "sample", global = true, long, value_name = "N")]
+    sample_n: Option<usize>,
+
+    /// Sort the results by this column, ascending unless followed by
+    /// `:desc`. Numbers sort as numbers, and nulls last. May be repeated,
+    /// to break ties.
+    #[clap(global = true, long, value_name = "COLUMN[:desc]")]
+    sort_by: Vec<SortKey>,
+
+    /// Drop repeated rows from the results, keeping the first.
+    #[clap(global = true, long)]
+    distinct: bool,
+
+    /// Drop rows whose values in this column, and any other --distinct-on
+    /// columns, repeat an earlier row's. May be repeated.
+    #[clap(global = true, long, value_name = "COLUMN")]
+    distinct_on: Vec<String>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// SQL query.
-    query: String,
+    #[clap(required = true)]
+    query: Option<String>,
 }
 
-struct MyArns {
-    aws_secret_store_arn: String,
-    db_cluster_or_instance_arn: String,
-}
+#[derive(Clone, Debug, Subcommand)]
+enum Command {
+    /// Run a named query from the configuration file.
+    ///
+    /// Named queries live in the configuration file under `[queries.<name>]`
+    /// or as `<name>.sql` files in a `queries` directory next to it.
+    Run {
+        /// Name of the query to run.
+        name: String,
+    },
 
-/// Extract a name for each column
-fn format_header<'a>(result: &'a ExecuteStatementOutput) -> impl Iterator<Item = &'a str> {
-    // This seems pretty crazed...
-    result
-        .column_metadata
-        .as_ref()
-        .map_or(&[][..], |x| &**x)
-        .iter()
-        .map::<&'a str, _>(|column| {
-            if let Some(ref label) = column.label {
-                label
-            } else if let Some(ref name) = column.name {
-                name
-            } else {
-                "?"
-            }
-        })
-}
+    Import(ImportArgs),
 
-fn format_value(value: &Field) -> String {
-    match value {
-        Field::ArrayValue(inner) => format!("{:?}", *inner),
-        Field::BlobValue(inner) => format!("{:?}", *inner),
-        Field::BooleanValue(inner) => format!("{:?}", *inner),
-        Field::DoubleValue(inner) => format!("{:?}", *inner),
-        Field::IsNull(_) => "NULL".to_owned(),
-        Field::LongValue(inner) => format!("{:?}", *inner),
-        Field::StringValue(inner) => inner.to_owned(),
-        _ => "UNKNOWN".to_owned(), // punt!!
-    }
-}
+    BatchExecute(BatchExecuteArgs),
 
-fn one_row(values: &[Field]) -> impl Iterator<Item = String> + '_ {
-    values.iter().map(format_value)
-}
+    Copy(CopyArgs),
 
-/// Return an iterator of iterators of strings
-fn format_rows(
-    result: &ExecuteStatementOutput,
-) -> impl Iterator<Item = impl Iterator<Item = String> + '_> {
-    // This seems pretty crazed...
-    result
-        .records
-        .as_ref()
-        .map_or(&[][..], |x| &**x)
-        .iter()
-        .map(|record| one_row(record))
-}
+    Diff(DiffArgs),
 
-fn cluster_ids(db_clusters: &[DbCluster]) -> Vec<String> {
-    db_clusters
-        .iter()
-        .map(|db_cluster| {
-            db_cluster
-                .db_cluster_identifier
-                .as_ref()
-                .unwrap_or(&"".to_string())
-                .to_owned()
-        })
-        .collect()
-}
+    Explain(ExplainArgs),
 
-fn my_cluster(
-    requested_db_cluster_identifier: &Option<String>,
-    db_clusters: &[DbCluster],
-) -> Result<DbCluster> {
-    match requested_db_cluster_identifier {
-        Some(requested_db_cluster_identifier) => {
-            for db_cluster in db_clusters {
-                if let Some(ref db_cluster_identifier) = db_cluster.db_cluster_identifier {
-                    // Since this is an exact match, we assume there is only one.
-                    if requested_db_cluster_identifier == db_cluster_identifier {
-                        return Ok(db_cluster.to_owned());
-                    }
-                }
-            }
-            Err(anyhow!(
-                "No cluster matched \"{}\", available ids are {:?}",
-                requested_db_cluster_identifier.to_owned(),
-                cluster_ids(db_clusters),
-            ))
-        }
-        None => {
-            match db_clusters.len() {
-                // There is exactly one: go ahead and use it.
-                1 => Ok(db_clusters[0].to_owned()),
-                0 => Err(anyhow!("No clusters found")),
-                _ => Err(anyhow!(
-                    "Multiple clusters found, please specify one of {:?}",
-                    cluster_ids(db_clusters)
-                )),
-            }
-        }
-    }
-}
+    Migrate(MigrateArgs),
 
-fn secrets_for_db<'a>(
-    requested_db_cluster_resource_id: &str,
-    secret_list: &'a [SecretListEntry],
-) -> Vec<&'a SecretListEntry> {
-    // I don't know if this is a universal naming standard for secrets.
-    // If not, this code is badly wrong.
-    let name_starts_with =
-        "rds-db-credentials/".to_string() + requested_db_cluster_resource_id + "/";
-    secret_list
-        .iter()
-        .filter(|secret_list_entry| match secret_list_entry.name {
-            Some(ref name) => name.starts_with(&name_starts_with).to_owned(),
-            None => false,
-        })
-        .collect()
-}
+    Schema(SchemaArgs),
 
-fn user_id_from_secret(secret_list_entry: &SecretListEntry) -> String {
-    match secret_list_entry.name {
-        Some(ref name) => name.splitn(3, '/').last().unwrap_or("").to_string(),
-        None => "".to_string(),
-    }
-}
+    EnableDataApi(EnableDataApiArgs),
 
-fn user_names(secret_list: &[&SecretListEntry]) -> Vec<String> {
-    secret_list
-        .iter()
-        .map(|entry| user_id_from_secret(entry))
-        .collect()
-}
+    Cancel(CancelArgs),
 
-fn my_secret(
-    requested_db_cluster_resource_id: &str,
-    requested_db_user_id: &Option<String>,
-    secret_list: &[SecretListEntry],
-) -> Result<SecretListEntry> {
-    let db_secrets = secrets_for_db(requested_db_cluster_resource_id, secret_list);
-
-    match requested_db_user_id {
-        Some(requested_db_user_id) => {
-            for secret_list_entry in &db_secrets {
-                if let Some(ref name) = secret_list_entry.name {
-                    if name.ends_with(requested_db_user_id) {
-                        // Since this is an exact match, we assume there is only one.
-                        return Ok((*secret_list_entry).to_owned());
-                    }
-                }
-            }
-            Err(anyhow!(
-                "No cluster user matched \"{}\", available users are {:?}",
-                requested_db_user_id.to_owned(),
-                user_names(&db_secrets),
-            ))
-        }
-        None => {
-            match db_secrets.len() {
-                // There is exactly one: go ahead and use it.
-                1 => Ok(db_secrets[0].to_owned()),
-                0 => Err(anyhow!("No cluster user secrets found")),
-                _ => Err(anyhow!(
-                    "Multiple cluster users found, please specify one of {:?}",
-                    user_names(&db_secrets),
-                )),
-            }
-        }
-    }
-}
+    Snapshot(SnapshotArgs),
 
-async fn get_arns(
-    aws_sdk_config: &SdkConfig,
-    requested_db_cluster_identifier: &Option<String>,
-    requested_user_id: &Option<String>,
-) -> Result<MyArns> {
-    let rds_client = aws_sdk_rds::Client::new(aws_sdk_config);
-    let secrets_manager_client = aws_sdk_secretsmanager::Client::new(aws_sdk_config);
-
-    let fut1 = rds_client
-        .describe_db_clusters()
-        .max_records(100)
-        .send()
-        .map_err(|e| anyhow!("Failed to lookup clusters: {}", e));
-    let fut2 = secrets_manager_client
-        .list_secrets()
-        .max_results(100)
-        .send()
-        .map_err(|e| anyhow!("Failed to lookup secrets: {}", e));
-
-    let (db_cluster_message, list_secrets_response) = join!(fut1, fut2);
-    info!("{:?}", db_cluster_message);
-    info!("{:?}", list_secrets_response);
-    let db_cluster = match db_cluster_message?.db_clusters {
-        Some(db_clusters) => my_cluster(requested_db_cluster_identifier, &db_clusters)?,
-        None => return Err(anyhow!("Failed to find any RDS clusters")),
-    };
-    let secret_list_entry = match list_secrets_response?.secret_list {
-        Some(secret_list) => my_secret(
-            &db_cluster.db_cluster_resource_id.unwrap(),
-            requested_user_id,
-            &secret_list,
-        )?,
-        None => return Err(anyhow!("Failed to find any secrets")),
-    };
-    Ok(MyArns {
-        aws_secret_store_arn: secret_list_entry.arn.unwrap(),
-        db_cluster_or_instance_arn: db_cluster.db_cluster_arn.unwrap(),
-    })
+    Script(ScriptArgs),
+
+    History(HistoryArgs),
+
+    /// Run a statement from the history again, against the same target.
+    ///
+    /// Output options, such as --format, apply as now given.
+    Rerun {
+        /// Number of the statement, as listed by `history`. Defaults to the
+        /// most recent.
+        id: Option<usize>,
+    },
+
+    /// Print a shell completion script.
+    ///
+    /// For example, `query-rds-data completions bash > /etc/bash_completion.d/query-rds-data`.
+    Completions {
+        /// Shell to complete in.
+        shell: clap_complete::Shell,
+    },
+
+    /// Print a roff man page, for packagers.
+    #[clap(hide = true)]
+    Mangen {
+        /// Instead, write man pages for every subcommand into this directory.
+        #[clap(long)]
+        out_dir: Option<PathBuf>,
+    },
+
+    /// Run a query and upload the formatted results to S3.
+    ///
+    /// The output is streamed through a multipart upload, so it needs no
+    /// local disk space.
+    Export {
+        /// Destination, as s3://bucket/key.
+        #[clap(long)]
+        s3_uri: String,
+
+        #[clap(flatten)]
+        s3_options: S3Options,
+
+        /// SQL query.
+        query: String,
+    },
 }
 
-fn csv_output(result: &ExecuteStatementOutput) -> Result<()> {
+fn csv_output(result: &ExecuteStatementOutput, style: Style, mut writer: impl Write) -> Result<()> {
     if result.number_of_records_updated > 0 || result.column_metadata.is_none() {
-        println!(
+        writeln!(
+            writer,
             "number_of_records_updated: {}",
             result.number_of_records_updated
-        )
+        )?
     }
-    let mut wtr = csv::Writer::from_writer(stdout());
-    wtr.write_record(format_header(result))?;
-    for row in format_rows(result) {
-        wtr.write_record(row)?;
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(format_header(result).map(|name| style.header(name)))?;
+    for record in result.records.as_deref().unwrap_or(&[]) {
+        wtr.write_record(
+            record
+                .iter()
+                .map(|value| style.value(value, &format_value(value))),
+        )?;
     }
+    wtr.flush()?;
     Ok(())
 }
 
@@ -319,61 +841,210 @@ struct Record {
     record: Vec<(String, Value)>,
 }
 
+/// What --include-metadata tells of a column.
 #[derive(Clone, Debug, Default, PartialEq, Serialize)]
-struct CookedResponse {
+#[serde(rename_all = "camelCase")]
+struct CookedColumn {
+    name: Option<String>,
+    label: Option<String>,
+    type_name: Option<String>,
+    /// Unknown, as `null`, when the database does not say.
+    nullable: Option<bool>,
+    precision: i32,
+    scale: i32,
+}
+
+impl From<&ColumnMetadata> for CookedColumn {
+    fn from(column: &ColumnMetadata) -> CookedColumn {
+        CookedColumn {
+            name: column.name.clone(),
+            label: column.label.clone(),
+            type_name: column.type_name.clone(),
+            // As JDBC's columnNoNulls, columnNullable and columnNullableUnknown.
+            nullable: match column.nullable {
+                0 => Some(false),
+                1 => Some(true),
+                _ => None,
+            },
+            precision: column.precision,
+            scale: column.scale,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CookedResponse<'a> {
+    /// The columns, with --include-metadata.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    columns: Option<Vec<CookedColumn>>,
+
     /// The number of records updated by the request.
     #[serde(rename = "numberOfRecordsUpdated")]
     number_of_records_updated: i64,
 
     /// The records returned by the SQL statement.
-    pub records: Vec<Record>,
+    records: CookedRecords<'a>,
 }
 
-fn field_value(field: &Field) -> Value {
-    match field {
-        Field::ArrayValue(_array_value) => Value::Null, // punt!!
-        Field::BlobValue(_blob_value) => Value::Null,   // punt!!
-        Field::BooleanValue(boolean_value) => Value::from(*boolean_value),
-        Field::DoubleValue(double_value) => Value::from(*double_value),
-        Field::IsNull(_) => Value::Null,
-        Field::LongValue(long_value) => Value::from(*long_value),
-        Field::StringValue(string_value) => Value::from(string_value.clone()),
-        _ => Value::Null, // punt!!
+/// The records of a result, each made into JSON only as it is written, so
+/// that memory use stays flat however many there are.
+struct CookedRecords<'a> {
+    args: &'a MyArgs,
+    header: Vec<String>,
+    json_columns: Vec<bool>,
+    records: &'a [Vec<Field>],
+}
+
+impl CookedRecords<'_> {
+    fn iter(&self) -> impl Iterator<Item = Result<Record>> + '_ {
+        self.records.iter().enumerate().map(|(index, record)| {
+            annotate_fields(
+                self.args,
+                &self.header,
+                &self.json_columns,
+                index + 1,
+                record,
+            )
+        })
     }
 }
 
-fn annotate_fields(header: &[&str], record: &[Field]) -> Record {
-    Record {
+impl Serialize for CookedRecords<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.records.len()))?;
+        for record in self.iter() {
+            seq.serialize_element(&record.map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+/// The record, as JSON, numbering its row from 1 for any error.
+fn annotate_fields(
+    args: &MyArgs,
+    header: &[String],
+    json_columns: &[bool],
+    row: usize,
+    record: &[Field],
+) -> Result<Record> {
+    Ok(Record {
         record: header
             .iter()
             .zip(record.iter())
-            .map(|(key, field)| ((*key).to_owned(), field_value(field)))
-            .collect(),
-    }
+            .zip(json_columns)
+            .map(|((key, field), json)| {
+                let value = match field {
+                    Field::StringValue(text) if *json => {
+                        serde_json::from_str(text).unwrap_or_else(|_| field_value(field))
+                    }
+                    Field::LongValue(long) if args.int64_as_string => Value::from(long.to_string()),
+                    Field::DoubleValue(double) if !double.is_finite() => match args.non_finite {
+                        NonFinite::Null => Value::Null,
+                        NonFinite::String => Value::from(match double {
+                            double if double.is_nan() => "NaN",
+                            double if double.is_sign_positive() => "Infinity",
+                            _ => "-Infinity",
+                        }),
+                        NonFinite::Error => {
+                            return Err(anyhow!(
+                                "Column \"{}\" of row {} is {}, which JSON has no number for",
+                                key,
+                                row,
+                                double
+                            ))
+                        }
+                    },
+                    field => field_value(field),
+                };
+                Ok((key.clone(), value))
+            })
+            .collect::<Result<_>>()?,
+    })
 }
 
-fn cook_response(result: &ExecuteStatementOutput) -> CookedResponse {
-    let header: Vec<&str> = format_header(result).collect();
-    CookedResponse {
+fn cook_response<'a>(
+    args: &'a MyArgs,
+    result: &'a ExecuteStatementOutput,
+) -> Result<CookedResponse<'a>> {
+    let json_columns = transform::json_columns(args, result)?;
+    let header: Vec<String> = format_header(result)
+        .map(|label| match args.key_case {
+            Some(key_case) => key_case.convert(label),
+            None => label.to_owned(),
+        })
+        .collect();
+    Ok(CookedResponse {
+        columns: args.include_metadata.then(|| {
+            result
+                .column_metadata
+                .iter()
+                .flatten()
+                .map(CookedColumn::from)
+                .collect()
+        }),
         number_of_records_updated: result.number_of_records_updated,
-        records: result
-            .records
-            .as_ref()
-            .map_or(&[][..], |x| &**x)
-            .iter()
-            .map(|record| annotate_fields(&header, record))
-            .collect(),
-    }
+        records: CookedRecords {
+            args,
+            header,
+            json_columns,
+            records: result.records.as_ref().map_or(&[][..], |x| &**x),
+        },
+    })
 }
 
-fn cooked_output(result: &ExecuteStatementOutput) -> Result<()> {
-    serde_json::to_writer_pretty(stdout(), &cook_response(result))?;
+fn cooked_output(
+    args: &MyArgs,
+    result: &ExecuteStatementOutput,
+    mut writer: impl Write,
+) -> Result<()> {
+    let cooked = cook_response(args, result)?;
+    if args.compact {
+        serde_json::to_writer(&mut writer, &cooked)?;
+    } else {
+        serde_json::to_writer_pretty(&mut writer, &cooked)?;
+    }
     // We'd like to write out a final newline. Ignore any failure to do so.
-    let _result = stdout().write(b"\n");
+    let _result = writer.write(b"\n");
+    Ok(())
+}
+
+fn ndjson_output(
+    args: &MyArgs,
+    result: &ExecuteStatementOutput,
+    mut writer: impl Write,
+) -> Result<()> {
+    for record in cook_response(args, result)?.records.iter() {
+        serde_json::to_writer(&mut writer, &record?)?;
+        writer.write_all(b"\n")?;
+    }
     Ok(())
 }
 
-async fn aws_sdk_config(args: &MyArgs) -> SdkConfig {
+fn write_output<W: Write + Send>(
+    args: &MyArgs,
+    style: Style,
+    result: &ExecuteStatementOutput,
+    mut writer: W,
+) -> Result<()> {
+    let _span = info_span!("format", format = ?args.format).entered();
+    match args.format {
+        Format::Csv => {
+            if args.bom {
+                writer.write_all("\u{feff}".as_bytes())?;
+            }
+            csv_output(result, style, writer)
+        }
+        Format::Json => cooked_output(args, result, writer),
+        Format::Ndjson => ndjson_output(args, result, writer),
+        Format::Parquet => parquet_output::parquet_output(result, writer),
+        Format::Table => pretty::table_output(result, style, writer),
+    }
+}
+
+async fn aws_sdk_config(args: &MyArgs, region: Option<&String>) -> SdkConfig {
     let base = aws_config::defaults(BehaviorVersion::latest()).identity_cache(
         IdentityCache::lazy()
             .load_timeout(Duration::from_secs(90))
@@ -383,43 +1054,1155 @@ async fn aws_sdk_config(args: &MyArgs) -> SdkConfig {
         None => base,
         Some(profile_name) => base.profile_name(profile_name),
     };
-    let with_overrides = match &args.region {
+    let with_overrides = match region {
         None => with_profile,
         Some(region_name) => with_profile.region(Region::new(region_name.clone())),
     };
-    with_overrides.load().await
+    let mut timeout_config = TimeoutConfig::builder();
+    timeout_config
+        .set_connect_timeout(args.connect_timeout)
+        .set_operation_timeout(args.operation_timeout);
+    let with_timeouts = with_overrides.timeout_config(timeout_config.build());
+    let default_client = || http::default_client(args.ca_bundle.as_ref());
+    let mut http_client = args.ca_bundle.is_some().then(default_client);
+    if let Some(trace_header) = http::trace_header() {
+        http_client = Some(SharedHttpClient::new(http::TraceHttpClient::new(
+            http_client.unwrap_or_else(default_client),
+            trace_header,
+        )));
+    }
+    if args.debug_http {
+        http_client = Some(SharedHttpClient::new(http::DebugHttpClient::new(
+            http_client.unwrap_or_else(default_client),
+        )));
+    }
+    let with_http = match http_client {
+        Some(http_client) => with_timeouts.http_client(http_client),
+        None => with_timeouts,
+    };
+    with_http.load().await
+}
+
+/// Work out the SQL and parameters to run, from the command line and config.
+fn statement(args: &MyArgs, config: &Config) -> Result<(String, Vec<Param>)> {
+    match &args.command {
+        Some(Command::Run { name }) => {
+            let named_query = config.query(name)?;
+            let params = merge_params(&named_query.default_params()?, &args.params);
+            Ok((named_query.sql.clone(), params))
+        }
+        Some(Command::Export { query, .. }) => Ok((query.clone(), args.params.clone())),
+        Some(Command::Diff(diff_args)) => Ok((diff_args.query.clone(), args.params.clone())),
+        Some(Command::BatchExecute(batch_args)) => {
+            Ok((batch_args.sql.clone(), args.params.clone()))
+        }
+        Some(Command::Explain(explain_args)) => {
+            Ok((explain_args.query.clone(), args.params.clone()))
+        }
+        Some(Command::EnableDataApi(EnableDataApiArgs {
+            query: Some(query), ..
+        })) => Ok((query.clone(), args.params.clone())),
+        Some(Command::Script(script_args)) => Ok((script::read(script_args)?, args.params.clone())),
+        Some(Command::Snapshot(snapshot_args)) => match snapshot_args.action.query() {
+            Some(query) => Ok((query.to_owned(), args.params.clone())),
+            None => {
+                let named_query = config.query(snapshot_args.action.name())?;
+                let params = merge_params(&named_query.default_params()?, &args.params);
+                Ok((named_query.sql.clone(), params))
+            }
+        },
+        _ => match &args.query {
+            Some(query) => Ok((query.clone(), args.params.clone())),
+            None => Err(anyhow!("No SQL query given")),
+        },
+    }
+}
+
+fn dry_run_output(request: &Request) {
+    let none = "(none)".to_owned();
+    let target = &request.target;
+    println!("resource_arn: {}", target.resource_arn);
+    println!("secret_arn: {}", target.secret_arn);
+    println!("database: {}", target.database.as_ref().unwrap_or(&none));
+    println!("schema: {}", target.schema.as_ref().unwrap_or(&none));
+    for param in &request.params {
+        println!("param: {}", param);
+    }
+    println!("sql: {}", request.sql);
+}
+
+/// The target, found by discovery, or with --offline-discovery, in the
+/// ARN cache. What discovery finds is cached for next time.
+async fn cluster_target(
+    args: &MyArgs,
+    config: &SdkConfig,
+    cluster_id: &Option<String>,
+) -> Result<Target> {
+    let key = arn_cache::key(&[
+        args.profile.as_deref(),
+        config.region().map(|region| region.as_ref()),
+        cluster_id.as_deref(),
+        args.user_id.as_deref(),
+    ]);
+    let arn_cache = ArnCache::new();
+    let arns = if args.offline_discovery {
+        arn_cache?.get(&key).ok_or_else(|| {
+            anyhow!(
+                "--offline-discovery has no cached ARNs for this target, run once \
+                 without it, or give --resource-arn and --secret-arn"
+            )
+        })?
+    } else {
+        let arns = get_arns(config, cluster_id, &args.user_id).await?;
+        match arn_cache {
+            Ok(arn_cache) => arn_cache.put(&key, &arns),
+            Err(e) => warn!("Not caching ARNs: {}", e),
+        }
+        arns
+    };
+    Ok(Target::new(
+        arns,
+        args.database.clone(),
+        args.schema.clone(),
+    ))
+}
+
+/// The cluster identifier given, directly or by DB instance.
+async fn cluster_id(args: &MyArgs, config: &SdkConfig) -> Result<Option<String>> {
+    match &args.instance_id {
+        Some(_) if args.offline_discovery => Err(anyhow!(
+            "--offline-discovery can not look up the cluster of --db-instance-identifier"
+        )),
+        Some(instance_id) => Ok(Some(instance_cluster_id(config, instance_id).await?)),
+        None => Ok(args.cluster_id.clone()),
+    }
+}
+
+/// The engine of the cluster, or DB instance, with this ARN.
+async fn resource_engine(config: &SdkConfig, resource_arn: &str) -> Result<String> {
+    let client = AwsClient::new(config);
+    let engine = match resource_arn.rsplit(':').collect::<Vec<_>>()[..] {
+        [name, "db", ..] => client.describe_db_instance(name.to_owned()).await?.engine,
+        _ => client
+            .describe_db_clusters()
+            .await?
+            .into_iter()
+            .find(|db_cluster| db_cluster.db_cluster_arn.as_deref() == Some(resource_arn))
+            .and_then(|db_cluster| db_cluster.engine),
+    };
+    engine.ok_or_else(|| anyhow!("Found no engine for {}", resource_arn))
+}
+
+async fn target(args: &MyArgs, config: &SdkConfig) -> Result<Target> {
+    if let (Some(resource_arn), Some(secret_arn)) = (&args.resource_arn, &args.secret_arn) {
+        let engine = match (&args.engine, &args.resource_engine) {
+            (Some(engine), _) | (None, Some(engine)) => engine.clone(),
+            (None, None) if args.offline_discovery => {
+                return Err(anyhow!(
+                "--offline-discovery can not look up the engine of --resource-arn, give --engine"
+            ))
+            }
+            (None, None) => resource_engine(config, resource_arn)
+                .await
+                .map_err(|e| anyhow!("{}, or give --engine", e))?,
+        };
+        let arns = MyArns {
+            aws_secret_store_arn: secret_arn.clone(),
+            db_cluster_or_instance_arn: resource_arn.clone(),
+            engine: Some(engine),
+        };
+        return Ok(Target::new(
+            arns,
+            args.database.clone(),
+            args.schema.clone(),
+        ));
+    }
+    let cluster_id = cluster_id(args, config).await?;
+    cluster_target(args, config, &cluster_id).await
+}
+
+/// Keep --username and --password in a temporary secret, and target it
+/// instead of looking up secrets. Nothing is created for a dry run.
+async fn ephemeral_secret(
+    args: &mut MyArgs,
+    config: &SdkConfig,
+) -> Result<Option<EphemeralSecret>> {
+    let (Some(username), Some(password)) = (args.username.clone(), args.password.clone()) else {
+        return Ok(None);
+    };
+    let db_clusters = AwsClient::new(config).describe_db_clusters().await?;
+    let db_cluster = my_cluster(&cluster_id(args, config).await?, &db_clusters)?;
+    let cluster_id = db_cluster.db_cluster_identifier.unwrap_or_default();
+    args.resource_arn = db_cluster.db_cluster_arn;
+    args.resource_engine = db_cluster.engine;
+    if args.dry_run {
+        args.secret_arn = Some(format!("(temporary secret for {})", username));
+        return Ok(None);
+    }
+    let secret = EphemeralSecret::create(config, &cluster_id, &username, &password).await?;
+    args.secret_arn = Some(secret.arn().to_owned());
+    Ok(Some(secret))
+}
+
+/// The SQL and parameters to run, before any target specific templating.
+fn untargeted_statement(args: &MyArgs, my_config: &Config) -> Result<(String, Vec<Param>)> {
+    let (sql, params) = statement(args, my_config)?;
+    let sql = if args.env_substitute {
+        template::substitute_env(&sql)?
+    } else {
+        sql
+    };
+    // Catch bad parameter values before talking to AWS.
+    sql_parameters(&params)?;
+    Ok((sql, params))
+}
+
+/// Finish preparing the statement for the target it will run against.
+fn request(args: &MyArgs, target: Target, sql: &str, params: &[Param]) -> Result<Request> {
+    let sql = if args.vars.is_empty() {
+        sql.to_owned()
+    } else {
+        template::render(sql, &args.vars, target.dialect())?
+    };
+    if args.check {
+        target.dialect().check_syntax(&sql)?;
+    }
+    let request = Request {
+        target,
+        params: params.to_vec(),
+        sql,
+    };
+    if args.read_only {
+        policy::read_only(&request)?;
+    }
+    Ok(request)
+}
+
+/// The --init-sql statements, as requests against the target, checked as
+/// the query is, except that --read-only also lets them set session
+/// variables.
+fn init_requests(args: &MyArgs, target: &Target) -> Result<Vec<Request>> {
+    args.init_sql
+        .iter()
+        .map(|sql| {
+            if args.check {
+                target.dialect().check_syntax(sql)?;
+            }
+            let request = Request {
+                target: target.clone(),
+                params: Vec::new(),
+                sql: sql.clone(),
+            };
+            if args.read_only {
+                policy::read_only_setup(&request)?;
+            }
+            Ok(request)
+        })
+        .collect()
+}
+
+/// Fill in the arguments from a named target, except those given on the
+/// command line.
+fn apply_target_alias(args: &mut MyArgs, matches: &ArgMatches, alias: &TargetAlias) -> Result<()> {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let fill = |id: &str, field: &mut Option<String>, value: &Option<String>| {
+        if value.is_some() && !given(id) {
+            field.clone_from(value);
+        }
+    };
+    fill("profile", &mut args.profile, &alias.profile);
+    fill("cluster_id", &mut args.cluster_id, &alias.cluster);
+    fill("user_id", &mut args.user_id, &alias.user);
+    fill("database", &mut args.database, &alias.database);
+    fill("schema", &mut args.schema, &alias.schema);
+    if let Some(region) = &alias.region {
+        if !given("region") {
+            args.region = vec![region.clone()];
+        }
+    }
+    if let Some(format) = &alias.format {
+        if !given("format") {
+            args.format = Format::from_str(format, true).map_err(|_e| {
+                anyhow!(
+                    "Unknown format \"{}\", available formats are {:?}",
+                    format,
+                    Format::value_variants()
+                        .iter()
+                        .filter_map(|format| format.to_possible_value())
+                        .map(|value| value.get_name().to_owned())
+                        .collect::<Vec<_>>()
+                )
+            })?;
+        }
+    }
+    args.read_only |= alias.read_only;
+    Ok(())
+}
+
+fn audit_log_path<'a>(args: &'a MyArgs, my_config: &'a Config) -> Option<&'a PathBuf> {
+    args.audit_log.as_ref().or(my_config.audit.log.as_ref())
+}
+
+fn open_audit_log(args: &MyArgs, my_config: &Config) -> Result<Option<AuditLog>> {
+    audit_log_path(args, my_config)
+        .map(|path| AuditLog::open(path, args.audit_hash_sql || my_config.audit.hash_sql))
+        .transpose()
+}
+
+fn audit_history(args: &MyArgs, my_config: &Config) -> Result<Vec<AuditEntry>> {
+    let path = audit_log_path(args, my_config).ok_or_else(|| {
+        anyhow!("History needs an audit log, from --audit-log or [audit] in the configuration file")
+    })?;
+    audit::read(path)
+}
+
+/// Turn the arguments into those running the logged statement again, as a
+/// plain query against exactly the same target.
+fn rerun_args(args: &mut MyArgs, entry: AuditEntry) -> Result<()> {
+    args.query = Some(
+        entry
+            .sql
+            .ok_or_else(|| anyhow!("That statement was logged without its SQL"))?,
+    );
+    args.params = entry
+        .params
+        .iter()
+        .map(|param| param.parse())
+        .collect::<Result<_>>()?;
+    args.resource_arn = Some(entry.cluster);
+    args.secret_arn = Some(entry.secret);
+    args.resource_engine = entry.engine;
+    args.database = entry.database;
+    args.schema = entry.schema;
+    if let Some(region) = entry.region {
+        args.region = vec![region];
+    }
+    if entry.profile.is_some() {
+        args.profile = entry.profile;
+    }
+    args.command = None;
+    Ok(())
+}
+
+/// Execute the request with the driver, after any --init-sql, checking
+/// and auditing each statement.
+async fn driver_execute(
+    args: &MyArgs,
+    guard: &Guard,
+    config: &SdkConfig,
+    request: Request,
+) -> Result<ExecuteStatementOutput> {
+    let init = init_requests(args, &request.target)?;
+    let checks: Vec<(&SdkConfig, &Request)> = init
+        .iter()
+        .chain([&request])
+        .map(|request| (config, request))
+        .collect();
+    guard.check(&checks, None).await?;
+    let execution = driver::execute(
+        config,
+        guard,
+        args.driver,
+        args.transient_retries,
+        &init,
+        args.isolation,
+        request.clone(),
+    );
+    guard.run(config, &request, execution).await
+}
+
+/// Use a cached result when there is a fresh one, and otherwise execute
+/// the request, caching its result. Cache hits are not audited, since
+/// nothing was executed.
+async fn cached_execute(
+    args: &MyArgs,
+    guard: &Guard,
+    config: &SdkConfig,
+    cache: Option<&Cache>,
+    request: Request,
+) -> Result<ExecuteStatementOutput> {
+    let Some(cache) = cache else {
+        return driver_execute(args, guard, config, request).await;
+    };
+    if let Some(output) = cache.get(&request) {
+        return Ok(output);
+    }
+    let result = driver_execute(args, guard, config, request.clone()).await;
+    if let Ok(output) = &result {
+        cache.put(&request, output);
+    }
+    result
+}
+
+/// Run a SQL statement, against one or many targets, and write out the
+/// results, keeping their values in the metrics, if given. Returns what
+/// the statements did, for the summary.
+async fn query(
+    args: &MyArgs,
+    my_config: &Config,
+    guard: &Guard,
+    config: &SdkConfig,
+    metrics: Option<&Metrics>,
+) -> Result<Summary> {
+    let (sql, params) = untargeted_statement(args, my_config)?;
+    let progress = Progress::start("resolving cluster");
+    let multi_region = fanout::multi_region(args);
+    let multi_database = fanout::multi_database(args);
+    let configs = if multi_region {
+        fanout::region_configs(args, config).await?
+    } else {
+        vec![config.clone()]
+    };
+    let region_targets = fanout::concurrently(
+        args.concurrency,
+        configs.iter().map(|config| async move {
+            let region = config.region().map(ToString::to_string).unwrap_or_default();
+            let mut targets = if args.all_clusters {
+                fanout::all_cluster_targets(args, config).await
+            } else {
+                target(args, config)
+                    .await
+                    .map(|target| vec![(String::new(), target)])
+            };
+            if let (Ok(cluster_targets), true) = (&mut targets, multi_database) {
+                targets =
+                    fanout::database_targets(args, guard, config, std::mem::take(cluster_targets))
+                        .await;
+            }
+            match targets {
+                Ok(targets) => Ok((region, config, targets)),
+                Err(e) if multi_region => Err(anyhow!("{}: {}", region, e)),
+                Err(e) => Err(e),
+            }
+        }),
+    )
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+    let mut columns = Vec::new();
+    if multi_region {
+        columns.push("region");
+    }
+    if args.all_clusters {
+        columns.push("cluster_id");
+    }
+    if multi_database {
+        columns.push("database");
+    }
+    let (foreach_names, parameter_sets) = match &args.foreach {
+        Some(path) => foreach::read(path)?,
+        None => (Vec::new(), vec![Vec::new()]),
+    };
+    columns.extend(foreach_names.iter().map(String::as_str));
+    let mut requests = Vec::new();
+    for (region, config, targets) in region_targets {
+        for (cluster_id, target) in targets {
+            let mut labels = Vec::new();
+            if multi_region {
+                labels.push(region.clone());
+            }
+            if args.all_clusters {
+                labels.push(cluster_id);
+            }
+            if multi_database {
+                labels.push(target.database.clone().unwrap_or_default());
+            }
+            for parameter_set in &parameter_sets {
+                let mut labels = labels.clone();
+                labels.extend(parameter_set.iter().map(|param| param.value.clone()));
+                let params = merge_params(&params, parameter_set);
+                requests.push((
+                    labels,
+                    config,
+                    request(args, target.clone(), &sql, &params)?,
+                ));
+            }
+        }
+    }
+    let time = output_pattern::time_now()?;
+    let output_paths = match (&args.output_pattern, &args.partition_output) {
+        (Some(pattern), None) => Some(
+            requests
+                .iter()
+                .enumerate()
+                .map(|(index, (_labels, config, request))| {
+                    let placeholders = output_pattern::Placeholders {
+                        index: index + 1,
+                        region: &config.region().map(ToString::to_string).unwrap_or_default(),
+                        cluster: output_pattern::cluster_id(&request.target.resource_arn),
+                        time: &time,
+                        value: None,
+                    };
+                    output_pattern::render(pattern, &placeholders)
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        _ => None,
+    };
+    let init = requests
+        .iter()
+        .map(|(_labels, _config, request)| init_requests(args, &request.target))
+        .collect::<Result<Vec<_>>>()?;
+    if args.dry_run {
+        drop(progress);
+        for (index, (_labels, _config, request)) in requests.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            dry_run_output(request);
+            for sql in &args.init_sql {
+                println!("init_sql: {}", sql);
+            }
+            if let Some(output_paths) = &output_paths {
+                println!("output: {}", output_paths[index].display());
+            }
+        }
+        if let (Some(pattern), Some(column)) = (&args.output_pattern, &args.partition_output) {
+            println!("\noutput: {}, for each value of {}", pattern, column);
+        }
+        return Ok(Summary::default());
+    }
+    if !my_config.policy.is_empty() {
+        progress.stage("checking policy");
+    }
+    let checks: Vec<(&SdkConfig, &Request)> = requests
+        .iter()
+        .zip(&init)
+        .flat_map(|((_labels, config, request), init)| {
+            init.iter()
+                .chain([request])
+                .map(|request| (*config, request))
+        })
+        .collect();
+    guard.check(&checks, Some(&progress)).await?;
+    let mut summary = Summary {
+        targets: requests
+            .iter()
+            .map(|(_labels, _config, request)| {
+                (
+                    request.target.resource_arn.clone(),
+                    request.target.database.clone(),
+                )
+            })
+            .collect(),
+        ..Summary::default()
+    };
+    let cache = args
+        .cache
+        .map(|ttl| Cache::new(ttl, args.driver, &args.init_sql, args.isolation))
+        .transpose()?;
+    let cache = cache.as_ref();
+    progress.stage("executing statement");
+    let outcomes = fanout::concurrently(
+        args.concurrency,
+        requests
+            .into_iter()
+            .map(|(labels, config, request)| async move {
+                (
+                    labels,
+                    cached_execute(args, guard, config, cache, request).await,
+                )
+            }),
+    )
+    .await;
+    drop(progress);
+    let mut results = Vec::with_capacity(outcomes.len());
+    for (labels, result) in outcomes {
+        if let Ok(output) = &result {
+            summary.rows += metrics::row_count(output);
+            summary.returned_rows |= output.records.is_some();
+            summary.records_updated += output.number_of_records_updated;
+            summary
+                .request_ids
+                .extend(output.request_id().map(str::to_owned));
+        }
+        results.push((labels, result));
+    }
+    if let Some(output_paths) = output_paths {
+        let results = results
+            .into_iter()
+            .map(|(labels, result)| match result {
+                Ok(output) => Ok(output),
+                Err(e) if labels.is_empty() => Err(e),
+                Err(e) => Err(anyhow!("{}: {}", labels.join("/"), e)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (path, mut output) in output_paths.iter().zip(results) {
+            transform::apply(args, &mut output)?;
+            let mut writer = BufWriter::new(output_pattern::create(path)?);
+            write_output(args, Style::plain(), &output, &mut writer)?;
+            writer
+                .flush()
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        }
+        return Ok(summary);
+    }
+    let mut execute_statement_output = if columns.is_empty() {
+        match results.pop() {
+            Some((_labels, result)) => result?,
+            None => return Err(anyhow!("Nothing to run")),
+        }
+    } else {
+        let results = results
+            .into_iter()
+            .map(|(labels, result)| match result {
+                Ok(output) => Ok((labels, output)),
+                Err(e) => Err(anyhow!("{}: {}", labels.join("/"), e)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        fanout::merge_results(&columns, results)?
+    };
+    transform::apply(args, &mut execute_statement_output)?;
+    if let Some(metrics) = metrics {
+        metrics.set_values(&execute_statement_output);
+    }
+    if let (Some(pattern), Some(column)) = (&args.output_pattern, &args.partition_output) {
+        let region = config.region().map(ToString::to_string).unwrap_or_default();
+        let cluster = summary
+            .targets
+            .first()
+            .map_or("", |(arn, _database)| output_pattern::cluster_id(arn));
+        let parts = partition::split_by_value(&execute_statement_output, column)?;
+        for (index, (value, part)) in parts.iter().enumerate() {
+            let placeholders = output_pattern::Placeholders {
+                index: index + 1,
+                region: &region,
+                cluster,
+                time: &time,
+                value: Some(value),
+            };
+            let path = output_pattern::render(pattern, &placeholders)?;
+            let mut writer = BufWriter::new(output_pattern::create(&path)?);
+            write_output(args, Style::plain(), part, &mut writer)?;
+            writer
+                .flush()
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        }
+        return Ok(summary);
+    }
+    if let Some(algorithm) = args.checksum {
+        let checksum =
+            checksum::checksum(algorithm, &execute_statement_output, args.checksum_sorted);
+        println!("{}", checksum);
+        return Ok(summary);
+    }
+    if let Some(path) = &args.expect_file {
+        let mut output = Vec::new();
+        write_output(args, Style::plain(), &execute_statement_output, &mut output)?;
+        return expect::compare(path, &output).map(|()| summary);
+    }
+    if let Some(command) = &args.exec {
+        return exec::each_row(command, args.concurrency, &execute_statement_output)
+            .await
+            .map(|()| summary);
+    }
+    if !args.partition_by.is_empty() {
+        let destination = match (&args.command, &args.output_dir) {
+            (
+                Some(Command::Export {
+                    s3_uri, s3_options, ..
+                }),
+                _,
+            ) => partition::Destination::S3(s3_uri, s3_options),
+            (_, Some(dir)) => partition::Destination::Dir(dir),
+            (_, None) => return Err(anyhow!("--partition-by needs --output-dir, or export")),
+        };
+        partition::write(
+            config,
+            destination,
+            &args.partition_by,
+            &execute_statement_output,
+            args.manifest,
+        )?;
+        return Ok(summary);
+    }
+    let written = match &args.command {
+        Some(Command::Export {
+            s3_uri, s3_options, ..
+        }) => {
+            let mut s3_writer =
+                S3Writer::new(config, s3_uri, args.format.content_type(), s3_options)?;
+            write_output(
+                args,
+                Style::plain(),
+                &execute_statement_output,
+                &mut s3_writer,
+            )?;
+            s3_writer.finish()
+        }
+        _ if !args.no_pager && stdout().is_terminal() => {
+            let mut output = Vec::new();
+            write_output(
+                args,
+                Style::for_stdout(args.color).max_col_width(args.max_col_width),
+                &execute_statement_output,
+                &mut output,
+            )?;
+            pager::show(&output)
+        }
+        _ => write_output(
+            args,
+            Style::for_stdout(args.color).max_col_width(args.max_col_width),
+            &execute_statement_output,
+            stdout(),
+        ),
+    };
+    written.map(|()| summary)
+}
+
+/// Run the query, recording how long it took, how many rows it returned
+/// and whether it failed, in the metrics, if given, and with
+/// --emit-metrics, in CloudWatch.
+async fn observed_query(
+    args: &MyArgs,
+    my_config: &Config,
+    guard: &Guard,
+    config: &SdkConfig,
+    metrics: Option<&Metrics>,
+) -> Result<()> {
+    let started = Instant::now();
+    let result = query(args, my_config, guard, config, metrics).await;
+    let duration = started.elapsed();
+    let rows = result.as_ref().ok().map(|summary| summary.rows);
+    if let Some(metrics) = metrics {
+        metrics.record_run(duration, rows);
+    }
+    if let (Some(namespace), false) = (&args.emit_metrics, args.dry_run) {
+        if let Err(e) = cloudwatch::emit(config, namespace, duration, rows).await {
+            eprintln!("{}", e);
+        }
+    }
+    let result = result.and_then(|summary| {
+        let updated = summary.records_updated;
+        let unexpected = match (args.expect_updates, args.min_updates) {
+            (Some(expected), _) if args.dry_run || updated == expected => None,
+            (Some(expected), _) => Some((expected, false)),
+            (None, Some(minimum)) if !args.dry_run && updated < minimum => Some((minimum, true)),
+            (None, _) => None,
+        };
+        match unexpected {
+            Some((expected, minimum)) => Err(Error::UnexpectedUpdates {
+                expected,
+                minimum,
+                updated,
+            }
+            .into()),
+            None => Ok(summary),
+        }
+    });
+    if let (Ok(summary), false) = (&result, args.dry_run) {
+        if !args.quiet {
+            eprintln!("{}", summary.line(duration));
+        }
+        if let Some(path) = &args.summary_json {
+            summary.write_json(path, duration)?;
+        }
+    }
+    result.map(|_summary| ())
+}
+
+/// The exit status when --expect-updates or --min-updates is not met, so
+/// that scripts can tell it from other failures.
+const UNEXPECTED_UPDATES_EXIT_CODE: u8 = 3;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // As returning the error from main would print it.
+            eprintln!("Error: {:?}", e);
+            match e.downcast_ref::<Error>() {
+                Some(Error::UnexpectedUpdates { .. }) => {
+                    ExitCode::from(UNEXPECTED_UPDATES_EXIT_CODE)
+                }
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn run() -> Result<()> {
+    let matches = MyArgs::command().get_matches();
+    let mut args = MyArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let mut filter = EnvFilter::from_default_env();
+    if args.debug_http {
+        filter = filter.add_directive("query_rds_data::http=debug".parse()?);
+    }
+    let otlp_exporter = args
+        .otlp_endpoint
+        .as_deref()
+        .map(otlp::Exporter::new)
+        .transpose()?;
     tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::from_default_env())
+        .with(
+            fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(filter),
+        )
+        .with(otlp_exporter.as_ref().map(otlp::Exporter::layer))
         .init();
-    let args = MyArgs::parse();
-    let output_format = args.format;
-    let config = aws_sdk_config(&args).await;
-    let MyArns {
-        aws_secret_store_arn: secret_arn,
-        db_cluster_or_instance_arn: resource_arn,
-    } = get_arns(&config, &args.cluster_id, &args.user_id).await?;
-    let rds_data_client = aws_sdk_rdsdata::Client::new(&config);
-    let result_set_options = ResultSetOptions::builder()
-        .decimal_return_type(DecimalReturnType::String)
-        .build();
-    let execute_statement_output = rds_data_client
-        .execute_statement()
-        .set_database(args.database)
-        .include_result_metadata(true)
-        .resource_arn(resource_arn)
-        .result_set_options(result_set_options)
-        .secret_arn(secret_arn)
-        .sql(args.query)
-        .send()
-        .await?;
-    info!("{:?}", execute_statement_output);
-    match output_format {
-        Format::Csv => csv_output(&execute_statement_output),
-        Format::Json => cooked_output(&execute_statement_output),
+    if let Some(Command::Completions { shell }) = &args.command {
+        let mut command = MyArgs::command();
+        let name = command.get_name().to_owned();
+        clap_complete::generate(*shell, &mut command, name, &mut stdout());
+        return Ok(());
+    }
+    if let Some(Command::Mangen { out_dir }) = &args.command {
+        return match out_dir {
+            Some(out_dir) => std::fs::create_dir_all(out_dir)
+                .and_then(|()| clap_mangen::generate_to(MyArgs::command(), out_dir))
+                .map_err(|e| anyhow!("Failed to write man pages to {}: {}", out_dir.display(), e)),
+            None => Ok(clap_mangen::Man::new(MyArgs::command()).render(&mut stdout())?),
+        };
+    }
+    let my_config = Config::load(args.config.as_deref())?;
+    args.init_sql
+        .splice(0..0, my_config.init_sql.iter().cloned());
+    if let Some(name) = &args.target {
+        let alias = my_config.target(name)?.clone();
+        apply_target_alias(&mut args, &matches, &alias)?;
+    }
+    if args.read_only
+        && matches!(
+            args.command,
+            Some(
+                Command::Import(_)
+                    | Command::BatchExecute(_)
+                    | Command::Copy(_)
+                    | Command::Migrate(_)
+                    | Command::Cancel(_)
+                    | Command::EnableDataApi(_)
+            )
+        )
+    {
+        return Err(anyhow!("--read-only can only be used to run queries"));
+    }
+    if let Some(Command::History(history_args)) = &args.command {
+        return history::history(history_args, &audit_history(&args, &my_config)?, stdout());
+    }
+    if let Some(Command::Rerun { id }) = args.command {
+        let entries = audit_history(&args, &my_config)?;
+        let entry = history::entry(&entries, id.unwrap_or(entries.len()))?.clone();
+        rerun_args(&mut args, entry)?;
+    }
+    let config = aws_sdk_config(&args, args.region.first()).await;
+    if (args.all_clusters
+        || fanout::multi_region(&args)
+        || fanout::multi_database(&args)
+        || args.foreach.is_some())
+        && matches!(
+            args.command,
+            Some(
+                Command::Import(_)
+                    | Command::BatchExecute(_)
+                    | Command::Diff(_)
+                    | Command::Explain(_)
+                    | Command::Cancel(_)
+                    | Command::Snapshot(_)
+                    | Command::Script(_)
+                    | Command::Copy(_)
+                    | Command::Migrate(_)
+                    | Command::Schema(_)
+                    | Command::EnableDataApi(_)
+            )
+        )
+    {
+        return Err(anyhow!(
+            "--all-clusters, --databases, --foreach and multiple regions can only be used to run a query"
+        ));
+    }
+    if args.schedule.is_some()
+        && !matches!(
+            args.command,
+            None | Some(Command::Run { .. }) | Some(Command::Export { .. })
+        )
+    {
+        return Err(anyhow!("--schedule can only be used to run a query"));
+    }
+    if args.emit_metrics.is_some()
+        && !matches!(
+            args.command,
+            None | Some(Command::Run { .. })
+                | Some(Command::Export { .. })
+                | Some(Command::EnableDataApi(_))
+        )
+    {
+        return Err(anyhow!("--emit-metrics can only be used to run a query"));
+    }
+    if args.driver != Driver::DataApi
+        && !matches!(
+            args.command,
+            None | Some(Command::Run { .. })
+                | Some(Command::Export { .. })
+                | Some(Command::Script(_))
+        )
+    {
+        return Err(anyhow!("--driver can only be used to run a query"));
+    }
+    if matches!(args.command, Some(Command::Export { .. })) {
+        // Each of these writes the results somewhere other than S3.
+        let local_outputs = [
+            ("--output-pattern", args.output_pattern.is_some()),
+            ("--exec", args.exec.is_some()),
+            ("--checksum", args.checksum.is_some()),
+            ("--expect-file", args.expect_file.is_some()),
+        ];
+        if let Some((flag, _given)) = local_outputs.iter().find(|(_flag, given)| *given) {
+            return Err(anyhow!("{} can not be used with export", flag));
+        }
+    }
+    if !args.partition_by.is_empty() && args.format != Format::Parquet {
+        return Err(anyhow!(
+            "--partition-by can only be used with --format parquet"
+        ));
+    }
+    if args.include_metadata && args.format != Format::Json {
+        return Err(anyhow!(
+            "--include-metadata can only be used with --format json"
+        ));
+    }
+    if !args.partition_by.is_empty()
+        && args.output_dir.is_none()
+        && !matches!(args.command, Some(Command::Export { .. }))
+    {
+        return Err(anyhow!("--partition-by needs --output-dir, or export"));
     }
+    if args.username.is_some() && fanout::multi_region(&args) {
+        return Err(anyhow!("--username can only be used in a single region"));
+    }
+    if args.username.is_some()
+        && matches!(
+            &args.command,
+            Some(Command::Diff(_))
+                | Some(Command::Schema(SchemaArgs {
+                    action: SchemaAction::Diff { .. }
+                }))
+        )
+    {
+        return Err(anyhow!(
+            "--username can not be used to compare two clusters"
+        ));
+    }
+    let guard = Guard::new(
+        my_config.policy.clone(),
+        args.profile.clone(),
+        args.concurrency,
+        !args.yes,
+        open_audit_log(&args, &my_config)?,
+    );
+    let ephemeral_secret = ephemeral_secret(&mut args, &config).await?;
+    // In a block of its own, so that the secret is deleted however the
+    // command finishes, including by failing.
+    let result = async {
+        match &args.command {
+            Some(Command::Import(import_args)) => {
+                let target = target(&args, &config).await?;
+                if args.dry_run {
+                    dry_run_output(&import::dry_run_request(&target, import_args)?);
+                    return Ok(());
+                }
+                if import_args.validate_only {
+                    let rows = import::validate(&guard, &config, &target, import_args).await?;
+                    println!("rows_validated: {}", rows);
+                    return Ok(());
+                }
+                let summary = import::import(&guard, &config, &target, import_args).await?;
+                println!("rows_imported: {}", summary.imported);
+                summary.result()
+            }
+            Some(Command::BatchExecute(batch_args)) => {
+                let (sql, params) = untargeted_statement(&args, &my_config)?;
+                let request = request(&args, target(&args, &config).await?, &sql, &params)?;
+                if args.dry_run {
+                    dry_run_output(&request);
+                    return Ok(());
+                }
+                let summary = batch::batch_execute(&guard, &config, &request, batch_args).await?;
+                println!("parameter_sets_executed: {}", summary.imported);
+                summary.result()
+            }
+            Some(Command::Explain(explain_args)) => {
+                let (sql, params) = untargeted_statement(&args, &my_config)?;
+                let request = request(&args, target(&args, &config).await?, &sql, &params)?;
+                if args.dry_run {
+                    dry_run_output(&request);
+                    return Ok(());
+                }
+                let style = Style::for_stdout(args.color).max_col_width(args.max_col_width);
+                explain::explain(
+                    &guard,
+                    &config,
+                    request,
+                    explain_args,
+                    args.isolation,
+                    style,
+                    stdout(),
+                )
+                .await
+            }
+            Some(Command::Cancel(cancel_args)) => {
+                let target = target(&args, &config).await?;
+                if args.dry_run {
+                    dry_run_output(&cancel::lookup_request(&target, cancel_args));
+                    return Ok(());
+                }
+                for pid in cancel::cancel(&guard, &config, &target, cancel_args).await? {
+                    println!("cancelled: {}", pid);
+                }
+                Ok(())
+            }
+            Some(Command::Diff(diff_args)) => {
+                let (sql, params) = untargeted_statement(&args, &my_config)?;
+                let cluster_a = Some(diff_args.target_a.clone());
+                let cluster_b = Some(diff_args.target_b.clone());
+                let (target_a, target_b) = join!(
+                    cluster_target(&args, &config, &cluster_a),
+                    cluster_target(&args, &config, &cluster_b),
+                );
+                let request_a = request(&args, target_a?, &sql, &params)?;
+                let request_b = request(&args, target_b?, &sql, &params)?;
+                let (result_a, result_b) = join!(
+                    guard.execute(&config, request_a),
+                    guard.execute(&config, request_b)
+                );
+                diff::diff(&result_a?, &result_b?, &diff_args.key, stdout())
+            }
+            Some(Command::Copy(copy_args)) => {
+                let source = target(&args, &config).await?;
+                if args.dry_run {
+                    dry_run_output(&copy::dry_run_request(&source, copy_args));
+                    return Ok(());
+                }
+                let destination =
+                    target(&copy::destination_args(&args, copy_args), &config).await?;
+                let summary = copy::copy(&guard, &config, &source, &destination, copy_args).await?;
+                println!("rows_copied: {}", summary.imported);
+                summary.result()
+            }
+            Some(Command::Script(script_args)) => {
+                let (sql, params) = untargeted_statement(&args, &my_config)?;
+                let target = target(&args, &config).await?;
+                let requests = target
+                    .dialect()
+                    .split_statements(&sql)
+                    .iter()
+                    .map(|sql| request(&args, target.clone(), sql, &params))
+                    .collect::<Result<Vec<_>>>()?;
+                let groups = script::groups(requests, script_args.parallel);
+                if args.dry_run {
+                    let mut first = true;
+                    for (index, group) in groups.iter().enumerate() {
+                        for request in group {
+                            if !first {
+                                println!();
+                            }
+                            first = false;
+                            dry_run_output(request);
+                            println!("group: {}", index + 1);
+                        }
+                    }
+                    return Ok(());
+                }
+                // Refuse, or ask about, any statement before the first runs.
+                let init = init_requests(&args, &target)?;
+                let checks: Vec<(&SdkConfig, &Request)> = init
+                    .iter()
+                    .chain(groups.iter().flatten())
+                    .map(|request| (&config, request))
+                    .collect();
+                guard.check(&checks, None).await?;
+                let style = Style::for_stdout(args.color).max_col_width(args.max_col_width);
+                let mut first = true;
+                for group in groups {
+                    let results = fanout::concurrently(
+                        args.concurrency,
+                        group
+                            .into_iter()
+                            .map(|request| driver_execute(&args, &guard, &config, request)),
+                    )
+                    .await;
+                    for result in results {
+                        let mut output = result?;
+                        transform::apply(&args, &mut output)?;
+                        if !first {
+                            println!();
+                        }
+                        first = false;
+                        write_output(&args, style, &output, stdout())?;
+                    }
+                }
+                Ok(())
+            }
+            Some(Command::Snapshot(snapshot_args)) => {
+                let (sql, params) = untargeted_statement(&args, &my_config)?;
+                let target = target(&args, &config).await?;
+                let request = request(&args, target, &sql, &params)?;
+                if args.dry_run {
+                    dry_run_output(&request);
+                    return Ok(());
+                }
+                let mut output = guard.execute(&config, request).await?;
+                transform::apply(&args, &mut output)?;
+                snapshot::snapshot(snapshot_args, output)
+            }
+            Some(Command::Migrate(migrate_args)) => {
+                let target = target(&args, &config).await?;
+                migrate::migrate(&guard, &config, &target, migrate_args, args.dry_run).await
+            }
+            Some(Command::Schema(schema_args)) => match &schema_args.action {
+                SchemaAction::Dump { json } => {
+                    let target = target(&args, &config).await?;
+                    schema::dump(&guard, &config, &target, *json, stdout()).await
+                }
+                SchemaAction::Diff { target_a, target_b } => {
+                    let cluster_a = Some(target_a.clone());
+                    let cluster_b = Some(target_b.clone());
+                    let (target_a, target_b) = join!(
+                        cluster_target(&args, &config, &cluster_a),
+                        cluster_target(&args, &config, &cluster_b),
+                    );
+                    schema::diff(&guard, &config, &target_a?, &target_b?, stdout()).await
+                }
+            },
+            Some(Command::EnableDataApi(enable_args)) => {
+                let db_clusters = AwsClient::new(&config).describe_db_clusters().await?;
+                let db_cluster = my_cluster(&cluster_id(&args, &config).await?, &db_clusters)?;
+                enable::enable_data_api(&config, &db_cluster, enable_args, args.yes, args.dry_run)
+                    .await?;
+                match enable_args.query {
+                    Some(_) => observed_query(&args, &my_config, &guard, &config, None).await,
+                    None => Ok(()),
+                }
+            }
+            _ => match &args.schedule {
+                Some(schedule) if args.dry_run => {
+                    observed_query(&args, &my_config, &guard, &config, None).await?;
+                    let next_run = schedule.next_after(OffsetDateTime::now_utc())?;
+                    println!("next_run: {}", next_run.format(&Rfc3339)?);
+                    Ok(())
+                }
+                Some(schedule) => {
+                    let metrics = match args.metrics_address {
+                        Some(address) => {
+                            let metrics = Arc::new(Metrics::default());
+                            metrics::serve(address, metrics.clone()).await?;
+                            Some(metrics)
+                        }
+                        None => None,
+                    };
+                    schedule::run(schedule, || {
+                        observed_query(&args, &my_config, &guard, &config, metrics.as_deref())
+                    })
+                    .await
+                }
+                None => observed_query(&args, &my_config, &guard, &config, None).await,
+            },
+        }
+    }
+    .await;
+    if let Some(secret) = ephemeral_secret {
+        secret.delete().await;
+    }
+    result
 }
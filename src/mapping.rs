@@ -0,0 +1,180 @@
+use crate::{
+    format_value,
+    import::{json_field, Rows},
+};
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::types::Field;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+use time::{
+    format_description::{self, OwnedFormatItem},
+    Date,
+};
+
+/// How to fill each column of the table from the input, read from a
+/// `--mapping` file. Only the columns listed are loaded, for example:
+///
+/// ```toml
+/// [columns.email]
+/// from = "E-Mail Address"
+/// transform = ["trim", "lowercase"]
+///
+/// [columns.signed_up]
+/// from = "Signup Date"
+/// date_format = "[day]/[month]/[year]"
+///
+/// [columns.source]
+/// value = "legacy-crm"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mapping {
+    columns: BTreeMap<String, ColumnMapping>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnMapping {
+    /// The input key to read, when not named for the column.
+    from: Option<String>,
+
+    /// The same value for every row, instead of one read from the input.
+    value: Option<toml::Value>,
+
+    /// Changes made to the text read, in order.
+    #[serde(default)]
+    transform: Vec<Transform>,
+
+    /// Read the text as a date in this format, loading it as
+    /// `YYYY-MM-DD`. The format is as described at
+    /// <https://time-rs.github.io/book/api/format-description.html>.
+    date_format: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Transform {
+    Trim,
+    Lowercase,
+    Uppercase,
+}
+
+/// Where a column's values come from, resolved against the input keys.
+enum Source {
+    Key {
+        index: usize,
+        transform: Vec<Transform>,
+        date_format: Option<(String, OwnedFormatItem)>,
+    },
+    Value(Field),
+}
+
+impl Mapping {
+    pub fn load(path: &Path) -> Result<Mapping> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    fn sources(&self, keys: &[String]) -> Result<Vec<Source>> {
+        self.columns
+            .iter()
+            .map(|(column, mapping)| {
+                if let Some(value) = &mapping.value {
+                    if mapping.from.is_some()
+                        || !mapping.transform.is_empty()
+                        || mapping.date_format.is_some()
+                    {
+                        return Err(anyhow!(
+                            "Mapping for column \"{}\" has a value, so reads nothing to change",
+                            column
+                        ));
+                    }
+                    return Ok(Source::Value(json_field(serde_json::to_value(value)?)));
+                }
+                let key = mapping.from.as_ref().unwrap_or(column);
+                let index = keys.iter().position(|k| k == key).ok_or_else(|| {
+                    anyhow!(
+                        "Mapping for column \"{}\" reads \"{}\", available keys are {:?}",
+                        column,
+                        key,
+                        keys
+                    )
+                })?;
+                let date_format = match &mapping.date_format {
+                    Some(format) => {
+                        let items = format_description::parse_owned::<2>(format).map_err(|e| {
+                            anyhow!(
+                                "Mapping for column \"{}\" has date format \"{}\": {}",
+                                column,
+                                format,
+                                e
+                            )
+                        })?;
+                        Some((format.clone(), items))
+                    }
+                    None => None,
+                };
+                Ok(Source::Key {
+                    index,
+                    transform: mapping.transform.clone(),
+                    date_format,
+                })
+            })
+            .collect()
+    }
+
+    /// The mapped columns, and the rows with a value for each.
+    pub fn apply(&self, (keys, records): Rows) -> Result<Rows> {
+        let sources = self.sources(&keys)?;
+        let columns: Vec<String> = self.columns.keys().cloned().collect();
+        let row_columns = columns.clone();
+        let mapped = records.enumerate().map(move |(index, record)| {
+            let record = record?;
+            sources
+                .iter()
+                .zip(&row_columns)
+                .map(|(source, column)| {
+                    value(source, &record)
+                        .map_err(|e| anyhow!("Row {}, column \"{}\": {}", index + 1, column, e))
+                })
+                .collect()
+        });
+        Ok((columns, Box::new(mapped)))
+    }
+}
+
+fn value(source: &Source, record: &[Field]) -> Result<Field> {
+    let (index, transform, date_format) = match source {
+        Source::Value(value) => return Ok(value.clone()),
+        Source::Key {
+            index,
+            transform,
+            date_format,
+        } => (*index, transform, date_format),
+    };
+    let mut text = match &record[index] {
+        Field::IsNull(_) => return Ok(Field::IsNull(true)),
+        Field::StringValue(string) => string.clone(),
+        value if transform.is_empty() && date_format.is_none() => return Ok(value.clone()),
+        value => format_value(value),
+    };
+    for change in transform {
+        text = match change {
+            Transform::Trim => text.trim().to_owned(),
+            Transform::Lowercase => text.to_lowercase(),
+            Transform::Uppercase => text.to_uppercase(),
+        };
+    }
+    if let Some((format, items)) = date_format {
+        let date = Date::parse(&text, items)
+            .map_err(|_| anyhow!("\"{}\" is not a date in format \"{}\"", text, format))?;
+        text = format!(
+            "{:04}-{:02}-{:02}",
+            date.year(),
+            u8::from(date.month()),
+            date.day()
+        );
+    }
+    Ok(Field::StringValue(text))
+}
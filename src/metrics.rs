@@ -0,0 +1,388 @@
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use query_rds_data::{format_header, format_value};
+use std::{
+    fmt::Write,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{info, warn};
+
+/// Upper bounds, in seconds, of the run duration histogram's buckets.
+const DURATION_BUCKETS: [f64; 12] = [
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// Counts of the scheduled runs, served in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    successes: u64,
+    failures: u64,
+    /// How many runs fell in each of `DURATION_BUCKETS`, not cumulative.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    duration_sum: f64,
+    rows: u64,
+    last_success: Option<f64>,
+    /// The numeric values of the last results, with their labels.
+    values: Vec<(Vec<(String, String)>, f64)>,
+}
+
+/// Prometheus label names are letters, digits and underscores.
+fn label_name(name: &str) -> String {
+    let mut label: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if label.is_empty() || label.starts_with(|c: char| c.is_ascii_digit()) {
+        label.insert(0, '_');
+    }
+    label
+}
+
+/// The label names of the columns, made distinct from each other and from
+/// `column`, which every sample has, by prefixing `_` to any that clash.
+/// Prometheus refuses a scrape with a label given twice.
+fn label_names<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut taken = vec!["column".to_owned()];
+    names
+        .map(|name| {
+            let mut label = label_name(name);
+            while taken.contains(&label) {
+                label.insert(0, '_');
+            }
+            taken.push(label.clone());
+            label
+        })
+        .collect()
+}
+
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(labels: &[(String, String)]) -> String {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, label_value(value)))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+/// A field's value, when it is a number. Decimals arrive as strings, so
+/// those are read by the column's type.
+fn numeric(field: &Field, type_name: &str) -> Option<f64> {
+    match field {
+        Field::LongValue(value) => Some(*value as f64),
+        Field::DoubleValue(value) => Some(*value),
+        Field::BooleanValue(value) => Some(f64::from(u8::from(*value))),
+        Field::StringValue(value)
+            if ["decimal", "numeric"].contains(&type_name.to_lowercase().as_str()) =>
+        {
+            value.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+/// How many rows a statement returned, or updated.
+pub fn row_count(output: &ExecuteStatementOutput) -> u64 {
+    match &output.records {
+        Some(records) => records.len() as u64,
+        None => output.number_of_records_updated.max(0) as u64,
+    }
+}
+
+impl Metrics {
+    /// Keep each numeric value of the results as a sample, labelled with
+    /// its column and the row's other values, as a SQL exporter would. So
+    /// `SELECT status, count(*) AS jobs FROM jobs GROUP BY status` gives
+    /// `query_rds_data_value{column="jobs",status="failed"} 3`. A column
+    /// whose label would clash, such as one named `column`, gets a `_`
+    /// prefix.
+    pub fn set_values(&self, output: &ExecuteStatementOutput) {
+        let names = label_names(format_header(output));
+        let type_names: Vec<&str> = output
+            .column_metadata
+            .iter()
+            .flatten()
+            .map(|column| column.type_name.as_deref().unwrap_or_default())
+            .collect();
+        let records = output.records.as_deref().unwrap_or_default();
+        // A column holding any number is a value, so NULLs in it aren't
+        // taken for labels.
+        let numeric_columns: Vec<bool> = (0..names.len())
+            .map(|index| {
+                records.iter().any(|record| {
+                    record
+                        .get(index)
+                        .and_then(|field| numeric(field, type_names[index]))
+                        .is_some()
+                })
+            })
+            .collect();
+        let mut values = Vec::new();
+        for record in records {
+            let row_labels: Vec<(String, String)> = names
+                .iter()
+                .zip(record)
+                .zip(&numeric_columns)
+                .filter(|(_label, numeric)| !**numeric)
+                .map(|((name, field), _numeric)| match field {
+                    Field::IsNull(_) => (name.clone(), String::new()),
+                    field => (name.clone(), format_value(field)),
+                })
+                .collect();
+            for ((name, field), type_name) in names.iter().zip(record).zip(&type_names) {
+                if let Some(number) = numeric(field, type_name) {
+                    let mut sample_labels = vec![("column".to_owned(), name.clone())];
+                    sample_labels.extend(row_labels.iter().cloned());
+                    values.push((sample_labels, number));
+                }
+            }
+        }
+        self.state.lock().unwrap().values = values;
+    }
+
+    /// Count a finished run, how long it took, and the rows it returned,
+    /// without which it failed.
+    pub fn record_run(&self, duration: Duration, rows: Option<u64>) {
+        let seconds = duration.as_secs_f64();
+        let mut state = self.state.lock().unwrap();
+        if let Some(rows) = rows {
+            state.successes += 1;
+            state.rows += rows;
+            state.last_success = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|since| since.as_secs_f64());
+        } else {
+            state.failures += 1;
+        }
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|le| seconds <= *le) {
+            state.buckets[bucket] += 1;
+        }
+        state.duration_sum += seconds;
+    }
+
+    /// The metrics, in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut text = String::new();
+        let runs = state.successes + state.failures;
+        let _ = writeln!(
+            text,
+            "# HELP query_rds_data_runs_total Scheduled runs, by outcome.\n\
+             # TYPE query_rds_data_runs_total counter\n\
+             query_rds_data_runs_total{{outcome=\"success\"}} {}\n\
+             query_rds_data_runs_total{{outcome=\"failure\"}} {}",
+            state.successes, state.failures
+        );
+        let _ = writeln!(
+            text,
+            "# HELP query_rds_data_run_duration_seconds How long scheduled runs took.\n\
+             # TYPE query_rds_data_run_duration_seconds histogram"
+        );
+        let mut cumulative = 0;
+        for (le, count) in DURATION_BUCKETS.iter().zip(state.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                text,
+                "query_rds_data_run_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, cumulative
+            );
+        }
+        let _ = writeln!(
+            text,
+            "query_rds_data_run_duration_seconds_bucket{{le=\"+Inf\"}} {}\n\
+             query_rds_data_run_duration_seconds_sum {}\n\
+             query_rds_data_run_duration_seconds_count {}",
+            runs, state.duration_sum, runs
+        );
+        let _ = writeln!(
+            text,
+            "# HELP query_rds_data_rows_total Rows returned, or updated, by all runs.\n\
+             # TYPE query_rds_data_rows_total counter\n\
+             query_rds_data_rows_total {}",
+            state.rows
+        );
+        if let Some(last_success) = state.last_success {
+            let _ = writeln!(
+                text,
+                "# HELP query_rds_data_last_success_timestamp_seconds When the last successful run finished.\n\
+                 # TYPE query_rds_data_last_success_timestamp_seconds gauge\n\
+                 query_rds_data_last_success_timestamp_seconds {}",
+                last_success
+            );
+        }
+        if !state.values.is_empty() {
+            let _ = writeln!(
+                text,
+                "# HELP query_rds_data_value Numeric values of the last successful run's results.\n\
+                 # TYPE query_rds_data_value gauge"
+            );
+            for (sample_labels, value) in &state.values {
+                let _ = writeln!(
+                    text,
+                    "query_rds_data_value{} {}",
+                    labels(sample_labels),
+                    value
+                );
+            }
+        }
+        text
+    }
+}
+
+/// Answer one request: the metrics for `GET /metrics`, and 404 otherwise.
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+    let read = stream.read(&mut buffer).await?;
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let mut request_line = request.split_whitespace();
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", "Not found, try /metrics\n".to_owned()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Serve the metrics at `http://<address>/metrics`, in the background.
+pub async fn serve(address: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| anyhow!("Failed to listen on {}: {}", address, e))?;
+    info!("Serving metrics at http://{}/metrics", address);
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _peer)) => {
+                    let metrics = metrics.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = respond(stream, &metrics).await {
+                            warn!("Failed to serve metrics: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept a metrics connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_rdsdata::types::ColumnMetadata;
+
+    fn output(columns: &[(&str, &str)], records: Vec<Vec<Field>>) -> ExecuteStatementOutput {
+        ExecuteStatementOutput::builder()
+            .set_column_metadata(Some(
+                columns
+                    .iter()
+                    .map(|(name, type_name)| {
+                        ColumnMetadata::builder()
+                            .name(*name)
+                            .type_name(*type_name)
+                            .build()
+                    })
+                    .collect(),
+            ))
+            .set_records(Some(records))
+            .build()
+    }
+
+    #[test]
+    fn labels_samples_by_column_and_row() {
+        let metrics = Metrics::default();
+        metrics.set_values(&output(
+            &[("status", "text"), ("jobs", "int8"), ("cost", "numeric")],
+            vec![vec![
+                Field::StringValue("failed".to_owned()),
+                Field::LongValue(3),
+                Field::StringValue("1.5".to_owned()),
+            ]],
+        ));
+        let text = metrics.render();
+        assert!(text.contains("query_rds_data_value{column=\"jobs\",status=\"failed\"} 3\n"));
+        assert!(text.contains("query_rds_data_value{column=\"cost\",status=\"failed\"} 1.5\n"));
+    }
+
+    #[test]
+    fn renames_clashing_labels() {
+        let metrics = Metrics::default();
+        metrics.set_values(&output(
+            &[
+                ("column", "text"),
+                ("a b", "text"),
+                ("a-b", "text"),
+                ("n", "int4"),
+            ],
+            vec![vec![
+                Field::StringValue("x".to_owned()),
+                Field::StringValue("y".to_owned()),
+                Field::StringValue("z".to_owned()),
+                Field::LongValue(1),
+            ]],
+        ));
+        assert!(metrics
+            .render()
+            .contains("query_rds_data_value{column=\"n\",_column=\"x\",a_b=\"y\",_a_b=\"z\"} 1\n"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(label_value("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+        assert_eq!(label_name("1st place"), "_1st_place");
+    }
+
+    #[test]
+    fn renders_a_cumulative_histogram() {
+        let metrics = Metrics::default();
+        metrics.record_run(Duration::from_millis(20), Some(2));
+        metrics.record_run(Duration::from_millis(700), Some(3));
+        metrics.record_run(Duration::from_secs(400), None);
+        let text = metrics.render();
+        let bucket = |le: &str| {
+            format!(
+                "query_rds_data_run_duration_seconds_bucket{{le=\"{}\"}} ",
+                le
+            )
+        };
+        for (le, count) in [("0.05", 1), ("0.5", 1), ("1", 2), ("300", 2), ("+Inf", 3)] {
+            assert!(
+                text.contains(&format!("{}{}\n", bucket(le), count)),
+                "{} in {}",
+                le,
+                text
+            );
+        }
+        assert!(text.contains("query_rds_data_run_duration_seconds_count 3\n"));
+        assert!(text.contains("query_rds_data_runs_total{outcome=\"failure\"} 1\n"));
+        assert!(text.contains("query_rds_data_rows_total 5\n"));
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    format_value,
+    guard::Guard,
+    params::{Param, ParamType},
+    transaction::Transaction,
+    Request, Target,
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use clap::{Args, Subcommand};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::stdout,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+/// Apply versioned SQL migrations.
+///
+/// Migrations are files named like `001_create_users.sql` (or
+/// `001_create_users.up.sql`), with an optional `001_create_users.down.sql`
+/// to revert them. Each migration runs in its own transaction, and applied
+/// versions are recorded in a table, created when missing. MySQL commits
+/// implicitly around most DDL, so there a failed migration may be left
+/// partly applied. With --dry-run, the table is read, but nothing is
+/// changed.
+#[derive(Args, Clone, Debug)]
+pub struct MigrateArgs {
+    /// Directory holding the migration files.
+    #[clap(default_value = "migrations", long)]
+    dir: PathBuf,
+
+    /// Table recording applied migrations, optionally qualified by schema.
+    #[clap(default_value = "schema_migrations", long)]
+    table: String,
+
+    #[clap(subcommand)]
+    action: MigrateAction,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum MigrateAction {
+    /// List every migration, with when it was applied.
+    Status,
+
+    /// Apply pending migrations, in version order.
+    Up {
+        /// Stop after applying this version.
+        #[clap(long)]
+        to: Option<u64>,
+    },
+
+    /// Revert the most recently applied migrations.
+    Down {
+        /// How many migrations to revert.
+        #[clap(default_value_t = 1, long)]
+        steps: usize,
+    },
+}
+
+struct Migration {
+    version: u64,
+    name: String,
+    /// The file name without extensions, such as `001_create_users`.
+    label: String,
+    up: Option<PathBuf>,
+    down: Option<PathBuf>,
+}
+
+struct Applied {
+    name: String,
+    applied_at: String,
+}
+
+/// Every migration in the directory, in version order.
+fn migrations(dir: &Path) -> Result<Vec<Migration>> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| anyhow!("Failed to read {}: {}", dir.display(), e))?;
+    let mut migrations: BTreeMap<u64, Migration> = BTreeMap::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(stem) = file_name.strip_suffix(".sql") else {
+            continue;
+        };
+        let (stem, is_down) = match stem.strip_suffix(".down") {
+            Some(stem) => (stem, true),
+            None => (stem.strip_suffix(".up").unwrap_or(stem), false),
+        };
+        let (version, name) = stem.split_once('_').unwrap_or((stem, ""));
+        let version: u64 = version.parse().map_err(|_| {
+            anyhow!(
+                "Migration \"{}\" does not start with a version number, like 001_create_users.sql",
+                file_name
+            )
+        })?;
+        let migration = migrations.entry(version).or_insert_with(|| Migration {
+            version,
+            name: name.to_owned(),
+            label: stem.to_owned(),
+            up: None,
+            down: None,
+        });
+        let slot = if is_down {
+            &mut migration.down
+        } else {
+            &mut migration.up
+        };
+        if migration.name != name || slot.is_some() {
+            return Err(anyhow!(
+                "Migration version {} is used more than once in {}",
+                version,
+                dir.display()
+            ));
+        }
+        *slot = Some(path);
+    }
+    for migration in migrations.values() {
+        if migration.up.is_none() {
+            return Err(anyhow!(
+                "Migration {} has a down file but no up file",
+                migration.label
+            ));
+        }
+    }
+    Ok(migrations.into_values().collect())
+}
+
+fn plain_request(target: &Target, sql: String) -> Request {
+    Request {
+        target: target.clone(),
+        params: Vec::new(),
+        sql,
+    }
+}
+
+fn version_param(version: u64) -> Param {
+    Param {
+        name: "version".to_owned(),
+        param_type: ParamType::Long,
+        value: version.to_string(),
+    }
+}
+
+/// Whether the error says the table does not exist, in PostgreSQL's words
+/// or MySQL's.
+fn is_missing_table(e: &anyhow::Error) -> bool {
+    let message = format!("{:#}", e);
+    (message.contains("relation") && message.contains("does not exist"))
+        || message.contains("doesn't exist")
+}
+
+/// Applied migrations by version. In a dry run the table is not created,
+/// but it is still read, so a dry run connects to the cluster. There a
+/// missing table counts as nothing applied; any other error is reported.
+async fn applied(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    table: &str,
+    dry_run: bool,
+) -> Result<BTreeMap<u64, Applied>> {
+    if !dry_run {
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS {} (version BIGINT PRIMARY KEY, name VARCHAR(255) NOT NULL, applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+            table
+        );
+        guard.execute(config, plain_request(target, create)).await?;
+    }
+    let select = format!(
+        "SELECT version, name, applied_at FROM {} ORDER BY version",
+        table
+    );
+    let output = match guard.execute(config, plain_request(target, select)).await {
+        Ok(output) => output,
+        Err(e) if dry_run && is_missing_table(&e) => {
+            info!("Treating migrations as unapplied: {}", e);
+            return Ok(BTreeMap::new());
+        }
+        Err(e) => return Err(e),
+    };
+    let mut applied = BTreeMap::new();
+    for record in output.records.unwrap_or_default() {
+        let values: Vec<String> = record.iter().map(format_value).collect();
+        let [version, name, applied_at] = &values[..] else {
+            return Err(anyhow!("Unexpected row in {}: {:?}", table, values));
+        };
+        let version = version
+            .parse()
+            .map_err(|_| anyhow!("Unexpected version in {}: {}", table, version))?;
+        applied.insert(
+            version,
+            Applied {
+                name: name.clone(),
+                applied_at: applied_at.clone(),
+            },
+        );
+    }
+    Ok(applied)
+}
+
+/// Run a migration file and its bookkeeping in one transaction.
+async fn run(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    path: &Path,
+    bookkeeping: Request,
+) -> Result<()> {
+    let script = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let requests: Vec<Request> = target
+        .dialect()
+        .split_statements(&script)
+        .into_iter()
+        .map(|sql| plain_request(target, sql))
+        .chain([bookkeeping])
+        .collect();
+    let checks: Vec<(&SdkConfig, &Request)> =
+        requests.iter().map(|request| (config, request)).collect();
+    guard.check(&checks, None).await?;
+    let transaction = Transaction::begin(config, target, None).await?;
+    let result = async {
+        for request in requests {
+            info!("{}", request.sql);
+            guard
+                .execute_in_transaction(config, request, Some(transaction.id()))
+                .await?;
+        }
+        Ok(())
+    }
+    .await;
+    match result {
+        Ok(()) => transaction.commit().await,
+        Err(e) => {
+            if let Err(rollback_error) = transaction.rollback().await {
+                warn!("{}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+fn status(migrations: &[Migration], applied: &BTreeMap<u64, Applied>) -> Result<()> {
+    let mut rows: BTreeMap<u64, (&str, &str)> = migrations
+        .iter()
+        .map(|migration| (migration.version, (migration.name.as_str(), "")))
+        .collect();
+    for (version, applied) in applied {
+        rows.insert(*version, (&applied.name, &applied.applied_at));
+    }
+    let mut wtr = csv::Writer::from_writer(stdout());
+    wtr.write_record(["version", "name", "applied_at"])?;
+    for (version, (name, applied_at)) in rows {
+        wtr.write_record([version.to_string().as_str(), name, applied_at])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// The migrations not yet applied, up to and including version `to`.
+fn pending<'a>(
+    migrations: &'a [Migration],
+    applied: &BTreeMap<u64, Applied>,
+    to: Option<u64>,
+) -> Vec<&'a Migration> {
+    migrations
+        .iter()
+        .filter(|migration| {
+            !applied.contains_key(&migration.version) && to.is_none_or(|to| migration.version <= to)
+        })
+        .collect()
+}
+
+async fn up(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    table: &str,
+    pending: Vec<&Migration>,
+    dry_run: bool,
+) -> Result<()> {
+    for migration in pending {
+        if dry_run {
+            println!("would_apply: {}", migration.label);
+            continue;
+        }
+        let bookkeeping = Request {
+            target: target.clone(),
+            params: vec![
+                version_param(migration.version),
+                Param {
+                    name: "name".to_owned(),
+                    param_type: ParamType::String,
+                    value: migration.name.clone(),
+                },
+            ],
+            sql: format!(
+                "INSERT INTO {} (version, name) VALUES (:version, :name)",
+                table
+            ),
+        };
+        let path = migration.up.as_deref().unwrap_or(Path::new(""));
+        run(guard, config, target, path, bookkeeping)
+            .await
+            .map_err(|e| anyhow!("Migration {} failed: {}", migration.label, e))?;
+        println!("applied: {}", migration.label);
+    }
+    Ok(())
+}
+
+/// The last `steps` migrations applied, latest first.
+fn latest<'a>(
+    migrations: &'a [Migration],
+    applied: &BTreeMap<u64, Applied>,
+    steps: usize,
+) -> Result<Vec<&'a Migration>> {
+    applied
+        .keys()
+        .rev()
+        .take(steps)
+        .map(|version| {
+            migrations
+                .iter()
+                .find(|migration| migration.version == *version)
+                .ok_or_else(|| anyhow!("Applied migration {} has no files", version))
+        })
+        .collect()
+}
+
+async fn down(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    table: &str,
+    latest: Vec<&Migration>,
+    dry_run: bool,
+) -> Result<()> {
+    for migration in latest {
+        let path = migration.down.as_deref().ok_or_else(|| {
+            anyhow!(
+                "Migration {} has no down file to revert it",
+                migration.label
+            )
+        })?;
+        if dry_run {
+            println!("would_revert: {}", migration.label);
+            continue;
+        }
+        let bookkeeping = Request {
+            target: target.clone(),
+            params: vec![version_param(migration.version)],
+            sql: format!("DELETE FROM {} WHERE version = :version", table),
+        };
+        run(guard, config, target, path, bookkeeping)
+            .await
+            .map_err(|e| anyhow!("Reverting migration {} failed: {}", migration.label, e))?;
+        println!("reverted: {}", migration.label);
+    }
+    Ok(())
+}
+
+pub async fn migrate(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    migrate_args: &MigrateArgs,
+    dry_run: bool,
+) -> Result<()> {
+    let migrations = migrations(&migrate_args.dir)?;
+    let table = target.dialect().quote_table(&migrate_args.table);
+    let applied = applied(guard, config, target, &table, dry_run).await?;
+    match &migrate_args.action {
+        MigrateAction::Status => status(&migrations, &applied),
+        MigrateAction::Up { to } => {
+            let pending = pending(&migrations, &applied, *to);
+            up(guard, config, target, &table, pending, dry_run).await
+        }
+        MigrateAction::Down { steps } => {
+            let latest = latest(&migrations, &applied, *steps)?;
+            down(guard, config, target, &table, latest, dry_run).await
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{anyhow, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing::{warn, Level, Subscriber};
+use tracing_subscriber::{filter::Targets, registry::LookupSpan, Layer};
+
+/// Sends our spans to an OTLP collector, over HTTP, until dropped.
+pub struct Exporter {
+    provider: SdkTracerProvider,
+}
+
+impl Exporter {
+    /// Export to the collector at this base URL, like
+    /// `http://localhost:4318`, as `OTEL_EXPORTER_OTLP_ENDPOINT` is read.
+    pub fn new(endpoint: &str) -> Result<Exporter> {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+            .build()
+            .map_err(|e| anyhow!("Failed to export traces to {}: {}", endpoint, e))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(env!("CARGO_PKG_NAME"))
+                    .build(),
+            )
+            .build();
+        Ok(Exporter { provider })
+    }
+
+    /// A layer turning our spans, such as `discover`, `execute_statement`
+    /// and `format`, into OpenTelemetry spans, whatever the log level.
+    pub fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.provider.tracer(env!("CARGO_PKG_NAME")))
+            .with_filter(Targets::new().with_target("query_rds_data", Level::INFO))
+    }
+}
+
+impl Drop for Exporter {
+    /// Send any spans not yet exported, before the process exits.
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("Failed to export traces: {}", e);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use time::{format_description, OffsetDateTime};
+
+/// The `{time}` of a run starting now.
+pub fn time_now() -> Result<String> {
+    let format =
+        format_description::parse_borrowed::<2>("[year][month][day]T[hour][minute][second]Z")?;
+    Ok(OffsetDateTime::now_utc().format(&format)?)
+}
+
+/// What a result set's output file is named after.
+pub struct Placeholders<'a> {
+    /// Counting from 1.
+    pub index: usize,
+    pub region: &'a str,
+    pub cluster: &'a str,
+    /// When the run started, in UTC, like `20240601T120500Z`.
+    pub time: &'a str,
+    /// With --partition-output, the partition's value, escaped for a path.
+    pub value: Option<&'a str>,
+}
+
+/// The cluster identifier at the end of a cluster ARN.
+pub fn cluster_id(resource_arn: &str) -> &str {
+    resource_arn.rsplit(':').next().unwrap_or(resource_arn)
+}
+
+/// Substitute `{index}`, `{region}`, `{cluster}`, `{time}` and `{value}`
+/// in an `--output-pattern`.
+pub fn render(pattern: &str, placeholders: &Placeholders) -> Result<PathBuf> {
+    let mut rendered = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after_open = &rest[start + 1..];
+        let end = after_open
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed \"{{\" in output pattern \"{}\"", pattern))?;
+        match &after_open[..end] {
+            "index" => rendered.push_str(&placeholders.index.to_string()),
+            "region" => rendered.push_str(placeholders.region),
+            "cluster" => rendered.push_str(placeholders.cluster),
+            "time" => rendered.push_str(placeholders.time),
+            "value" => rendered.push_str(placeholders.value.ok_or_else(|| {
+                anyhow!(
+                    "Output pattern \"{}\" uses {{value}}, which needs --partition-output",
+                    pattern
+                )
+            })?),
+            name => {
+                return Err(anyhow!(
+                    "Unknown output pattern placeholder \"{}\", expected one of {:?}",
+                    name,
+                    ["index", "region", "cluster", "time", "value"],
+                ))
+            }
+        }
+        rest = &after_open[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(PathBuf::from(rendered))
+}
+
+/// Create the file, and any missing directories leading to it.
+pub fn create(path: &Path) -> Result<fs::File> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::File::create(path).map_err(|e| anyhow!("Failed to create {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders() -> Placeholders<'static> {
+        Placeholders {
+            index: 2,
+            region: "us-east-1",
+            cluster: "orders",
+            time: "20240601T120500Z",
+            value: None,
+        }
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        assert_eq!(
+            render("out/{region}/{cluster}-{index}.csv", &placeholders()).unwrap(),
+            Path::new("out/us-east-1/orders-2.csv")
+        );
+        assert_eq!(
+            render("plain.csv", &placeholders()).unwrap(),
+            Path::new("plain.csv")
+        );
+    }
+
+    #[test]
+    fn refuses_bad_patterns() {
+        let error = |pattern| render(pattern, &placeholders()).unwrap_err().to_string();
+        assert!(error("{db}.csv")
+            .starts_with("Unknown output pattern placeholder \"db\", expected one of"));
+        assert_eq!(
+            error("{index.csv"),
+            "Unclosed \"{\" in output pattern \"{index.csv\""
+        );
+    }
+
+    #[test]
+    fn finds_the_cluster_id() {
+        assert_eq!(
+            cluster_id("arn:aws:rds:us-east-1:123456789012:cluster:orders"),
+            "orders"
+        );
+        assert_eq!(cluster_id("orders"), "orders");
+    }
+
+    #[test]
+    fn renders_the_time() {
+        assert_eq!(
+            render("runs/{time}.csv", &placeholders()).unwrap(),
+            Path::new("runs/20240601T120500Z.csv")
+        );
+        let now = time_now().unwrap();
+        assert_eq!(now.len(), "20240601T120500Z".len());
+        assert!(now.ends_with('Z') && now.chars().nth(8) == Some('T'));
+    }
+
+    #[test]
+    fn renders_the_partition_value() {
+        let partition = Placeholders {
+            value: Some("2024-06-01"),
+            ..placeholders()
+        };
+        assert_eq!(
+            render("by-day/{value}.csv", &partition).unwrap(),
+            Path::new("by-day/2024-06-01.csv")
+        );
+        assert_eq!(
+            render("{value}.csv", &placeholders())
+                .unwrap_err()
+                .to_string(),
+            "Output pattern \"{value}.csv\" uses {value}, which needs --partition-output"
+        );
+    }
+}
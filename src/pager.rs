@@ -0,0 +1,51 @@
+use anyhow::Result;
+use std::{
+    io::{stdout, Write},
+    process::{Command, Stdio},
+};
+use terminal_size::{terminal_size, Height};
+use tracing::warn;
+
+const DEFAULT_PAGER: &str = "less -S";
+
+/// Print the output, through `$PAGER` when it is taller than the terminal.
+/// A pager that fails to start is reported, and the output printed anyway.
+pub fn show(output: &[u8]) -> Result<()> {
+    let lines = output.iter().filter(|&&byte| byte == b'\n').count();
+    let fits = match terminal_size() {
+        Some((_width, Height(height))) => lines < height as usize,
+        None => true,
+    };
+    if fits {
+        stdout().write_all(output)?;
+        return Ok(());
+    }
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_owned());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        // An empty PAGER means no pager.
+        stdout().write_all(output)?;
+        return Ok(());
+    };
+    let mut command = Command::new(program);
+    command.args(words).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        // Let colors through, when less is the pager.
+        command.env("LESS", "R");
+    }
+    match command.spawn() {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The reader may quit the pager early, closing the pipe.
+                let _result = stdin.write_all(output);
+            }
+            child.wait()?;
+            Ok(())
+        }
+        Err(e) => {
+            warn!("Failed to run pager \"{}\": {}", pager, e);
+            stdout().write_all(output)?;
+            Ok(())
+        }
+    }
+}
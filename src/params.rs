@@ -0,0 +1,369 @@
+use anyhow::{anyhow, Error, Result};
+use aws_sdk_rdsdata::types::{Field, SqlParameter, TypeHint};
+use std::{fmt, str::FromStr};
+use time::{format_description, Date, PrimitiveDateTime, Time};
+
+/// How a parameter value is sent to the Data API.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParamType {
+    String,
+    Long,
+    Double,
+    Boolean,
+    /// A string, which the database casts to a UUID.
+    Uuid,
+    /// JSON text, checked locally, which the database casts to JSON.
+    Json,
+    /// `YYYY-MM-DD`.
+    Date,
+    /// `HH:MM:SS`, optionally with fractional seconds.
+    Time,
+    /// `YYYY-MM-DD HH:MM:SS`, optionally with fractional seconds.
+    Timestamp,
+}
+
+impl FromStr for ParamType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "string" => Ok(ParamType::String),
+            "long" => Ok(ParamType::Long),
+            "double" => Ok(ParamType::Double),
+            "boolean" => Ok(ParamType::Boolean),
+            "uuid" => Ok(ParamType::Uuid),
+            "json" => Ok(ParamType::Json),
+            "date" => Ok(ParamType::Date),
+            "time" => Ok(ParamType::Time),
+            "timestamp" => Ok(ParamType::Timestamp),
+            _ => Err(anyhow!(
+                "Unknown parameter type \"{}\", expected one of {:?}",
+                s,
+                [
+                    "string",
+                    "long",
+                    "double",
+                    "boolean",
+                    "uuid",
+                    "json",
+                    "date",
+                    "time",
+                    "timestamp"
+                ],
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParamType::String => "string",
+            ParamType::Long => "long",
+            ParamType::Double => "double",
+            ParamType::Boolean => "boolean",
+            ParamType::Uuid => "uuid",
+            ParamType::Json => "json",
+            ParamType::Date => "date",
+            ParamType::Time => "time",
+            ParamType::Timestamp => "timestamp",
+        })
+    }
+}
+
+/// Whether the text looks like `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`, in hex.
+fn is_uuid(text: &str) -> bool {
+    text.len() == 36
+        && text.char_indices().all(|(index, c)| match index {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+const DATE_FORMAT: &str = "[year]-[month]-[day]";
+const TIME_FORMAT: &str = "[hour]:[minute]:[second]";
+
+/// The text without any fractional seconds, of up to nine digits.
+fn whole_seconds(text: &str) -> Option<&str> {
+    match text.rsplit_once('.') {
+        Some((whole, fraction))
+            if (1..=9).contains(&fraction.len())
+                && fraction.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            Some(whole)
+        }
+        Some(_) => None,
+        None => Some(text),
+    }
+}
+
+/// Whether the text is a date, time or timestamp, in the format the Data
+/// API expects for the type.
+fn is_date_time(param_type: ParamType, text: &str) -> bool {
+    let (format, text) = match param_type {
+        ParamType::Date => (DATE_FORMAT.to_owned(), Some(text)),
+        ParamType::Time => (TIME_FORMAT.to_owned(), whole_seconds(text)),
+        _ => (
+            format!("{} {}", DATE_FORMAT, TIME_FORMAT),
+            whole_seconds(text),
+        ),
+    };
+    let (Ok(format), Some(text)) = (format_description::parse_borrowed::<2>(&format), text) else {
+        return false;
+    };
+    match param_type {
+        ParamType::Date => Date::parse(text, &format).is_ok(),
+        ParamType::Time => Time::parse(text, &format).is_ok(),
+        _ => PrimitiveDateTime::parse(text, &format).is_ok(),
+    }
+}
+
+/// A named SQL parameter, given as `name=value` or `name:type=value`.
+/// The SQL refers to it as `:name`. A JSON value of `@path` is read from
+/// that file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub param_type: ParamType,
+    pub value: String,
+}
+
+impl FromStr for Param {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Parameter \"{}\" is not of the form name=value", s))?;
+        let (name, param_type) = match key.split_once(':') {
+            Some((name, type_name)) => (name, type_name.parse()?),
+            None => (key, ParamType::String),
+        };
+        if name.is_empty() {
+            return Err(anyhow!("Parameter \"{}\" has an empty name", s));
+        }
+        let value = match value.strip_prefix('@') {
+            Some(path) if param_type == ParamType::Json => {
+                std::fs::read_to_string(path).map_err(|e| {
+                    anyhow!("Failed to read parameter \"{}\" from {}: {}", name, path, e)
+                })?
+            }
+            _ => value.to_owned(),
+        };
+        Ok(Param {
+            name: name.to_owned(),
+            param_type,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}={}", self.name, self.param_type, self.value)
+    }
+}
+
+impl Param {
+    fn field(&self) -> Result<Field> {
+        let bad_value = || {
+            anyhow!(
+                "Parameter \"{}\" value \"{}\" is not a valid {:?}",
+                self.name,
+                self.value,
+                self.param_type,
+            )
+        };
+        Ok(match self.param_type {
+            ParamType::String => Field::StringValue(self.value.clone()),
+            ParamType::Long => Field::LongValue(self.value.parse().map_err(|_| bad_value())?),
+            ParamType::Double => Field::DoubleValue(self.value.parse().map_err(|_| bad_value())?),
+            ParamType::Boolean => Field::BooleanValue(self.value.parse().map_err(|_| bad_value())?),
+            ParamType::Uuid if is_uuid(&self.value) => Field::StringValue(self.value.clone()),
+            ParamType::Uuid => return Err(bad_value()),
+            ParamType::Json => {
+                serde_json::from_str::<serde_json::Value>(&self.value).map_err(|_| bad_value())?;
+                Field::StringValue(self.value.clone())
+            }
+            ParamType::Date | ParamType::Time | ParamType::Timestamp => {
+                if !is_date_time(self.param_type, &self.value) {
+                    return Err(bad_value());
+                }
+                Field::StringValue(self.value.clone())
+            }
+        })
+    }
+
+    /// Tells the Data API how to cast a string value.
+    fn type_hint(&self) -> Option<TypeHint> {
+        match self.param_type {
+            ParamType::Uuid => Some(TypeHint::Uuid),
+            ParamType::Json => Some(TypeHint::Json),
+            ParamType::Date => Some(TypeHint::Date),
+            ParamType::Time => Some(TypeHint::Time),
+            ParamType::Timestamp => Some(TypeHint::Timestamp),
+            _ => None,
+        }
+    }
+
+    pub fn to_sql_parameter(&self) -> Result<SqlParameter> {
+        Ok(SqlParameter::builder()
+            .name(&self.name)
+            .value(self.field()?)
+            .set_type_hint(self.type_hint())
+            .build())
+    }
+}
+
+/// Combine parameters, later ones replacing earlier ones of the same name.
+pub fn merge_params(defaults: &[Param], overrides: &[Param]) -> Vec<Param> {
+    let mut merged: Vec<Param> = defaults
+        .iter()
+        .filter(|default| !overrides.iter().any(|p| p.name == default.name))
+        .cloned()
+        .collect();
+    merged.extend(overrides.iter().cloned());
+    merged
+}
+
+pub fn sql_parameters(params: &[Param]) -> Result<Option<Vec<SqlParameter>>> {
+    if params.is_empty() {
+        Ok(None)
+    } else {
+        params
+            .iter()
+            .map(Param::to_sql_parameter)
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_params() {
+        assert_eq!(
+            "id:long=42".parse::<Param>().unwrap(),
+            Param {
+                name: "id".to_owned(),
+                param_type: ParamType::Long,
+                value: "42".to_owned(),
+            }
+        );
+        let param: Param = "note=a=b".parse().unwrap();
+        assert_eq!(param.param_type, ParamType::String);
+        assert_eq!(param.value, "a=b");
+    }
+
+    #[test]
+    fn refuses_bad_params() {
+        let error = |s: &str| s.parse::<Param>().unwrap_err().to_string();
+        assert_eq!(
+            error("id"),
+            "Parameter \"id\" is not of the form name=value"
+        );
+        assert_eq!(error(":long=1"), "Parameter \":long=1\" has an empty name");
+        assert!(error("id:int=1").starts_with("Unknown parameter type \"int\", expected one of"));
+    }
+
+    #[test]
+    fn checks_values_against_their_type() {
+        let value = |s: &str| s.parse::<Param>().unwrap().to_sql_parameter();
+        assert_eq!(
+            value("ok:boolean=true").unwrap().value,
+            Some(Field::BooleanValue(true))
+        );
+        assert_eq!(
+            value("id:long=x").unwrap_err().to_string(),
+            "Parameter \"id\" value \"x\" is not a valid Long"
+        );
+        assert!(value("size:double=1.5x").is_err());
+    }
+
+    #[test]
+    fn merges_params_by_name() {
+        let params = |list: &[&str]| -> Vec<Param> {
+            list.iter().map(|param| param.parse().unwrap()).collect()
+        };
+        assert_eq!(
+            merge_params(&params(&["a=1", "b=2"]), &params(&["b=3", "c=4"])),
+            params(&["a=1", "b=3", "c=4"])
+        );
+    }
+
+    #[test]
+    fn checks_uuids() {
+        let value = |s: &str| s.parse::<Param>().unwrap().to_sql_parameter();
+        let param = value("id:uuid=123e4567-e89b-12d3-a456-426614174000").unwrap();
+        assert_eq!(param.type_hint, Some(TypeHint::Uuid));
+        for bad in [
+            "123e4567e89b12d3a456426614174000",
+            "123e4567-e89b-12d3-a456-42661417400g",
+            "123e4567-e89b-12d3-a456-4266141740000",
+        ] {
+            assert_eq!(
+                value(&format!("id:uuid={}", bad)).unwrap_err().to_string(),
+                format!("Parameter \"id\" value \"{}\" is not a valid Uuid", bad)
+            );
+        }
+    }
+
+    #[test]
+    fn checks_json() {
+        let value = |s: &str| s.parse::<Param>().unwrap().to_sql_parameter();
+        let param = value(r#"doc:json={"a": [1, 2]}"#).unwrap();
+        assert_eq!(param.type_hint, Some(TypeHint::Json));
+        assert_eq!(
+            param.value,
+            Some(Field::StringValue(r#"{"a": [1, 2]}"#.to_owned()))
+        );
+        assert_eq!(
+            value("doc:json={a}").unwrap_err().to_string(),
+            "Parameter \"doc\" value \"{a}\" is not a valid Json"
+        );
+        // Only JSON values are read from files.
+        assert_eq!(
+            "note=@notes.txt".parse::<Param>().unwrap().value,
+            "@notes.txt"
+        );
+        assert!("doc:json=@/nonexistent/doc.json"
+            .parse::<Param>()
+            .unwrap_err()
+            .to_string()
+            .starts_with("Failed to read parameter \"doc\" from /nonexistent/doc.json"));
+    }
+
+    #[test]
+    fn checks_dates_and_times() {
+        let value = |s: &str| s.parse::<Param>().unwrap().to_sql_parameter();
+        for good in [
+            "d:date=2024-02-29",
+            "t:time=23:59:59",
+            "t:time=23:59:59.123456",
+            "ts:timestamp=2024-06-01 12:00:00",
+            "ts:timestamp=2024-06-01 12:00:00.5",
+        ] {
+            assert!(value(good).is_ok(), "{}", good);
+        }
+        assert_eq!(
+            value("d:date=2023-02-29").unwrap_err().to_string(),
+            "Parameter \"d\" value \"2023-02-29\" is not a valid Date"
+        );
+        for bad in [
+            "d:date=2024-6-1",
+            "t:time=24:00:00",
+            "t:time=12:00:00.",
+            "t:time=12:00:00.1234567890",
+            "ts:timestamp=2024-06-01T12:00:00",
+            "ts:timestamp=2024-06-01",
+        ] {
+            assert!(value(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(
+            value("ts:timestamp=2024-06-01 12:00:00").unwrap().type_hint,
+            Some(TypeHint::Timestamp)
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{format_header, format_value};
+use anyhow::{anyhow, Result};
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray,
+};
+use arrow_schema::{DataType, Field as ArrowField, Schema};
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use parquet::arrow::ArrowWriter;
+use std::{io::Write, sync::Arc};
+
+/// Pick a Parquet column type from the returned values. Columns mixing
+/// value types, or holding only NULLs, become strings.
+fn column_type(values: &[Option<&Field>]) -> DataType {
+    let mut column_type = None;
+    for value in values {
+        let value_type = match value {
+            Some(Field::LongValue(_)) => DataType::Int64,
+            Some(Field::DoubleValue(_)) => DataType::Float64,
+            Some(Field::BooleanValue(_)) => DataType::Boolean,
+            Some(Field::BlobValue(_)) => DataType::Binary,
+            Some(Field::IsNull(_)) | None => continue,
+            Some(_) => DataType::Utf8,
+        };
+        match &column_type {
+            None => column_type = Some(value_type),
+            Some(known) if *known == value_type => {}
+            Some(_) => return DataType::Utf8,
+        }
+    }
+    column_type.unwrap_or(DataType::Utf8)
+}
+
+fn column_array(data_type: &DataType, values: &[Option<&Field>]) -> ArrayRef {
+    match data_type {
+        DataType::Int64 => Arc::new(Int64Array::from_iter(values.iter().map(
+            |value| match value {
+                Some(Field::LongValue(long)) => Some(*long),
+                _ => None,
+            },
+        ))),
+        DataType::Float64 => Arc::new(Float64Array::from_iter(values.iter().map(
+            |value| match value {
+                Some(Field::DoubleValue(double)) => Some(*double),
+                _ => None,
+            },
+        ))),
+        DataType::Boolean => Arc::new(BooleanArray::from_iter(values.iter().map(
+            |value| match value {
+                Some(Field::BooleanValue(boolean)) => Some(*boolean),
+                _ => None,
+            },
+        ))),
+        DataType::Binary => Arc::new(BinaryArray::from_iter(values.iter().map(
+            |value| match value {
+                Some(Field::BlobValue(blob)) => Some(blob.as_ref()),
+                _ => None,
+            },
+        ))),
+        _ => Arc::new(StringArray::from_iter(values.iter().map(
+            |value| match value {
+                Some(Field::IsNull(_)) | None => None,
+                Some(field) => Some(format_value(field)),
+            },
+        ))),
+    }
+}
+
+/// The Parquet schema of the records, with each column's type picked from
+/// all of its values.
+pub fn schema(result: &ExecuteStatementOutput) -> Result<Schema> {
+    let header: Vec<&str> = format_header(result).collect();
+    if header.is_empty() {
+        return Err(anyhow!("No columns to write as Parquet"));
+    }
+    let records = result.records.as_deref().unwrap_or(&[]);
+    let fields: Vec<ArrowField> = header
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let values: Vec<Option<&Field>> =
+                records.iter().map(|record| record.get(index)).collect();
+            ArrowField::new(*name, column_type(&values), true)
+        })
+        .collect();
+    Ok(Schema::new(fields))
+}
+
+/// Write the records as a single Parquet row group.
+pub fn parquet_output<W: Write + Send>(result: &ExecuteStatementOutput, writer: W) -> Result<()> {
+    parquet_output_with_schema(result, Arc::new(schema(result)?), writer)
+}
+
+/// Write the records as a single Parquet row group, with a schema picked
+/// from other records too, so that the files of a partitioned export agree.
+pub fn parquet_output_with_schema<W: Write + Send>(
+    result: &ExecuteStatementOutput,
+    schema: Arc<Schema>,
+    writer: W,
+) -> Result<()> {
+    let records = result.records.as_deref().unwrap_or(&[]);
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let values: Vec<Option<&Field>> =
+                records.iter().map(|record| record.get(index)).collect();
+            column_array(field.data_type(), &values)
+        })
+        .collect();
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    let mut parquet_writer = ArrowWriter::try_new(writer, schema, None)?;
+    parquet_writer.write(&batch)?;
+    parquet_writer.close()?;
+    Ok(())
+}
@@ -0,0 +1,253 @@
+use crate::{
+    audit::sha256_hex,
+    format_value, output_pattern,
+    parquet_output::{self, parquet_output_with_schema},
+    s3::{S3Options, S3Writer},
+    transform::{column_indexes, drop_columns},
+};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use serde_json::json;
+use std::{collections::BTreeMap, io::Write, path::PathBuf, sync::Arc};
+
+const PARQUET_CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+/// The name Hive, and so Athena and Glue, give the partition of NULLs.
+const NULL_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// Where the partitions are written.
+pub enum Destination<'a> {
+    Dir(&'a PathBuf),
+    /// An `s3://bucket/prefix` URI.
+    S3(&'a str, &'a S3Options),
+}
+
+/// Percent-encode characters that would upset a path or an S3 key, as
+/// Hive does.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// A value as it appears in a partition's path, or file name.
+fn path_value(field: Option<&Field>) -> String {
+    match field {
+        None | Some(Field::IsNull(_)) => NULL_PARTITION.to_owned(),
+        Some(value) => escape(&format_value(value)),
+    }
+}
+
+/// Split the results by the value of the column, in the order each value
+/// first appears, for --partition-output. Unlike --partition-by, the
+/// column is kept.
+pub fn split_by_value(
+    output: &ExecuteStatementOutput,
+    column: &str,
+) -> Result<Vec<(String, ExecuteStatementOutput)>> {
+    let index = column_indexes(output, &[column])?[0];
+    let mut parts: Vec<(String, ExecuteStatementOutput)> = Vec::new();
+    for record in output.records.iter().flatten() {
+        let value = path_value(record.get(index));
+        let part = match parts.iter().position(|(known, _part)| *known == value) {
+            Some(position) => &mut parts[position].1,
+            None => {
+                let part = ExecuteStatementOutput::builder()
+                    .set_column_metadata(output.column_metadata.clone())
+                    .set_records(Some(Vec::new()))
+                    .build();
+                parts.push((value, part));
+                &mut parts.last_mut().unwrap().1
+            }
+        };
+        part.records
+            .get_or_insert_with(Vec::new)
+            .push(record.clone());
+    }
+    Ok(parts)
+}
+
+/// Split the results by the values of the columns, keyed by the partition's
+/// path, like `date=2024-06-01`. The partition columns are left out of each
+/// part, since their values are in its path.
+fn split(
+    output: &ExecuteStatementOutput,
+    columns: &[String],
+) -> Result<BTreeMap<String, ExecuteStatementOutput>> {
+    let names: Vec<&str> = columns.iter().map(String::as_str).collect();
+    let indexes = column_indexes(output, &names)?;
+    let empty = ExecuteStatementOutput::builder()
+        .set_column_metadata(output.column_metadata.clone())
+        .set_records(Some(Vec::new()))
+        .build();
+    let mut partitions: BTreeMap<String, ExecuteStatementOutput> = BTreeMap::new();
+    for record in output.records.iter().flatten() {
+        let path: Vec<String> = names
+            .iter()
+            .zip(&indexes)
+            .map(|(name, index)| format!("{}={}", escape(name), path_value(record.get(*index))))
+            .collect();
+        partitions
+            .entry(path.join("/"))
+            .or_insert_with(|| empty.clone())
+            .records
+            .get_or_insert_with(Vec::new)
+            .push(record.clone());
+    }
+    for partition in partitions.values_mut() {
+        drop_columns(partition, columns)?;
+    }
+    Ok(partitions)
+}
+
+/// Write the bytes to the path under the destination, returning where.
+fn put(
+    config: &SdkConfig,
+    destination: &Destination,
+    path: &str,
+    content_type: &str,
+    bytes: &[u8],
+) -> Result<String> {
+    match destination {
+        Destination::Dir(dir) => {
+            let file = dir.join(path);
+            output_pattern::create(&file)?
+                .write_all(bytes)
+                .map_err(|e| anyhow!("Failed to write {}: {}", file.display(), e))?;
+            Ok(file.display().to_string())
+        }
+        Destination::S3(prefix, options) => {
+            let uri = format!("{}/{}", prefix.trim_end_matches('/'), path);
+            let mut s3_writer = S3Writer::new(config, &uri, content_type, options)?;
+            s3_writer.write_all(bytes)?;
+            s3_writer.finish()?;
+            Ok(uri)
+        }
+    }
+}
+
+/// Write the results as Parquet, one `part-0.parquet` per partition. With
+/// `manifest`, also write a `manifest.json` listing each part with its
+/// size, row count and SHA-256, in the form Redshift's COPY accepts, so
+/// that loaders can check they have every part. The parts share a schema,
+/// picked from all of the results.
+pub fn write(
+    config: &SdkConfig,
+    destination: Destination,
+    columns: &[String],
+    output: &ExecuteStatementOutput,
+    manifest: bool,
+) -> Result<()> {
+    let names: Vec<&str> = columns.iter().map(String::as_str).collect();
+    let partitioned = column_indexes(output, &names)?;
+    let schema = parquet_output::schema(output)?;
+    let kept: Vec<usize> = (0..schema.fields().len())
+        .filter(|index| !partitioned.contains(index))
+        .collect();
+    let schema = Arc::new(schema.project(&kept)?);
+    let mut entries = Vec::new();
+    for (path, partition) in split(output, columns)? {
+        let mut bytes = Vec::new();
+        parquet_output_with_schema(&partition, schema.clone(), &mut bytes)?;
+        let url = put(
+            config,
+            &destination,
+            &format!("{}/part-0.parquet", path),
+            PARQUET_CONTENT_TYPE,
+            &bytes,
+        )?;
+        entries.push(json!({
+            "url": url,
+            "mandatory": true,
+            "meta": {
+                "content_length": bytes.len(),
+                "record_count": partition.records.as_ref().map_or(0, Vec::len),
+                "sha256": sha256_hex(&bytes),
+            },
+        }));
+    }
+    if manifest {
+        let mut manifest = serde_json::to_vec_pretty(&json!({ "entries": entries }))?;
+        manifest.push(b'\n');
+        put(
+            config,
+            &destination,
+            "manifest.json",
+            "application/json",
+            &manifest,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format_header;
+    use aws_sdk_rdsdata::types::ColumnMetadata;
+
+    fn output(columns: &[&str], records: Vec<Vec<Field>>) -> ExecuteStatementOutput {
+        ExecuteStatementOutput::builder()
+            .set_column_metadata(Some(
+                columns
+                    .iter()
+                    .map(|name| ColumnMetadata::builder().name(*name).build())
+                    .collect(),
+            ))
+            .set_records(Some(records))
+            .build()
+    }
+
+    #[test]
+    fn escapes_values_for_paths() {
+        assert_eq!(escape("2024-06-01"), "2024-06-01");
+        assert_eq!(escape("a/b c"), "a%2Fb%20c");
+        assert_eq!(escape("x=1&y"), "x%3D1%26y");
+        assert_eq!(escape("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn splits_into_partitions() {
+        let long = |value| Field::LongValue(value);
+        let text = |value: &str| Field::StringValue(value.to_owned());
+        let partitions = split(
+            &output(
+                &["region", "day", "n"],
+                vec![
+                    vec![text("eu/west"), text("2024-06-01"), long(1)],
+                    vec![Field::IsNull(true), text("2024-06-01"), long(2)],
+                    vec![text("eu/west"), text("2024-06-01"), long(3)],
+                ],
+            ),
+            &["region".to_owned(), "day".to_owned()],
+        )
+        .unwrap();
+        let paths: Vec<&str> = partitions.keys().map(String::as_str).collect();
+        assert_eq!(
+            paths,
+            [
+                "region=__HIVE_DEFAULT_PARTITION__/day=2024-06-01",
+                "region=eu%2Fwest/day=2024-06-01"
+            ]
+        );
+        let part = &partitions["region=eu%2Fwest/day=2024-06-01"];
+        assert_eq!(format_header(part).collect::<Vec<_>>(), ["n"]);
+        assert_eq!(part.records, Some(vec![vec![long(1)], vec![long(3)]]));
+    }
+
+    #[test]
+    fn refuses_an_unknown_column() {
+        let error = split(&output(&["n"], Vec::new()), &["day".to_owned()]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No column matched \"day\", available columns are [\"n\"]"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Number, Value};
+use std::{iter::Peekable, str::Chars};
+
+/// The element type of a Postgres array type, which the Data API names
+/// like `_int4`, or `int4[]`.
+pub fn element_type(type_name: &str) -> Option<&str> {
+    type_name
+        .strip_prefix('_')
+        .or_else(|| type_name.strip_suffix("[]"))
+}
+
+/// An element, as JSON of its element type. Text that is not
+/// valid for the type is kept as a string.
+fn element(text: &str, element_type: &str) -> Value {
+    let parsed = match element_type {
+        "int2" | "int4" | "int8" | "oid" => text.parse::<i64>().ok().map(Value::from),
+        // JSON has no NaN or Infinity, so those stay strings.
+        "float4" | "float8" => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        "bool" => match text {
+            "t" => Some(Value::Bool(true)),
+            "f" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::from(text))
+}
+
+/// Parse one array, from just after its `{` to just after its `}`.
+fn parse_array(chars: &mut Peekable<Chars>, element_type: &str) -> Option<Value> {
+    let mut elements = Vec::new();
+    if chars.next_if_eq(&'}').is_some() {
+        return Some(Value::Array(elements));
+    }
+    loop {
+        match chars.peek()? {
+            '{' => {
+                chars.next();
+                elements.push(parse_array(chars, element_type)?);
+            }
+            '"' => {
+                chars.next();
+                let mut quoted = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => quoted.push(chars.next()?),
+                        c => quoted.push(c),
+                    }
+                }
+                elements.push(element(&quoted, element_type));
+            }
+            _ => {
+                let mut unquoted = String::new();
+                while let Some(c) = chars.next_if(|c| *c != ',' && *c != '}') {
+                    unquoted.push(c);
+                }
+                let unquoted = unquoted.trim();
+                if unquoted.eq_ignore_ascii_case("NULL") {
+                    elements.push(Value::Null);
+                } else {
+                    elements.push(element(unquoted, element_type));
+                }
+            }
+        }
+        match chars.next()? {
+            ',' => continue,
+            '}' => return Some(Value::Array(elements)),
+            _ => return None,
+        }
+    }
+}
+
+/// Parse Postgres array text, like `{a,"b c",NULL}` or `{{1,2},{3,4}}`,
+/// into a JSON array.
+pub fn parse(text: &str, element_type: &str) -> Result<Value> {
+    let invalid = || anyhow!("Array value \"{}\" is not Postgres array text", text);
+    // Arrays not starting at 1 lead with their bounds, like `[0:1]={a,b}`.
+    let array = match text.strip_prefix('[') {
+        Some(_) => text.split_once('=').ok_or_else(invalid)?.1,
+        None => text,
+    };
+    let mut chars = array.trim().chars().peekable();
+    if chars.next() != Some('{') {
+        return Err(invalid());
+    }
+    match parse_array(&mut chars, element_type) {
+        Some(value) if chars.next().is_none() => Ok(value),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn finds_element_types() {
+        assert_eq!(element_type("_int4"), Some("int4"));
+        assert_eq!(element_type("text[]"), Some("text"));
+        assert_eq!(element_type("int4"), None);
+    }
+
+    #[test]
+    fn parses_arrays() {
+        assert_eq!(parse("{1,2,NULL}", "int4").unwrap(), json!([1, 2, null]));
+        assert_eq!(
+            parse(r#"{a,"b c","say \"hi\"","NULL","x,y"}"#, "text").unwrap(),
+            json!(["a", "b c", "say \"hi\"", "NULL", "x,y"])
+        );
+        assert_eq!(
+            parse("{{1,2},{3,4}}", "int8").unwrap(),
+            json!([[1, 2], [3, 4]])
+        );
+        assert_eq!(parse("{}", "text").unwrap(), json!([]));
+        assert_eq!(parse("[0:1]={t,f}", "bool").unwrap(), json!([true, false]));
+        assert_eq!(
+            parse("{1.5,NaN,abc}", "float8").unwrap(),
+            json!([1.5, "NaN", "abc"])
+        );
+    }
+
+    #[test]
+    fn refuses_what_is_not_array_text() {
+        for text in ["1,2", "{1,2", "{1,2}x", "[0:1]", "{\"a}"] {
+            assert_eq!(
+                parse(text, "int4").unwrap_err().to_string(),
+                format!("Array value \"{}\" is not Postgres array text", text)
+            );
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{dialect::READ_ONLY, output_pattern::cluster_id, Request};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// A rule on which statements may run, from a `[[policy]]` section of the
+/// configuration file. It applies to a statement when each of its filters
+/// that is given matches. Statement kinds are named by their leading keyword.
+/// For example, to refuse DROP and TRUNCATE on clusters tagged
+/// `env = prod`, and to let the `analyst` profile run only queries:
+///
+/// ```toml
+/// [[policy]]
+/// tags = { env = "prod" }
+/// deny = ["DROP", "TRUNCATE"]
+///
+/// [[policy]]
+/// profiles = ["analyst"]
+/// allow = ["SELECT"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PolicyRule {
+    /// AWS profiles the rule applies to, with `default` when none is given.
+    #[serde(default)]
+    pub profiles: Vec<String>,
+
+    /// Cluster identifiers the rule applies to.
+    #[serde(default)]
+    pub clusters: Vec<String>,
+
+    /// Tags a cluster must all have for the rule to apply to it.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+
+    /// When given, the only statement kinds allowed.
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    /// Statement kinds refused.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+fn names(list: &[String], name: &str) -> bool {
+    list.iter().any(|listed| listed.eq_ignore_ascii_case(name))
+}
+
+async fn cluster_tags(config: &SdkConfig, resource_arn: &str) -> Result<BTreeMap<String, String>> {
+    let output = aws_sdk_rds::Client::new(config)
+        .list_tags_for_resource()
+        .resource_name(resource_arn)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to lookup tags of {}: {}", resource_arn, e))?;
+    info!("{:?}", output);
+    Ok(output
+        .tag_list
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|tag| Some((tag.key?, tag.value.unwrap_or_default())))
+        .collect())
+}
+
+/// Whether the request's statements only read. Statements we cannot parse
+/// are assumed to write.
+pub fn reads_only(request: &Request) -> bool {
+    request.target.dialect().reads_only(&request.sql)
+}
+
+/// Statement kinds a setup statement, from --init-sql, may also be under
+/// --read-only, since they only change the session.
+pub const SESSION: &[&str] = &["SET"];
+
+fn only(request: &Request, allowed: &[&str]) -> Result<()> {
+    let kinds = request
+        .target
+        .dialect()
+        .statement_kinds(&request.sql)
+        .map_err(|e| anyhow!("--read-only cannot check the SQL: {}", e))?;
+    match kinds.iter().find(|kind| !allowed.contains(&kind.as_str())) {
+        Some(kind) => Err(anyhow!(
+            "--read-only does not allow {} statements, allowed statements are {:?}",
+            kind,
+            allowed
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Refuse the request unless its statements only read, for --read-only.
+pub fn read_only(request: &Request) -> Result<()> {
+    only(request, READ_ONLY)
+}
+
+/// Refuse the setup statement unless it only reads or sets session
+/// variables, for --read-only.
+pub fn read_only_setup(request: &Request) -> Result<()> {
+    only(request, &[READ_ONLY, SESSION].concat())
+}
+
+/// Refuse the request when a policy rule that applies to it does not allow
+/// its statements. SQL that cannot be parsed is refused by any rule that
+/// applies. Cluster tags are only looked up when a rule needs them.
+pub async fn check(
+    config: &SdkConfig,
+    rules: &[PolicyRule],
+    profile: Option<&str>,
+    request: &Request,
+) -> Result<()> {
+    let profile = profile.unwrap_or("default");
+    let cluster = cluster_id(&request.target.resource_arn);
+    let mut applicable: Vec<&PolicyRule> = rules
+        .iter()
+        .filter(|rule| rule.profiles.is_empty() || rule.profiles.iter().any(|p| p == profile))
+        .filter(|rule| rule.clusters.is_empty() || rule.clusters.iter().any(|c| c == cluster))
+        .collect();
+    if applicable.iter().any(|rule| !rule.tags.is_empty()) {
+        let tags = cluster_tags(config, &request.target.resource_arn).await?;
+        applicable.retain(|rule| {
+            rule.tags
+                .iter()
+                .all(|(key, value)| tags.get(key) == Some(value))
+        });
+    }
+    if applicable.is_empty() {
+        return Ok(());
+    }
+    let kinds = request
+        .target
+        .dialect()
+        .statement_kinds(&request.sql)
+        .map_err(|e| anyhow!("The policy for {} cannot check the SQL: {}", cluster, e))?;
+    for rule in applicable {
+        for kind in &kinds {
+            if names(&rule.deny, kind) {
+                return Err(anyhow!(
+                    "The policy for {} denies {} statements",
+                    cluster,
+                    kind
+                ));
+            }
+            if !rule.allow.is_empty() && !names(&rule.allow, kind) {
+                return Err(anyhow!(
+                    "The policy for {} does not allow {} statements, allowed statements are {:?}",
+                    cluster,
+                    kind,
+                    rule.allow
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Target;
+
+    fn request(sql: &str) -> Request {
+        Request {
+            target: Target {
+                resource_arn: "arn:aws:rds:us-east-1:123456789012:cluster:orders".to_owned(),
+                secret_arn: "arn:aws:secretsmanager:us-east-1:123456789012:secret:orders"
+                    .to_owned(),
+                database: None,
+                schema: None,
+                engine: Some("aurora-postgresql".to_owned()),
+            },
+            params: Vec::new(),
+            sql: sql.to_owned(),
+        }
+    }
+
+    #[test]
+    fn read_only_allows_reads() {
+        assert!(read_only(&request("select 1; show search_path")).is_ok());
+        assert!(read_only(&request("explain select * from t")).is_ok());
+        assert!(reads_only(&request("select 1")));
+    }
+
+    #[test]
+    fn read_only_refuses_writes() {
+        let refused = |sql| read_only(&request(sql)).unwrap_err().to_string();
+        assert!(refused("select 1; insert into t values (1)")
+            .starts_with("--read-only does not allow INSERT statements"));
+        assert!(
+            refused("with gone as (delete from t returning *) select * from gone")
+                .starts_with("--read-only does not allow DELETE statements")
+        );
+        assert!(refused("explain analyze delete from t")
+            .starts_with("--read-only does not allow DELETE statements"));
+        assert!(refused("truncate t").starts_with("--read-only does not allow TRUNCATE statements"));
+        assert!(refused("selec 1").starts_with("--read-only cannot check the SQL"));
+        assert!(!reads_only(&request("update t set a = 1")));
+        assert!(!reads_only(&request("selec 1")));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{format_header, format_value};
+use anyhow::Result;
+use aws_sdk_rdsdata::{operation::execute_statement::ExecuteStatementOutput, types::Field};
+use clap::ValueEnum;
+use std::io::{stdout, IsTerminal, Write};
+
+/// When to color output.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    /// When writing to a terminal, and NO_COLOR is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// How to decorate output for a human reader.
+#[derive(Copy, Clone, Debug)]
+pub struct Style {
+    color: bool,
+    max_col_width: Option<usize>,
+}
+
+impl Style {
+    /// No decoration, for output that is not going to a terminal.
+    pub fn plain() -> Style {
+        Style {
+            color: false,
+            max_col_width: None,
+        }
+    }
+
+    pub fn for_stdout(choice: ColorChoice) -> Style {
+        let color = match choice {
+            ColorChoice::Auto => stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        Style {
+            color,
+            max_col_width: None,
+        }
+    }
+
+    /// Truncate longer table cells, with an ellipsis.
+    pub fn max_col_width(self, max_col_width: Option<u32>) -> Style {
+        Style {
+            max_col_width: max_col_width.map(|max| max as usize),
+            ..self
+        }
+    }
+
+    fn truncate(&self, text: String) -> String {
+        match self.max_col_width {
+            Some(max) if text.chars().count() > max => {
+                let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+                truncated.push('…');
+                truncated
+            }
+            _ => text,
+        }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    pub fn header(&self, text: &str) -> String {
+        self.paint(BOLD, text)
+    }
+
+    /// Style a formatted value, dimming NULLs.
+    pub fn value(&self, value: &Field, text: &str) -> String {
+        match value {
+            Field::IsNull(_) => self.paint(DIM, text),
+            _ => text.to_owned(),
+        }
+    }
+}
+
+fn is_number(value: &Field) -> bool {
+    matches!(value, Field::LongValue(_) | Field::DoubleValue(_))
+}
+
+/// Write the records as aligned columns, with numbers right-aligned.
+pub fn table_output(
+    result: &ExecuteStatementOutput,
+    style: Style,
+    mut writer: impl Write,
+) -> Result<()> {
+    if result.number_of_records_updated > 0 || result.column_metadata.is_none() {
+        writeln!(
+            writer,
+            "number_of_records_updated: {}",
+            result.number_of_records_updated
+        )?;
+    }
+    let header: Vec<&str> = format_header(result).collect();
+    if header.is_empty() {
+        return Ok(());
+    }
+    let records = result.records.as_deref().unwrap_or(&[]);
+    let rows: Vec<Vec<(&Field, String)>> = records
+        .iter()
+        .map(|record| {
+            record
+                .iter()
+                .map(|value| (value, style.truncate(format_value(value))))
+                .collect()
+        })
+        .collect();
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in &rows {
+        for (width, (_value, text)) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text.chars().count());
+        }
+    }
+    // Padding is worked out before styling, which adds invisible characters.
+    let pad = |text: &str, width: usize, right: bool| {
+        let padding = " ".repeat(width.saturating_sub(text.chars().count()));
+        if right {
+            padding + text
+        } else {
+            text.to_owned() + &padding
+        }
+    };
+    let cells: Vec<String> = header
+        .iter()
+        .zip(&widths)
+        .map(|(name, width)| style.header(&pad(name, *width, false)))
+        .collect();
+    writeln!(writer, " {}", cells.join(" | ").trim_end())?;
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+    writeln!(writer, "{}", rule.join("+"))?;
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|((value, text), width)| style.value(value, &pad(text, *width, is_number(value))))
+            .collect();
+        writeln!(writer, " {}", cells.join(" | ").trim_end())?;
+    }
+    let count = rows.len();
+    writeln!(
+        writer,
+        "({} row{})",
+        count,
+        if count == 1 { "" } else { "s" }
+    )?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    io::{stderr, IsTerminal},
+    time::Duration,
+};
+
+/// A spinner on stderr naming what we are waiting for, so a slow statement
+/// doesn't look like a hang. Nothing is shown when stderr is not a terminal.
+pub struct Progress {
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    pub fn start(stage: &str) -> Progress {
+        if !stderr().is_terminal() {
+            return Progress { bar: None };
+        }
+        let bar = ProgressBar::new_spinner();
+        if let Ok(style) = ProgressStyle::with_template("{spinner} {msg} {elapsed}") {
+            bar.set_style(style);
+        }
+        bar.enable_steady_tick(Duration::from_millis(100));
+        let progress = Progress { bar: Some(bar) };
+        progress.stage(stage);
+        progress
+    }
+
+    /// Move on to the next stage, restarting the clock.
+    pub fn stage(&self, stage: &str) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(format!("{}…", stage));
+            bar.reset_elapsed();
+        }
+    }
+
+    /// Hide the spinner while `f` runs, as when asking a question.
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match &self.bar {
+            Some(bar) => bar.suspend(f),
+            None => f(),
+        }
+    }
+}
+
+impl Drop for Progress {
+    /// Clear the spinner, before anything else is written.
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
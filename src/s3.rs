@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_s3::{
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart, ServerSideEncryption, StorageClass},
+    Client,
+};
+use clap::{builder::PossibleValuesParser, Args};
+use std::{fmt::Display, future::Future, io, time::Duration};
+use tokio::{runtime::Handle, task::block_in_place, time::sleep};
+use tracing::{info, warn};
+
+/// S3 requires every part but the last to be at least 5 MiB.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// How many times to retry an upload call beyond the SDK's own retries,
+/// since one failed part would otherwise lose the whole export.
+const UPLOAD_RETRIES: u32 = 3;
+
+/// How uploaded objects are stored.
+#[derive(Args, Clone, Debug, Default)]
+pub struct S3Options {
+    /// Encrypt uploaded objects with this server-side encryption.
+    #[clap(long, value_parser = ["AES256", "aws:kms", "aws:kms:dsse"])]
+    sse: Option<String>,
+
+    /// With --sse aws:kms, the KMS key to encrypt with, instead of the
+    /// account's default S3 key.
+    #[clap(long, requires = "sse")]
+    kms_key_id: Option<String>,
+
+    /// Storage class of uploaded objects, such as STANDARD_IA.
+    #[clap(long, value_parser = PossibleValuesParser::new(StorageClass::values()))]
+    storage_class: Option<String>,
+}
+
+/// Split `s3://bucket/key` into the bucket and key.
+pub fn parse_s3_uri(uri: &str) -> Result<(String, String)> {
+    match uri
+        .strip_prefix("s3://")
+        .and_then(|rest| rest.split_once('/'))
+    {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => {
+            Ok((bucket.to_owned(), key.to_owned()))
+        }
+        _ => Err(anyhow!("\"{}\" is not of the form s3://bucket/key", uri)),
+    }
+}
+
+/// A `Write` that streams into an S3 object through a multipart upload.
+/// Output smaller than one part is sent with a single `put_object`.
+/// Nothing is visible in S3 until `finish` succeeds.
+pub struct S3Writer {
+    client: Client,
+    runtime: Handle,
+    bucket: String,
+    key: String,
+    content_type: String,
+    options: S3Options,
+    upload_id: Option<String>,
+    parts: Vec<CompletedPart>,
+    buffer: Vec<u8>,
+}
+
+impl S3Writer {
+    /// Must be called from within a multi-threaded tokio runtime.
+    pub fn new(
+        config: &SdkConfig,
+        uri: &str,
+        content_type: &str,
+        options: &S3Options,
+    ) -> Result<S3Writer> {
+        let (bucket, key) = parse_s3_uri(uri)?;
+        Ok(S3Writer {
+            client: Client::new(config),
+            runtime: Handle::current(),
+            bucket,
+            key,
+            content_type: content_type.to_owned(),
+            options: options.clone(),
+            upload_id: None,
+            parts: Vec::new(),
+            buffer: Vec::with_capacity(PART_SIZE),
+        })
+    }
+
+    /// `Write` is synchronous, so we wait here for each S3 call.
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        block_in_place(|| self.runtime.block_on(future))
+    }
+
+    fn sse(&self) -> Option<ServerSideEncryption> {
+        self.options.sse.as_deref().map(ServerSideEncryption::from)
+    }
+
+    fn storage_class(&self) -> Option<StorageClass> {
+        self.options
+            .storage_class
+            .as_deref()
+            .map(StorageClass::from)
+    }
+
+    /// Make the call, retrying it with a growing delay when it fails.
+    fn with_retries<T, E: Display, F: Future<Output = Result<T, E>>>(
+        &self,
+        what: &str,
+        call: impl Fn() -> F,
+    ) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match self.block_on(call()) {
+                Ok(output) => return Ok(output),
+                Err(e) if attempt < UPLOAD_RETRIES => {
+                    attempt += 1;
+                    warn!("Failed to {}, retrying: {}", what, e);
+                    self.block_on(sleep(Duration::from_millis(500 << attempt)));
+                }
+                Err(e) => return Err(anyhow!("Failed to {}: {}", what, e)),
+            }
+        }
+    }
+
+    fn upload_part(&mut self) -> Result<()> {
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let created = self.with_retries("start upload to S3", || {
+                    self.client
+                        .create_multipart_upload()
+                        .bucket(&self.bucket)
+                        .key(&self.key)
+                        .content_type(&self.content_type)
+                        .set_server_side_encryption(self.sse())
+                        .set_ssekms_key_id(self.options.kms_key_id.clone())
+                        .set_storage_class(self.storage_class())
+                        .send()
+                })?;
+                let upload_id = created
+                    .upload_id
+                    .ok_or_else(|| anyhow!("S3 returned no upload id"))?;
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            }
+        };
+        let part_number = self.parts.len() as i32 + 1;
+        let body = std::mem::take(&mut self.buffer);
+        let uploaded = self.with_retries(&format!("upload part {} to S3", part_number), || {
+            self.client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .upload_id(&upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(body.clone()))
+                .send()
+        })?;
+        info!(
+            "Uploaded part {} to s3://{}/{}",
+            part_number, self.bucket, self.key
+        );
+        self.parts.push(
+            CompletedPart::builder()
+                .set_e_tag(uploaded.e_tag)
+                .part_number(part_number)
+                .build(),
+        );
+        Ok(())
+    }
+
+    /// Upload whatever is left and complete the object.
+    pub fn finish(mut self) -> Result<()> {
+        if self.upload_id.is_none() {
+            let body = std::mem::take(&mut self.buffer);
+            self.with_retries("upload to S3", || {
+                self.client
+                    .put_object()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .content_type(&self.content_type)
+                    .set_server_side_encryption(self.sse())
+                    .set_ssekms_key_id(self.options.kms_key_id.clone())
+                    .set_storage_class(self.storage_class())
+                    .body(ByteStream::from(body.clone()))
+                    .send()
+            })?;
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.upload_part()?;
+        }
+        let upload_id = self.upload_id.take().unwrap_or_default();
+        let completed = CompletedMultipartUpload::builder()
+            .set_parts(Some(std::mem::take(&mut self.parts)))
+            .build();
+        let result = self.with_retries("complete upload to S3", || {
+            self.client
+                .complete_multipart_upload()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .upload_id(&upload_id)
+                .multipart_upload(completed.clone())
+                .send()
+        });
+        if result.is_err() {
+            // Let Drop clean up the parts already uploaded.
+            self.upload_id = Some(upload_id);
+        }
+        result.map(|_output| ())
+    }
+}
+
+impl io::Write for S3Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= PART_SIZE {
+            self.upload_part().map_err(io::Error::other)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Parts have a minimum size, so flushing waits for `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for S3Writer {
+    /// Abort an unfinished multipart upload, so S3 doesn't keep (and charge
+    /// for) its orphaned parts.
+    fn drop(&mut self) {
+        if let Some(upload_id) = self.upload_id.take() {
+            let _result = self.block_on(
+                self.client
+                    .abort_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .upload_id(upload_id)
+                    .send(),
+            );
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::audit::now_timestamp;
+use anyhow::{anyhow, Error, Result};
+use std::{future::Future, str::FromStr};
+use time::{format_description::well_known::Rfc3339, Date, Month, OffsetDateTime, Time};
+use tokio::time::sleep;
+use tracing::info;
+
+/// How far ahead to look for the next run, so that a schedule that can
+/// never match, like `0 0 30 2 *`, fails instead of looping forever.
+const MAX_YEARS_AHEAD: i32 = 5;
+
+/// A cron schedule: minute, hour, day of month, month and day of week, in
+/// UTC. Each field is `*`, a number, a range `a-b`, any of those with a
+/// step like `*/15`, or a comma separated list of them. Days of the week
+/// count from 0 for Sunday, which may also be 7.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    text: String,
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    /// When both days of the month and of the week are restricted, cron
+    /// runs on days matching either.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+/// Which values from `min` to `max` the field allows, indexed from 0.
+fn parse_field(field: &str, name: &str, min: u32, max: u32) -> Result<Vec<bool>> {
+    let mut allowed = vec![false; max as usize + 1];
+    let invalid = || {
+        anyhow!(
+            "Schedule {} \"{}\" is not *, a number from {} to {}, a range, or a step",
+            name,
+            field,
+            min,
+            max
+        )
+    };
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (item, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (
+                first.parse().map_err(|_| invalid())?,
+                last.parse().map_err(|_| invalid())?,
+            ),
+            // A single value with a step runs from there to the end.
+            None if item.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+            None => {
+                let value = range.parse().map_err(|_| invalid())?;
+                (value, value)
+            }
+        };
+        if step == 0 || first < min || last > max || first > last {
+            return Err(invalid());
+        }
+        for value in (first..=last).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Schedule> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(anyhow!(
+                "Schedule \"{}\" does not have the five fields {:?}",
+                s,
+                ["minute", "hour", "day of month", "month", "day of week"]
+            ));
+        };
+        let mut weekdays = parse_field(weekday, "day of week", 0, 7)?;
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        Ok(Schedule {
+            text: s.to_owned(),
+            minutes: parse_field(minute, "minute", 0, 59)?,
+            hours: parse_field(hour, "hour", 0, 23)?,
+            days: parse_field(day, "day of month", 1, 31)?,
+            months: parse_field(month, "month", 1, 12)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+}
+
+impl Schedule {
+    fn day_matches(&self, date: Date) -> bool {
+        let day = self.days[date.day() as usize];
+        let weekday = self.weekdays[date.weekday().number_days_from_sunday() as usize];
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first minute after `time` that the schedule runs at.
+    pub fn next_after(&self, time: OffsetDateTime) -> Result<OffsetDateTime> {
+        let never = || anyhow!("Schedule \"{}\" never runs", self.text);
+        let limit = time.year() + MAX_YEARS_AHEAD;
+        let mut next = time
+            .replace_second(0)
+            .and_then(|next| next.replace_nanosecond(0))?
+            + time::Duration::MINUTE;
+        while next.year() <= limit {
+            let midnight = |date: Option<Date>| {
+                date.map(|date| next.replace_date(date).replace_time(Time::MIDNIGHT))
+                    .ok_or_else(never)
+            };
+            if !self.months[u8::from(next.month()) as usize] {
+                // The first day of the next month.
+                let (year, month) = match next.month() {
+                    Month::December => (next.year() + 1, Month::January),
+                    month => (next.year(), month.next()),
+                };
+                next = midnight(Date::from_calendar_date(year, month, 1).ok())?;
+            } else if !self.day_matches(next.date()) {
+                next = midnight(next.date().next_day())?;
+            } else if !self.hours[next.hour() as usize] {
+                next = next.replace_minute(0)? + time::Duration::HOUR;
+            } else if !self.minutes[next.minute() as usize] {
+                next += time::Duration::MINUTE;
+            } else {
+                return Ok(next);
+            }
+        }
+        Err(never())
+    }
+}
+
+/// Run the job at each time the schedule gives, forever. A failed run is
+/// reported on stderr, and does not stop later runs.
+pub async fn run<F, Fut>(schedule: &Schedule, mut job: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    loop {
+        let now = OffsetDateTime::now_utc();
+        let next = schedule.next_after(now)?;
+        info!("Next run at {}", next.format(&Rfc3339)?);
+        sleep((next - now).try_into().unwrap_or_default()).await;
+        if let Err(e) = job().await {
+            eprintln!("{}: {}", now_timestamp(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(text, &Rfc3339).unwrap()
+    }
+
+    fn next(schedule: &str, after: &str) -> String {
+        schedule
+            .parse::<Schedule>()
+            .unwrap()
+            .next_after(at(after))
+            .unwrap()
+            .format(&Rfc3339)
+            .unwrap()
+    }
+
+    fn allowed(field: &str, min: u32, max: u32) -> Vec<u32> {
+        parse_field(field, "minute", min, max)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_value, allowed)| **allowed)
+            .map(|(value, _allowed)| value as u32)
+            .collect()
+    }
+
+    #[test]
+    fn parses_steps_ranges_and_lists() {
+        assert_eq!(allowed("*/15", 0, 59), [0, 15, 30, 45]);
+        assert_eq!(allowed("5/15", 0, 59), [5, 20, 35, 50]);
+        assert_eq!(allowed("1-5", 0, 23), [1, 2, 3, 4, 5]);
+        assert_eq!(allowed("10-20/5,3", 0, 59), [3, 10, 15, 20]);
+        assert_eq!(allowed("*/5", 1, 12), [1, 6, 11]);
+    }
+
+    #[test]
+    fn refuses_bad_fields() {
+        for field in ["60", "*/0", "5-1", "a", "1-", "", "*/x"] {
+            let error = parse_field(field, "minute", 0, 59).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "Schedule minute \"{}\" is not *, a number from 0 to 59, a range, or a step",
+                    field
+                )
+            );
+        }
+        assert!("0 0 * *".parse::<Schedule>().is_err());
+        assert!("0 0 0 * *".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn finds_the_next_minute() {
+        assert_eq!(
+            next("*/15 * * * *", "2026-10-14T10:07:42Z"),
+            "2026-10-14T10:15:00Z"
+        );
+        // The next run is always after the time given.
+        assert_eq!(
+            next("*/15 * * * *", "2026-10-14T10:15:00Z"),
+            "2026-10-14T10:30:00Z"
+        );
+        assert_eq!(
+            next("30 9-17 * * *", "2026-10-14T17:45:00Z"),
+            "2026-10-15T09:30:00Z"
+        );
+    }
+
+    #[test]
+    fn rolls_over_into_the_next_year() {
+        assert_eq!(
+            next("0 0 1 * *", "2026-12-31T23:59:30Z"),
+            "2027-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            next("0 12 * 3 *", "2026-12-15T00:00:00Z"),
+            "2027-03-01T12:00:00Z"
+        );
+    }
+
+    #[test]
+    fn counts_7_as_sunday() {
+        // 2026-10-18 is a Sunday.
+        assert_eq!(
+            next("0 8 * * 7", "2026-10-14T00:00:00Z"),
+            "2026-10-18T08:00:00Z"
+        );
+        assert_eq!(
+            next("0 8 * * 0", "2026-10-14T00:00:00Z"),
+            "2026-10-18T08:00:00Z"
+        );
+    }
+
+    #[test]
+    fn runs_on_either_restricted_day() {
+        // The 20th, or a Sunday, whichever comes first.
+        assert_eq!(
+            next("0 0 20 * 0", "2026-10-14T00:00:00Z"),
+            "2026-10-18T00:00:00Z"
+        );
+        assert_eq!(
+            next("0 0 15 * 0", "2026-10-14T00:00:00Z"),
+            "2026-10-15T00:00:00Z"
+        );
+        // With only one of them restricted, that one decides.
+        assert_eq!(
+            next("0 0 20 * *", "2026-10-14T00:00:00Z"),
+            "2026-10-20T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn reports_a_schedule_that_never_runs() {
+        let schedule: Schedule = "0 0 31 2 *".parse().unwrap();
+        assert_eq!(
+            schedule
+                .next_after(at("2026-10-14T00:00:00Z"))
+                .unwrap_err()
+                .to_string(),
+            "Schedule \"0 0 31 2 *\" never runs"
+        );
+    }
+}
@@ -0,0 +1,571 @@
+use crate::{dialect::Dialect, format_value, guard::Guard, Request, Target};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::types::Field;
+use clap::{Args, Subcommand};
+use futures::try_join;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::Write};
+
+/// Inspect the structure of the target database.
+#[derive(Args, Clone, Debug)]
+pub struct SchemaArgs {
+    #[clap(subcommand)]
+    pub action: SchemaAction,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum SchemaAction {
+    /// Describe the tables, columns, constraints and indexes, as DDL.
+    ///
+    /// Tables come from the current database on MySQL, and from the current
+    /// schema (or `--schema`) on PostgreSQL. Foreign keys are added after
+    /// every table is created, so the DDL can be replayed in order.
+    Dump {
+        /// Describe the schema as JSON, instead of DDL.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Compare the schemas of two clusters, to find drift.
+    ///
+    /// Differences are written as CSV, with a leading `diff` column of `-`
+    /// for objects only in target A, `+` for objects only in target B and
+    /// `~` for objects that differ. Exits with an error when there are
+    /// differences.
+    Diff {
+        /// RDS cluster identifier of the first target.
+        #[clap(long)]
+        target_a: String,
+
+        /// RDS cluster identifier of the second target.
+        #[clap(long)]
+        target_b: String,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    /// The default as a SQL expression.
+    pub default: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Reference {
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Constraint {
+    pub name: String,
+    /// PRIMARY KEY, UNIQUE or FOREIGN KEY.
+    pub kind: String,
+    pub columns: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub references: Option<Reference>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Index {
+    pub name: String,
+    pub unique: bool,
+    pub columns: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+    pub constraints: Vec<Constraint>,
+    /// Indexes other than those backing a constraint.
+    pub indexes: Vec<Index>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Schema {
+    pub tables: Vec<Table>,
+}
+
+type Row = Vec<Option<String>>;
+
+fn text(value: &Field) -> Option<String> {
+    match value {
+        Field::IsNull(_) => None,
+        value => Some(format_value(value)),
+    }
+}
+
+async fn rows(guard: &Guard, config: &SdkConfig, target: &Target, sql: String) -> Result<Vec<Row>> {
+    let request = Request {
+        target: target.clone(),
+        params: Vec::new(),
+        sql,
+    };
+    let output = guard.execute(config, request).await?;
+    Ok(output
+        .records
+        .unwrap_or_default()
+        .iter()
+        .map(|record| record.iter().map(text).collect())
+        .collect())
+}
+
+/// A SQL expression for the schema holding the tables.
+pub fn scope(target: &Target) -> String {
+    let dialect = target.dialect();
+    match (&target.schema, dialect) {
+        (Some(schema), _) => dialect.quote_literal(schema),
+        (None, Dialect::MySql) => "DATABASE()".to_owned(),
+        (None, Dialect::Postgres) => "current_schema()".to_owned(),
+    }
+}
+
+fn tables_sql(scope: &str) -> String {
+    format!(
+        "SELECT table_name FROM information_schema.tables \
+         WHERE table_schema = {} AND table_type = 'BASE TABLE' ORDER BY table_name",
+        scope
+    )
+}
+
+/// Columns as table, name, type, nullable, default. MySQL reports string
+/// defaults unquoted, so those are quoted here to keep them valid SQL.
+fn columns_sql(dialect: Dialect, scope: &str) -> String {
+    match dialect {
+        Dialect::MySql => format!(
+            "SELECT table_name, column_name, \
+             TRIM(CONCAT(column_type, IF(extra LIKE '%auto_increment%', ' AUTO_INCREMENT', ''))), \
+             is_nullable, \
+             CASE WHEN column_default IS NULL THEN NULL \
+             WHEN extra LIKE '%DEFAULT_GENERATED%' OR column_default = 'CURRENT_TIMESTAMP' THEN column_default \
+             ELSE QUOTE(column_default) END \
+             FROM information_schema.columns WHERE table_schema = {} \
+             ORDER BY table_name, ordinal_position",
+            scope
+        ),
+        Dialect::Postgres => format!(
+            "SELECT c.table_name, c.column_name, pg_catalog.format_type(a.atttypid, a.atttypmod), \
+             c.is_nullable, c.column_default \
+             FROM information_schema.columns c \
+             JOIN pg_catalog.pg_attribute a \
+             ON a.attrelid = (quote_ident(c.table_schema) || '.' || quote_ident(c.table_name))::regclass \
+             AND a.attname = c.column_name \
+             WHERE c.table_schema = {} ORDER BY c.table_name, c.ordinal_position",
+            scope
+        ),
+    }
+}
+
+/// Constraint columns as table, name, kind, column, referenced table and
+/// referenced column.
+fn constraints_sql(dialect: Dialect, scope: &str) -> String {
+    let references = match dialect {
+        Dialect::MySql => "kcu.referenced_table_name, kcu.referenced_column_name \
+             FROM information_schema.table_constraints tc \
+             JOIN information_schema.key_column_usage kcu \
+             ON kcu.constraint_schema = tc.constraint_schema \
+             AND kcu.constraint_name = tc.constraint_name AND kcu.table_name = tc.table_name",
+        Dialect::Postgres => "ref.table_name, ref.column_name \
+             FROM information_schema.table_constraints tc \
+             JOIN information_schema.key_column_usage kcu \
+             ON kcu.constraint_schema = tc.constraint_schema \
+             AND kcu.constraint_name = tc.constraint_name AND kcu.table_name = tc.table_name \
+             LEFT JOIN information_schema.referential_constraints rc \
+             ON rc.constraint_schema = tc.constraint_schema AND rc.constraint_name = tc.constraint_name \
+             LEFT JOIN information_schema.key_column_usage ref \
+             ON ref.constraint_schema = rc.unique_constraint_schema \
+             AND ref.constraint_name = rc.unique_constraint_name \
+             AND ref.ordinal_position = kcu.position_in_unique_constraint",
+    };
+    format!(
+        "SELECT tc.table_name, tc.constraint_name, tc.constraint_type, kcu.column_name, {} \
+         WHERE tc.table_schema = {} AND tc.constraint_type IN ('PRIMARY KEY', 'UNIQUE', 'FOREIGN KEY') \
+         ORDER BY tc.table_name, tc.constraint_name, kcu.ordinal_position",
+        references, scope
+    )
+}
+
+/// Index columns as table, name, unique and column. Expression indexes
+/// are left out, having no plain columns.
+fn indexes_sql(dialect: Dialect, scope: &str) -> String {
+    match dialect {
+        Dialect::MySql => format!(
+            "SELECT table_name, index_name, non_unique = 0, column_name \
+             FROM information_schema.statistics \
+             WHERE table_schema = {} AND column_name IS NOT NULL \
+             ORDER BY table_name, index_name, seq_in_index",
+            scope
+        ),
+        Dialect::Postgres => format!(
+            "SELECT t.relname, i.relname, ix.indisunique, a.attname \
+             FROM pg_catalog.pg_index ix \
+             JOIN pg_catalog.pg_class t ON t.oid = ix.indrelid \
+             JOIN pg_catalog.pg_class i ON i.oid = ix.indexrelid \
+             JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace \
+             JOIN LATERAL unnest(ix.indkey) WITH ORDINALITY AS k(attnum, position) ON true \
+             JOIN pg_catalog.pg_attribute a ON a.attrelid = t.oid AND a.attnum = k.attnum \
+             WHERE n.nspname = {} ORDER BY t.relname, i.relname, k.position",
+            scope
+        ),
+    }
+}
+
+fn required(row: &Row, index: usize) -> Result<String> {
+    row.get(index)
+        .cloned()
+        .flatten()
+        .ok_or_else(|| anyhow!("Unexpected NULL in schema description {:?}", row))
+}
+
+/// Read the target's schema from `information_schema` and the catalogs.
+pub async fn introspect(guard: &Guard, config: &SdkConfig, target: &Target) -> Result<Schema> {
+    let dialect = target.dialect();
+    let scope = scope(target);
+    let (table_rows, column_rows, constraint_rows, index_rows) = try_join!(
+        rows(guard, config, target, tables_sql(&scope)),
+        rows(guard, config, target, columns_sql(dialect, &scope)),
+        rows(guard, config, target, constraints_sql(dialect, &scope)),
+        rows(guard, config, target, indexes_sql(dialect, &scope)),
+    )?;
+    let mut tables: BTreeMap<String, Table> = BTreeMap::new();
+    for row in &table_rows {
+        let name = required(row, 0)?;
+        tables.insert(
+            name.clone(),
+            Table {
+                name,
+                ..Default::default()
+            },
+        );
+    }
+    for row in &column_rows {
+        // Views have columns too, and are left out.
+        if let Some(table) = tables.get_mut(&required(row, 0)?) {
+            table.columns.push(Column {
+                name: required(row, 1)?,
+                data_type: required(row, 2)?,
+                nullable: required(row, 3)? == "YES",
+                default: row.get(4).cloned().flatten(),
+            });
+        }
+    }
+    for row in &constraint_rows {
+        let Some(table) = tables.get_mut(&required(row, 0)?) else {
+            continue;
+        };
+        let name = required(row, 1)?;
+        let column = required(row, 3)?;
+        let referenced = match (row.get(4).cloned().flatten(), row.get(5).cloned().flatten()) {
+            (Some(table), Some(column)) => Some((table, column)),
+            _ => None,
+        };
+        match table.constraints.last_mut() {
+            Some(constraint) if constraint.name == name => {
+                constraint.columns.push(column);
+                if let (Some(references), Some((_table, column))) =
+                    (&mut constraint.references, referenced)
+                {
+                    references.columns.push(column);
+                }
+            }
+            _ => table.constraints.push(Constraint {
+                name,
+                kind: required(row, 2)?,
+                columns: vec![column],
+                references: referenced.map(|(table, column)| Reference {
+                    table,
+                    columns: vec![column],
+                }),
+            }),
+        }
+    }
+    for row in &index_rows {
+        let Some(table) = tables.get_mut(&required(row, 0)?) else {
+            continue;
+        };
+        let name = required(row, 1)?;
+        // Primary keys and unique constraints are enforced by an index of
+        // the same name, which the constraint already describes.
+        if table
+            .constraints
+            .iter()
+            .any(|constraint| constraint.name == name && constraint.kind != "FOREIGN KEY")
+        {
+            continue;
+        }
+        let column = required(row, 3)?;
+        match table.indexes.last_mut() {
+            Some(index) if index.name == name => index.columns.push(column),
+            _ => table.indexes.push(Index {
+                name,
+                unique: matches!(required(row, 2)?.as_str(), "1" | "true"),
+                columns: vec![column],
+            }),
+        }
+    }
+    Ok(Schema {
+        tables: tables.into_values().collect(),
+    })
+}
+
+fn column_list(dialect: Dialect, columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| dialect.quote_identifier(column))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Write the schema as DDL that recreates it.
+pub fn ddl_output(dialect: Dialect, schema: &Schema, mut writer: impl Write) -> Result<()> {
+    for table in &schema.tables {
+        let mut lines: Vec<String> = table
+            .columns
+            .iter()
+            .map(|column| {
+                let mut line = format!(
+                    "  {} {}",
+                    dialect.quote_identifier(&column.name),
+                    column.data_type
+                );
+                if !column.nullable {
+                    line += " NOT NULL";
+                }
+                if let Some(default) = &column.default {
+                    line += &format!(" DEFAULT {}", default);
+                }
+                line
+            })
+            .collect();
+        for constraint in &table.constraints {
+            let columns = column_list(dialect, &constraint.columns);
+            match constraint.kind.as_str() {
+                "PRIMARY KEY" => lines.push(format!("  PRIMARY KEY ({})", columns)),
+                "UNIQUE" => lines.push(format!(
+                    "  CONSTRAINT {} UNIQUE ({})",
+                    dialect.quote_identifier(&constraint.name),
+                    columns
+                )),
+                _ => {}
+            }
+        }
+        writeln!(
+            writer,
+            "CREATE TABLE {} (\n{}\n);",
+            dialect.quote_identifier(&table.name),
+            lines.join(",\n")
+        )?;
+        for index in &table.indexes {
+            writeln!(
+                writer,
+                "CREATE {}INDEX {} ON {} ({});",
+                if index.unique { "UNIQUE " } else { "" },
+                dialect.quote_identifier(&index.name),
+                dialect.quote_identifier(&table.name),
+                column_list(dialect, &index.columns)
+            )?;
+        }
+        writeln!(writer)?;
+    }
+    for table in &schema.tables {
+        for constraint in &table.constraints {
+            if let Some(references) = &constraint.references {
+                writeln!(
+                    writer,
+                    "ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({});",
+                    dialect.quote_identifier(&table.name),
+                    dialect.quote_identifier(&constraint.name),
+                    column_list(dialect, &constraint.columns),
+                    dialect.quote_identifier(&references.table),
+                    column_list(dialect, &references.columns)
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn dump(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    json: bool,
+    mut writer: impl Write,
+) -> Result<()> {
+    let schema = introspect(guard, config, target).await?;
+    if json {
+        serde_json::to_writer_pretty(&mut writer, &schema)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    } else {
+        ddl_output(target.dialect(), &schema, writer)
+    }
+}
+
+fn describe_column(column: &Column) -> String {
+    let mut description = column.data_type.clone();
+    if !column.nullable {
+        description += " NOT NULL";
+    }
+    if let Some(default) = &column.default {
+        description += &format!(" DEFAULT {}", default);
+    }
+    description
+}
+
+fn describe_constraint(constraint: &Constraint) -> String {
+    let mut description = format!("{} ({})", constraint.kind, constraint.columns.join(", "));
+    if let Some(references) = &constraint.references {
+        description += &format!(
+            " REFERENCES {} ({})",
+            references.table,
+            references.columns.join(", ")
+        );
+    }
+    description
+}
+
+fn describe_index(index: &Index) -> String {
+    let columns = index.columns.join(", ");
+    if index.unique {
+        format!("UNIQUE ({})", columns)
+    } else {
+        format!("({})", columns)
+    }
+}
+
+#[derive(Default)]
+struct Drift {
+    /// Marker, kind, name and the descriptions in each target.
+    lines: Vec<[String; 5]>,
+    added: usize,
+    removed: usize,
+    changed: usize,
+}
+
+impl Drift {
+    /// Compare named objects of one kind, matching them up by name.
+    fn compare<'a, T: 'a>(
+        &mut self,
+        kind: &str,
+        prefix: &str,
+        objects_a: impl IntoIterator<Item = (&'a str, &'a T)>,
+        objects_b: impl IntoIterator<Item = (&'a str, &'a T)>,
+        describe: impl Fn(&T) -> String,
+    ) {
+        let mut described_b: BTreeMap<&str, String> = objects_b
+            .into_iter()
+            .map(|(name, object)| (name, describe(object)))
+            .collect();
+        let mut lines = Vec::new();
+        for (name, object) in objects_a {
+            let description = describe(object);
+            match described_b.remove(name) {
+                Some(description_b) if description_b == description => {}
+                Some(description_b) => {
+                    self.changed += 1;
+                    lines.push(("~", name, description, description_b));
+                }
+                None => {
+                    self.removed += 1;
+                    lines.push(("-", name, description, String::new()));
+                }
+            }
+        }
+        for (name, description_b) in described_b {
+            self.added += 1;
+            lines.push(("+", name, String::new(), description_b));
+        }
+        for (marker, name, description_a, description_b) in lines {
+            self.lines.push([
+                marker.to_owned(),
+                kind.to_owned(),
+                format!("{}{}", prefix, name),
+                description_a,
+                description_b,
+            ]);
+        }
+    }
+}
+
+fn drift(schema_a: &Schema, schema_b: &Schema) -> Drift {
+    let mut drift = Drift::default();
+    let named_tables = |schema: &'_ Schema| -> BTreeMap<String, Table> {
+        schema
+            .tables
+            .iter()
+            .map(|table| (table.name.clone(), table.clone()))
+            .collect()
+    };
+    let tables_a = named_tables(schema_a);
+    let tables_b = named_tables(schema_b);
+    drift.compare(
+        "table",
+        "",
+        tables_a.iter().map(|(name, table)| (name.as_str(), table)),
+        tables_b.iter().map(|(name, table)| (name.as_str(), table)),
+        |_table| String::new(),
+    );
+    for (name, table_a) in &tables_a {
+        let Some(table_b) = tables_b.get(name) else {
+            continue;
+        };
+        let prefix = format!("{}.", name);
+        drift.compare(
+            "column",
+            &prefix,
+            table_a.columns.iter().map(|c| (c.name.as_str(), c)),
+            table_b.columns.iter().map(|c| (c.name.as_str(), c)),
+            describe_column,
+        );
+        drift.compare(
+            "constraint",
+            &prefix,
+            table_a.constraints.iter().map(|c| (c.name.as_str(), c)),
+            table_b.constraints.iter().map(|c| (c.name.as_str(), c)),
+            describe_constraint,
+        );
+        drift.compare(
+            "index",
+            &prefix,
+            table_a.indexes.iter().map(|i| (i.name.as_str(), i)),
+            table_b.indexes.iter().map(|i| (i.name.as_str(), i)),
+            describe_index,
+        );
+    }
+    drift
+}
+
+pub async fn diff(
+    guard: &Guard,
+    config: &SdkConfig,
+    target_a: &Target,
+    target_b: &Target,
+    writer: impl Write,
+) -> Result<()> {
+    let (schema_a, schema_b) = try_join!(
+        introspect(guard, config, target_a),
+        introspect(guard, config, target_b)
+    )?;
+    let drift = drift(&schema_a, &schema_b);
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(["diff", "kind", "name", "target_a", "target_b"])?;
+    for line in &drift.lines {
+        wtr.write_record(line)?;
+    }
+    wtr.flush()?;
+    if drift.lines.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} added, {} removed, {} changed",
+            drift.added,
+            drift.removed,
+            drift.changed,
+        ))
+    }
+}
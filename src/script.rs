@@ -0,0 +1,47 @@
+use crate::{import::open_input, policy::reads_only, Request};
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::{io::Read, path::PathBuf};
+
+/// Run the statements of a SQL file, in order.
+///
+/// Statements are split on semicolons, and every --param is given to each
+/// of them. Each statement's results are written out in turn, separated by
+/// a blank line. The script stops at the first statement that fails.
+#[derive(Args, Clone, Debug)]
+pub struct ScriptArgs {
+    /// Run consecutive read-only statements, such as report queries, at the
+    /// same time, up to --concurrency at once. Any other statement waits
+    /// for those before it, and runs alone, before those after it.
+    #[clap(long)]
+    pub parallel: bool,
+
+    /// SQL file, or `-` for standard input.
+    pub file: PathBuf,
+}
+
+pub fn read(script_args: &ScriptArgs) -> Result<String> {
+    let path = &script_args.file;
+    let mut script = String::new();
+    open_input(path)?
+        .read_to_string(&mut script)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    Ok(script)
+}
+
+/// Group the statements into those that may run together, in order. With
+/// `parallel`, consecutive read-only statements share a group; otherwise,
+/// and for any statement that may write, each has a group of its own.
+pub fn groups(requests: Vec<Request>, parallel: bool) -> Vec<Vec<Request>> {
+    let mut groups: Vec<Vec<Request>> = Vec::new();
+    let mut joinable = false;
+    for request in requests {
+        let reads_only = parallel && reads_only(&request);
+        match groups.last_mut() {
+            Some(group) if joinable && reads_only => group.push(request),
+            _ => groups.push(vec![request]),
+        }
+        joinable = reads_only;
+    }
+    groups
+}
@@ -0,0 +1,182 @@
+use crate::{csv_output, expect, transform::drop_columns, Style};
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::operation::execute_statement::ExecuteStatementOutput;
+use clap::{Args, Subcommand};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Save query results as snapshots, and check later runs against them.
+///
+/// Snapshots are CSV files, named for the snapshot, in the snapshots
+/// directory. Without a query, the named query of the same name is run.
+/// Sort the results, with --sort-by, when their order is not fixed.
+#[derive(Args, Clone, Debug)]
+pub struct SnapshotArgs {
+    /// Directory holding the snapshots.
+    #[clap(default_value = "snapshots", long)]
+    dir: PathBuf,
+
+    /// Leave this column out, for values that change on every run, such as
+    /// timestamps. May be repeated.
+    #[clap(long = "ignore-column", value_name = "COLUMN")]
+    ignore_columns: Vec<String>,
+
+    /// When checking, treat numbers as equal when they differ by no more
+    /// than this. Without it, cells must match exactly, so `1.0` differs
+    /// from `1`.
+    #[clap(default_value_t = 0.0, long, value_name = "DELTA")]
+    float_tolerance: f64,
+
+    #[clap(subcommand)]
+    pub action: SnapshotAction,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum SnapshotAction {
+    /// Run the query and save its results as the snapshot, replacing any
+    /// saved before.
+    Save {
+        /// Name of the snapshot.
+        name: String,
+
+        /// SQL query.
+        query: Option<String>,
+    },
+
+    /// Run the query and compare its results with the snapshot. Exits with
+    /// an error, after a unified diff, when they differ.
+    Check {
+        /// Name of the snapshot.
+        name: String,
+
+        /// SQL query.
+        query: Option<String>,
+    },
+}
+
+impl SnapshotAction {
+    pub fn name(&self) -> &str {
+        match self {
+            SnapshotAction::Save { name, .. } | SnapshotAction::Check { name, .. } => name,
+        }
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        match self {
+            SnapshotAction::Save { query, .. } | SnapshotAction::Check { query, .. } => {
+                query.as_deref()
+            }
+        }
+    }
+}
+
+/// Whether two cells match: the same text, or numbers close enough.
+/// Whether the cells are the same text, or, given a tolerance, numbers
+/// that close.
+fn cells_match(expected: &str, actual: &str, tolerance: f64) -> bool {
+    expected == actual
+        || tolerance > 0.0
+            && match (expected.parse::<f64>(), actual.parse::<f64>()) {
+                (Ok(expected), Ok(actual)) => (expected - actual).abs() <= tolerance,
+                _ => false,
+            }
+}
+
+fn matches(expected: &str, actual: &str, tolerance: f64) -> Result<bool> {
+    let records = |text: &str| -> Result<Vec<csv::StringRecord>> {
+        Ok(csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes())
+            .records()
+            .collect::<Result<_, _>>()?)
+    };
+    let (expected, actual) = (records(expected)?, records(actual)?);
+    Ok(expected.len() == actual.len()
+        && expected.iter().zip(&actual).all(|(expected, actual)| {
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual)
+                    .all(|(expected, actual)| cells_match(expected, actual, tolerance))
+        }))
+}
+
+/// Where the snapshot is kept. Names are file names, so that a snapshot
+/// cannot be written outside the directory.
+fn snapshot_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(anyhow!(
+            "Snapshot name \"{}\" must not be empty, or contain \"/\", \"\\\" or \"..\"",
+            name
+        ));
+    }
+    Ok(dir.join(format!("{}.csv", name)))
+}
+
+/// Save the results, or check them against the saved snapshot.
+pub fn snapshot(snapshot_args: &SnapshotArgs, mut output: ExecuteStatementOutput) -> Result<()> {
+    drop_columns(&mut output, &snapshot_args.ignore_columns)?;
+    let mut csv = Vec::new();
+    csv_output(&output, Style::plain(), &mut csv)?;
+    let csv = String::from_utf8(csv)?;
+    let path = snapshot_path(&snapshot_args.dir, snapshot_args.action.name())?;
+    match &snapshot_args.action {
+        SnapshotAction::Save { .. } => {
+            fs::create_dir_all(&snapshot_args.dir)
+                .map_err(|e| anyhow!("Failed to create {}: {}", snapshot_args.dir.display(), e))?;
+            fs::write(&path, csv)
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+            println!("saved: {}", path.display());
+            Ok(())
+        }
+        SnapshotAction::Check { .. } => {
+            let expected = fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+            if matches(&expected, &csv, snapshot_args.float_tolerance)? {
+                return Ok(());
+            }
+            expect::print_diff(&path, &expected, &csv);
+            Err(anyhow!("Results differ from {}", path.display()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_numbers_only_within_a_tolerance() {
+        assert!(cells_match("1.0", "1.0", 0.0));
+        assert!(!cells_match("1.0", "1", 0.0));
+        assert!(!cells_match("1e3", "1000", 0.0));
+        assert!(!cells_match("inf", "infinity", 0.0));
+        assert!(cells_match("1.0", "1", 0.01));
+        assert!(cells_match("0.1", "0.105", 0.01));
+        assert!(!cells_match("0.1", "0.2", 0.01));
+        assert!(!cells_match("a", "b", 0.01));
+    }
+
+    #[test]
+    fn compares_rows_and_cells() {
+        assert!(matches("id,x\n1,2.0\n", "id,x\n1,2.0\n", 0.0).unwrap());
+        assert!(!matches("id,x\n1,2.0\n", "id,x\n1,2\n", 0.0).unwrap());
+        assert!(matches("id,x\n1,2.0\n", "id,x\n1,2\n", 0.5).unwrap());
+        assert!(!matches("id,x\n1,2\n", "id,x\n1,2\n2,3\n", 0.0).unwrap());
+    }
+
+    #[test]
+    fn keeps_snapshots_in_their_directory() {
+        let dir = Path::new("snapshots");
+        assert_eq!(
+            snapshot_path(dir, "orders").unwrap(),
+            Path::new("snapshots/orders.csv")
+        );
+        for name in ["", "../orders", "a/b", "a\\b", ".."] {
+            assert!(snapshot_path(dir, name).is_err(), "{}", name);
+        }
+    }
+}
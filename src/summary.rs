@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{fs, path::Path, time::Duration};
+
+/// What a run of a query did, as told on stderr afterwards, and with
+/// --summary-json.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    /// Rows returned or, by statements returning none, updated.
+    pub rows: u64,
+    /// Whether any statement returned rows.
+    pub returned_rows: bool,
+    pub records_updated: i64,
+    /// The cluster, or instance, ARN and the database of each statement.
+    pub targets: Vec<(String, Option<String>)>,
+    /// Of each statement the Data API ran, rather than the cache answered.
+    pub request_ids: Vec<String>,
+}
+
+/// The --summary-json document.
+#[derive(Serialize)]
+struct SummaryJson<'a> {
+    rows: u64,
+    records_updated: i64,
+    duration_seconds: f64,
+    targets: Vec<TargetJson<'a>>,
+    request_ids: &'a [String],
+}
+
+#[derive(Serialize)]
+struct TargetJson<'a> {
+    cluster: &'a str,
+    database: Option<&'a str>,
+}
+
+/// Like `cluster orders-prod`, from the ARN of a cluster or an instance.
+fn resource(arn: &str) -> String {
+    match arn.rsplit(':').collect::<Vec<_>>()[..] {
+        [name, "db", ..] => format!("instance {}", name),
+        [name, ..] => format!("cluster {}", name),
+        [] => arn.to_owned(),
+    }
+}
+
+impl Summary {
+    /// One line, like `42 rows in 1.37s from cluster orders-prod / db
+    /// analytics`.
+    pub fn line(&self, duration: Duration) -> String {
+        let rows = match (self.returned_rows, self.rows) {
+            (true, 1) => "1 row".to_owned(),
+            (true, rows) => format!("{} rows", rows),
+            (false, _) if self.records_updated == 1 => "1 row updated".to_owned(),
+            (false, _) => format!("{} rows updated", self.records_updated),
+        };
+        // --foreach runs against the same target several times.
+        let mut targets: Vec<&(String, Option<String>)> = Vec::new();
+        for target in &self.targets {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        let from = match &targets[..] {
+            [(arn, Some(database))] => format!(" from {} / db {}", resource(arn), database),
+            [(arn, None)] => format!(" from {}", resource(arn)),
+            [] => String::new(),
+            [(arn, _database), rest @ ..] if rest.iter().all(|(other, _database)| other == arn) => {
+                format!(" from {} / {} databases", resource(arn), rest.len() + 1)
+            }
+            targets => format!(" from {} targets", targets.len()),
+        };
+        format!("{} in {:.2}s{}", rows, duration.as_secs_f64(), from)
+    }
+
+    /// Write the summary to the path, as JSON.
+    pub fn write_json(&self, path: &Path, duration: Duration) -> Result<()> {
+        let summary = SummaryJson {
+            rows: self.rows,
+            records_updated: self.records_updated,
+            duration_seconds: duration.as_secs_f64(),
+            targets: self
+                .targets
+                .iter()
+                .map(|(cluster, database)| TargetJson {
+                    cluster,
+                    database: database.as_deref(),
+                })
+                .collect(),
+            request_ids: &self.request_ids,
+        };
+        let mut json = serde_json::to_vec_pretty(&summary)?;
+        json.push(b'\n');
+        fs::write(path, json).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+}
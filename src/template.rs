@@ -0,0 +1,158 @@
+use crate::dialect::Dialect;
+use anyhow::{anyhow, Result};
+
+/// Parse a `--var name=value` argument.
+pub fn parse_var(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_owned(), value.to_owned())),
+        _ => Err(anyhow!("Variable \"{}\" is not of the form name=value", s)),
+    }
+}
+
+/// Substitute `{{name}}` with the value of `name` quoted as a string literal,
+/// and `{{name|ident}}` with the value quoted as an identifier.
+/// Every referenced variable must be defined.
+pub fn render(sql: &str, vars: &[(String, String)], dialect: Dialect) -> Result<String> {
+    let mut rendered = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let end = after_open
+            .find("}}")
+            .ok_or_else(|| anyhow!("Unclosed \"{{{{\" in SQL template"))?;
+        let (name, filter) = match after_open[..end].split_once('|') {
+            Some((name, filter)) => (name.trim(), filter.trim()),
+            None => (after_open[..end].trim(), "literal"),
+        };
+        let value = vars
+            .iter()
+            .rev()
+            .find(|(var_name, _)| var_name == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| anyhow!("Template variable \"{}\" is not defined", name))?;
+        match filter {
+            "literal" => rendered.push_str(&dialect.quote_literal(value)),
+            "ident" => rendered.push_str(&dialect.quote_identifier(value)),
+            _ => {
+                return Err(anyhow!(
+                    "Unknown template filter \"{}\", expected one of {:?}",
+                    filter,
+                    ["literal", "ident"],
+                ))
+            }
+        }
+        rest = &after_open[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Expand `${VAR}` references from the environment. Undefined (or non-UTF-8)
+/// variables are an error, rather than silently becoming empty.
+pub fn substitute_env(sql: &str) -> Result<String> {
+    let mut substituted = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(start) = rest.find("${") {
+        substituted.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let end = after_open
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed \"${{\" in SQL"))?;
+        let name = &after_open[..end];
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!("Invalid environment variable name \"{}\"", name));
+        }
+        let value =
+            std::env::var(name).map_err(|e| anyhow!("Environment variable \"{}\": {}", name, e))?;
+        substituted.push_str(&value);
+        rest = &after_open[end + 1..];
+    }
+    substituted.push_str(rest);
+    Ok(substituted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn quotes_literals_and_identifiers() {
+        let vars = vars(&[("name", "O'Brien"), ("table", "my \"odd\" `table`")]);
+        assert_eq!(
+            render(
+                "select * from {{table|ident}} where name = {{ name }}",
+                &vars,
+                Dialect::Postgres
+            )
+            .unwrap(),
+            r#"select * from "my ""odd"" `table`" where name = 'O''Brien'"#
+        );
+        assert_eq!(
+            render(
+                "select * from {{table|ident}} where name = {{name|literal}}",
+                &vars,
+                Dialect::MySql
+            )
+            .unwrap(),
+            r#"select * from `my "odd" ``table``` where name = 'O''Brien'"#
+        );
+    }
+
+    #[test]
+    fn escapes_backslashes_for_mysql_only() {
+        let vars = vars(&[("path", r"C:\temp\'")]);
+        assert_eq!(
+            render("{{path}}", &vars, Dialect::MySql).unwrap(),
+            r"'C:\\temp\\'''"
+        );
+        assert_eq!(
+            render("{{path}}", &vars, Dialect::Postgres).unwrap(),
+            r"'C:\temp\'''"
+        );
+    }
+
+    #[test]
+    fn uses_the_last_definition() {
+        let vars = vars(&[("id", "1"), ("id", "2")]);
+        assert_eq!(
+            render("select {{id}}", &vars, Dialect::Postgres).unwrap(),
+            "select '2'"
+        );
+    }
+
+    #[test]
+    fn refuses_bad_templates() {
+        let vars = vars(&[("id", "1")]);
+        let error = |sql| {
+            render(sql, &vars, Dialect::Postgres)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("select {{other}}"),
+            "Template variable \"other\" is not defined"
+        );
+        assert_eq!(
+            error("select {{id|raw}}"),
+            "Unknown template filter \"raw\", expected one of [\"literal\", \"ident\"]"
+        );
+        assert_eq!(error("select {{id"), "Unclosed \"{{\" in SQL template");
+    }
+
+    #[test]
+    fn parses_vars() {
+        assert_eq!(
+            parse_var("a=b=c").unwrap(),
+            ("a".to_owned(), "b=c".to_owned())
+        );
+        assert!(parse_var("=b").is_err());
+        assert!(parse_var("ab").is_err());
+    }
+}
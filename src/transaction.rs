@@ -0,0 +1,94 @@
+use crate::{dialect::Dialect, AwsClient, Client, Request, Target};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use clap::ValueEnum;
+use tracing::info;
+
+/// How isolated a transaction is from others running at the same time.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Isolation {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl Isolation {
+    /// The statement setting it, as the first statement of a PostgreSQL
+    /// transaction. MySQL only takes it before the transaction begins.
+    fn sql(self) -> &'static str {
+        match self {
+            Isolation::ReadCommitted => "SET TRANSACTION ISOLATION LEVEL READ COMMITTED",
+            Isolation::RepeatableRead => "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ",
+            Isolation::Serializable => "SET TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+        }
+    }
+}
+
+/// A Data API transaction. Statements join it by passing `id()` along.
+/// Transactions left open are rolled back by the Data API after a few
+/// minutes, so always `commit` or `rollback`.
+pub struct Transaction {
+    client: AwsClient,
+    target: Target,
+    id: String,
+}
+
+impl Transaction {
+    /// Begin a transaction, at the isolation level when given, or else the
+    /// database's default. The Data API begins MySQL transactions itself,
+    /// too late to set their level, so an isolation level needs PostgreSQL.
+    pub async fn begin(
+        config: &SdkConfig,
+        target: &Target,
+        isolation: Option<Isolation>,
+    ) -> Result<Transaction> {
+        if isolation.is_some() && target.dialect() == Dialect::MySql {
+            return Err(anyhow!(
+                "--isolation can only be used with PostgreSQL, {} is {}",
+                target.resource_arn,
+                target.engine.as_deref().unwrap_or("MySQL")
+            ));
+        }
+        let client = AwsClient::new(config);
+        let id = client.begin_transaction(target.clone()).await?;
+        info!("Began transaction {}", id);
+        let transaction = Transaction {
+            client,
+            target: target.clone(),
+            id,
+        };
+        if let Some(isolation) = isolation {
+            if let Err(e) = transaction.set_isolation(isolation).await {
+                let _rollback = transaction.rollback().await;
+                return Err(e);
+            }
+        }
+        Ok(transaction)
+    }
+
+    async fn set_isolation(&self, isolation: Isolation) -> Result<()> {
+        info!("{}", isolation.sql());
+        let request = Request {
+            target: self.target.clone(),
+            params: Vec::new(),
+            sql: isolation.sql().to_owned(),
+        };
+        self.client
+            .execute_statement(request, Some(self.id.clone()))
+            .await
+            .map_err(|e| anyhow!("Failed to set the transaction isolation level: {}", e))?;
+        Ok(())
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub async fn commit(self) -> Result<()> {
+        self.client.commit_transaction(self.target, self.id).await
+    }
+
+    pub async fn rollback(self) -> Result<()> {
+        self.client.rollback_transaction(self.target, self.id).await
+    }
+}
@@ -0,0 +1,495 @@
+use crate::{
+    blobs, format_header, format_value,
+    geometry::{self, Geometry},
+    import::json_field,
+    is_supported, pg_array, Format, MyArgs,
+};
+use anyhow::{anyhow, Result};
+use aws_sdk_rdsdata::{
+    operation::execute_statement::ExecuteStatementOutput,
+    types::{ColumnMetadata, Field},
+};
+use clap::ValueEnum;
+use rand::{seq::index, RngExt};
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, collections::HashSet, str::FromStr};
+
+/// A `--sort-by column[:desc]` key.
+#[derive(Clone, Debug)]
+pub struct SortKey {
+    column: String,
+    descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (column, descending) = match s.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            Some((_column, order)) => {
+                return Err(anyhow!(
+                    "Unknown sort order \"{}\", expected one of {:?}",
+                    order,
+                    ["asc", "desc"],
+                ))
+            }
+            None => (s, false),
+        };
+        if column.is_empty() {
+            return Err(anyhow!("Sort key \"{}\" names no column", s));
+        }
+        Ok(SortKey {
+            column: column.to_owned(),
+            descending,
+        })
+    }
+}
+
+/// Fail on the first value this tool can't write, rather than writing it
+/// as `UNKNOWN`, or as `null` in JSON, which has no place for arrays and
+/// blobs the Data API returns.
+fn check_supported(output: &ExecuteStatementOutput, json: bool) -> Result<()> {
+    let header: Vec<&str> = format_header(output).collect();
+    for (index, record) in output.records.iter().flatten().enumerate() {
+        for (field, column) in record.iter().zip(&header) {
+            let lost = match field {
+                Field::ArrayValue(_) if json => Some("an array value, which JSON output"),
+                Field::BlobValue(_) if json => Some("a blob value, which JSON output"),
+                field if !is_supported(field) => Some("a value of a kind this tool"),
+                _ => None,
+            };
+            if let Some(lost) = lost {
+                return Err(anyhow!(
+                    "Column \"{}\" of row {} has {} can't write",
+                    column,
+                    index + 1,
+                    lost
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How to tell apart columns with the same label.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum DedupeColumns {
+    /// Relabel repeats of `name` as `name_2`, `name_3` and so on.
+    Suffix,
+}
+
+/// Relabel each repeated label with the first free suffix, so a `name_2`
+/// in the results is never repeated either.
+fn dedupe_columns(output: &mut ExecuteStatementOutput) {
+    let labels: Vec<String> = format_header(output).map(str::to_owned).collect();
+    let mut used: HashSet<String> = labels.iter().cloned().collect();
+    let mut seen = HashSet::new();
+    let mut metadata = output.column_metadata.take().unwrap_or_default();
+    for (column, label) in metadata.iter_mut().zip(labels) {
+        if seen.insert(label.clone()) {
+            continue;
+        }
+        let relabel = (2..)
+            .map(|suffix| format!("{}_{}", label, suffix))
+            .find(|relabel| !used.contains(relabel))
+            .expect("some suffix is free");
+        used.insert(relabel.clone());
+        column.label = Some(relabel);
+    }
+    output.column_metadata = Some(metadata);
+}
+
+/// Parse a `--rename old=new`.
+pub fn parse_rename(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+            Ok((old.to_owned(), new.to_owned()))
+        }
+        _ => Err(anyhow!("Rename \"{}\" is not of the form old=new", s)),
+    }
+}
+
+/// Give the columns new names, as their labels are what output uses.
+fn rename(output: &mut ExecuteStatementOutput, renames: &[(String, String)]) -> Result<()> {
+    let old: Vec<&str> = renames.iter().map(|(old, _new)| old.as_str()).collect();
+    let indexes = column_indexes(output, &old)?;
+    let mut metadata = output.column_metadata.take().unwrap_or_default();
+    for (index, (_old, new)) in indexes.into_iter().zip(renames) {
+        metadata[index].label = Some(new.clone());
+    }
+    output.column_metadata = Some(metadata);
+    Ok(())
+}
+
+/// Leave out the named columns, and their values.
+pub fn drop_columns(output: &mut ExecuteStatementOutput, columns: &[String]) -> Result<()> {
+    let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+    let dropped: HashSet<usize> = column_indexes(output, &columns)?.into_iter().collect();
+    let kept = |index: &usize| !dropped.contains(index);
+    if let Some(metadata) = output.column_metadata.take() {
+        output.column_metadata = Some(
+            metadata
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _column)| kept(index))
+                .map(|(_index, column)| column)
+                .collect(),
+        );
+    }
+    for record in output.records.iter_mut().flatten() {
+        *record = record
+            .drain(..)
+            .enumerate()
+            .filter(|(index, _value)| kept(index))
+            .map(|(_index, value)| value)
+            .collect();
+    }
+    Ok(())
+}
+
+/// Parse a `--sample` fraction, between 0 and 1.
+pub fn parse_fraction(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(anyhow!(
+            "Sample \"{}\" is not a fraction between 0 and 1",
+            s
+        )),
+    }
+}
+
+/// Keep each row with this probability, in order.
+fn sample_fraction(output: &mut ExecuteStatementOutput, fraction: f64) {
+    let mut rng = rand::rng();
+    if let Some(records) = &mut output.records {
+        records.retain(|_record| rng.random_bool(fraction));
+    }
+}
+
+/// Keep this many rows, chosen at random, in order.
+fn sample_count(output: &mut ExecuteStatementOutput, count: usize) {
+    let Some(records) = output.records.take() else {
+        return;
+    };
+    let mut indexes =
+        index::sample(&mut rand::rng(), records.len(), count.min(records.len())).into_vec();
+    indexes.sort_unstable();
+    let mut indexes = indexes.into_iter().peekable();
+    let sampled = records
+        .into_iter()
+        .enumerate()
+        .filter(|(row, _record)| indexes.next_if_eq(row).is_some())
+        .map(|(_row, record)| record)
+        .collect();
+    output.records = Some(sampled);
+}
+
+/// The index of each named column.
+pub fn column_indexes(output: &ExecuteStatementOutput, columns: &[&str]) -> Result<Vec<usize>> {
+    let header: Vec<&str> = format_header(output).collect();
+    columns
+        .iter()
+        .map(|column| {
+            header
+                .iter()
+                .position(|name| name == column)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "No column matched \"{}\", available columns are {:?}",
+                        column,
+                        header
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Order values by type: numbers compare as numbers, whether long or
+/// double.
+fn compare_fields(a: &Field, b: &Field) -> Ordering {
+    fn number(field: &Field) -> Option<f64> {
+        match field {
+            Field::LongValue(long) => Some(*long as f64),
+            Field::DoubleValue(double) => Some(*double),
+            _ => None,
+        }
+    }
+    match (a, b) {
+        (Field::LongValue(a), Field::LongValue(b)) => a.cmp(b),
+        (Field::StringValue(a), Field::StringValue(b)) => a.cmp(b),
+        (Field::BooleanValue(a), Field::BooleanValue(b)) => a.cmp(b),
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => format_value(a).cmp(&format_value(b)),
+        },
+    }
+}
+
+/// Order by one key, with nulls last whichever the direction.
+fn compare_by_key(key: &SortKey, a: &Field, b: &Field) -> Ordering {
+    match (a, b) {
+        (Field::IsNull(_), Field::IsNull(_)) => Ordering::Equal,
+        (Field::IsNull(_), _) => Ordering::Greater,
+        (_, Field::IsNull(_)) => Ordering::Less,
+        _ if key.descending => compare_fields(b, a),
+        _ => compare_fields(a, b),
+    }
+}
+
+/// Sort the rows by each key in turn, keeping the order of ties.
+fn sort_by(output: &mut ExecuteStatementOutput, keys: &[SortKey]) -> Result<()> {
+    let columns: Vec<&str> = keys.iter().map(|key| key.column.as_str()).collect();
+    let indexes = column_indexes(output, &columns)?;
+    if let Some(records) = &mut output.records {
+        records.sort_by(|a, b| {
+            keys.iter()
+                .zip(&indexes)
+                .map(|(key, index)| compare_by_key(key, &a[*index], &b[*index]))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+    Ok(())
+}
+
+/// Drop rows repeating an earlier row, or its values in the given columns.
+fn distinct(output: &mut ExecuteStatementOutput, columns: &[String]) -> Result<()> {
+    let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+    let indexes = column_indexes(output, &columns)?;
+    if let Some(records) = &mut output.records {
+        let mut seen = HashSet::new();
+        records.retain(|record| {
+            // Debug output tells a null from the string "NULL", and 1 from 1.0.
+            let key: Vec<String> = if indexes.is_empty() {
+                record.iter().map(|field| format!("{:?}", field)).collect()
+            } else {
+                indexes
+                    .iter()
+                    .map(|index| format!("{:?}", record[*index]))
+                    .collect()
+            };
+            seen.insert(key)
+        });
+    }
+    Ok(())
+}
+
+/// The JSON object a string value holds, if it holds one.
+fn json_object(field: &Field) -> Option<Map<String, Value>> {
+    match field {
+        Field::StringValue(string) => match serde_json::from_str(string) {
+            Ok(Value::Object(object)) => Some(object),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A type name for a column of values like this one.
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_i64() => "bigint",
+        Value::Number(_) => "double",
+        Value::String(_) => "varchar",
+        _ => "json",
+    }
+}
+
+/// Expand each column whose values are all JSON objects, or NULL, into a
+/// column for each of their top-level keys, named like `meta.plan`. Keys
+/// missing from a row are NULL, and nested values stay JSON text.
+fn flatten(output: &mut ExecuteStatementOutput) {
+    let header: Vec<String> = format_header(output).map(str::to_owned).collect();
+    let records = output.records.take().unwrap_or_default();
+    let objects: Vec<Vec<Option<Map<String, Value>>>> = records
+        .iter()
+        .map(|record| record.iter().map(json_object).collect())
+        .collect();
+    // The keys of each flattened column, with the type of their first
+    // non-null value.
+    let keys: Vec<Option<Vec<(String, &'static str)>>> = (0..header.len())
+        .map(|index| {
+            let flattened = records.iter().zip(&objects).all(|(record, objects)| {
+                matches!(record.get(index), Some(Field::IsNull(_))) || objects[index].is_some()
+            }) && objects.iter().any(|objects| objects[index].is_some());
+            flattened.then(|| {
+                let mut keys: Vec<(String, &'static str)> = Vec::new();
+                for object in objects.iter().filter_map(|objects| objects[index].as_ref()) {
+                    for (key, value) in object {
+                        match keys.iter_mut().find(|(known, _type_name)| known == key) {
+                            Some((_known, type_name @ "null")) => {
+                                *type_name = json_type_name(value)
+                            }
+                            Some(_known) => {}
+                            None if value.is_null() => keys.push((key.clone(), "null")),
+                            None => keys.push((key.clone(), json_type_name(value))),
+                        }
+                    }
+                }
+                keys
+            })
+        })
+        .collect();
+    if let Some(metadata) = output.column_metadata.take() {
+        output.column_metadata = Some(
+            metadata
+                .into_iter()
+                .zip(header.iter().zip(&keys))
+                .flat_map(|(column, (name, keys))| match keys {
+                    None => vec![column],
+                    Some(keys) => keys
+                        .iter()
+                        .map(|(key, type_name)| {
+                            let name = format!("{}.{}", name, key);
+                            ColumnMetadata::builder()
+                                .name(&name)
+                                .label(name)
+                                .type_name(*type_name)
+                                .nullable(1)
+                                .build()
+                        })
+                        .collect(),
+                })
+                .collect(),
+        );
+    }
+    output.records = Some(
+        records
+            .into_iter()
+            .zip(objects)
+            .map(|(record, mut objects)| {
+                record
+                    .into_iter()
+                    .zip(objects.iter_mut().zip(&keys))
+                    .flat_map(|(field, (object, keys))| match keys {
+                        None => vec![field],
+                        Some(keys) => {
+                            let mut object = object.take().unwrap_or_default();
+                            keys.iter()
+                                .map(|(key, _type_name)| {
+                                    json_field(object.remove(key).unwrap_or(Value::Null))
+                                })
+                                .collect()
+                        }
+                    })
+                    .collect()
+            })
+            .collect(),
+    );
+}
+
+/// Rewrite the Postgres array text of each array column as JSON text.
+fn parse_arrays(output: &mut ExecuteStatementOutput) -> Result<()> {
+    let element_types: Vec<Option<String>> = output
+        .column_metadata
+        .iter()
+        .flatten()
+        .map(|column| {
+            column
+                .type_name
+                .as_deref()
+                .and_then(pg_array::element_type)
+                .map(str::to_lowercase)
+        })
+        .collect();
+    for record in output.records.iter_mut().flatten() {
+        for (field, element_type) in record.iter_mut().zip(&element_types) {
+            if let (Field::StringValue(text), Some(element_type)) = (&*field, element_type) {
+                *field = Field::StringValue(pg_array::parse(text, element_type)?.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rewrite the hex WKB of each geometry column as WKT, or GeoJSON text.
+fn convert_geometries(output: &mut ExecuteStatementOutput, to: Geometry) -> Result<()> {
+    let geometries: Vec<bool> = output
+        .column_metadata
+        .iter()
+        .flatten()
+        .map(|column| geometry::is_geometry(column.type_name.as_deref().unwrap_or_default()))
+        .collect();
+    for record in output.records.iter_mut().flatten() {
+        for (field, is_geometry) in record.iter_mut().zip(&geometries) {
+            if let (Field::StringValue(hex), true) = (&*field, is_geometry) {
+                *field = Field::StringValue(geometry::convert(hex, to)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a column of this type holds exact decimals, which arrive as
+/// text.
+fn is_decimal(type_name: &str) -> bool {
+    ["numeric", "decimal"].contains(&type_name.to_lowercase().as_str())
+}
+
+/// Which columns hold JSON text to write as nested JSON: those named by
+/// --parse-json-columns, with --parse-json those of type `json` or `jsonb`,
+/// with --parse-arrays the arrays, with `--geometry geojson` the
+/// geometries, and with --numeric-decimals the decimals.
+pub fn json_columns(args: &MyArgs, output: &ExecuteStatementOutput) -> Result<Vec<bool>> {
+    let mut json_columns: Vec<bool> = output
+        .column_metadata
+        .iter()
+        .flatten()
+        .map(|column| {
+            let type_name = column.type_name.as_deref().unwrap_or_default();
+            (args.parse_json && ["json", "jsonb"].contains(&type_name.to_lowercase().as_str()))
+                || (args.parse_arrays && pg_array::element_type(type_name).is_some())
+                || (args.geometry == Some(Geometry::Geojson) && geometry::is_geometry(type_name))
+                || (args.numeric_decimals && is_decimal(type_name))
+        })
+        .collect();
+    let columns: Vec<&str> = args.parse_json_columns.iter().map(String::as_str).collect();
+    for index in column_indexes(output, &columns)? {
+        json_columns[index] = true;
+    }
+    Ok(json_columns)
+}
+
+/// Reshape a result, as asked on the command line, before it is written out.
+pub fn apply(args: &MyArgs, output: &mut ExecuteStatementOutput) -> Result<()> {
+    if !args.rename.is_empty() {
+        rename(output, &args.rename)?;
+    }
+    if let Some(DedupeColumns::Suffix) = args.dedupe_columns {
+        dedupe_columns(output);
+    }
+    if args.parse_arrays {
+        parse_arrays(output)?;
+    }
+    if let Some(to) = args.geometry {
+        convert_geometries(output, to)?;
+    }
+    if args.flatten {
+        flatten(output);
+    }
+    if args.distinct || !args.distinct_on.is_empty() {
+        distinct(output, &args.distinct_on)?;
+    }
+    if let Some(fraction) = args.sample {
+        sample_fraction(output, fraction);
+    }
+    if let Some(count) = args.sample_n {
+        sample_count(output, count);
+    }
+    if !args.sort_by.is_empty() {
+        sort_by(output, &args.sort_by)?;
+    }
+    if let Some(dir) = &args.blob_dir {
+        blobs::extract(output, dir, args.blob_key.as_deref())?;
+    }
+    if args.strict {
+        check_supported(
+            output,
+            [Format::Json, Format::Ndjson].contains(&args.format),
+        )?;
+    }
+    Ok(())
+}
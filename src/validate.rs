@@ -0,0 +1,207 @@
+use crate::{dialect::Dialect, format_value, guard::Guard, schema::scope, Request, Target};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_rdsdata::types::Field;
+use serde_json::Value;
+use time::{format_description, Date, Time};
+
+/// A column of the table being imported into, from `information_schema`.
+pub struct TableColumn {
+    pub name: String,
+    /// The type without its size, such as `integer` or `character varying`.
+    pub data_type: String,
+    pub nullable: bool,
+    /// Whether the database fills the column in when it is left out.
+    pub has_default: bool,
+    pub max_length: Option<usize>,
+}
+
+fn columns_sql(dialect: Dialect, scope: &str, table: &str) -> String {
+    let has_default = match dialect {
+        Dialect::MySql => {
+            "column_default IS NOT NULL OR extra LIKE '%auto_increment%' \
+             OR extra LIKE '%GENERATED%'"
+        }
+        Dialect::Postgres => {
+            "column_default IS NOT NULL OR is_identity = 'YES' OR is_generated = 'ALWAYS'"
+        }
+    };
+    format!(
+        "SELECT column_name, data_type, is_nullable, {}, character_maximum_length \
+         FROM information_schema.columns WHERE table_schema = {} AND table_name = {} \
+         ORDER BY ordinal_position",
+        has_default,
+        scope,
+        dialect.quote_literal(table)
+    )
+}
+
+/// The table's columns, in order. The table may be qualified by schema.
+pub async fn table_columns(
+    guard: &Guard,
+    config: &SdkConfig,
+    target: &Target,
+    table: &str,
+) -> Result<Vec<TableColumn>> {
+    let dialect = target.dialect();
+    let (scope, name) = match table.rsplit_once('.') {
+        Some((schema, name)) => (dialect.quote_literal(schema), name),
+        None => (scope(target), table),
+    };
+    let request = Request {
+        target: target.clone(),
+        params: Vec::new(),
+        sql: columns_sql(dialect, &scope, name),
+    };
+    let output = guard.execute(config, request).await?;
+    let columns: Vec<TableColumn> = output
+        .records
+        .unwrap_or_default()
+        .iter()
+        .map(|record| {
+            let text = |index: usize| record.get(index).map(format_value).unwrap_or_default();
+            TableColumn {
+                name: text(0),
+                data_type: text(1).to_lowercase(),
+                nullable: text(2) == "YES",
+                has_default: matches!(text(3).as_str(), "true" | "1"),
+                max_length: text(4).parse().ok(),
+            }
+        })
+        .collect();
+    if columns.is_empty() {
+        return Err(anyhow!("No table named \"{}\" found", table));
+    }
+    Ok(columns)
+}
+
+fn is_date(text: &str) -> bool {
+    format_description::parse_borrowed::<2>("[year]-[month]-[day]")
+        .is_ok_and(|format| Date::parse(text, &format).is_ok())
+}
+
+/// Hours, minutes and seconds, ignoring any fraction or time zone.
+fn is_time(text: &str) -> bool {
+    format_description::parse_borrowed::<2>("[hour]:[minute]:[second]").is_ok_and(|format| {
+        text.get(..8)
+            .is_some_and(|hms| Time::parse(hms, &format).is_ok())
+    })
+}
+
+fn is_uuid(text: &str) -> bool {
+    let groups: Vec<&str> = text.split('-').collect();
+    groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|group| group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Why the value does not suit the column, when it does not. Types not
+/// listed here are left for the database to judge.
+fn mismatch(column: &TableColumn, value: &Field) -> Option<String> {
+    let text = match value {
+        Field::IsNull(_) if column.nullable => return None,
+        Field::IsNull(_) => return Some("NULL, but the column is NOT NULL".to_owned()),
+        Field::StringValue(string) => string.clone(),
+        value => format_value(value),
+    };
+    let suits = match column.data_type.as_str() {
+        "smallint" | "integer" | "bigint" | "int" | "tinyint" | "mediumint" => {
+            text.parse::<i64>().is_ok()
+        }
+        "numeric" | "decimal" | "real" | "double precision" | "double" | "float" => {
+            text.parse::<f64>().is_ok()
+        }
+        "boolean" => matches!(
+            text.to_lowercase().as_str(),
+            "true" | "false" | "t" | "f" | "yes" | "no" | "on" | "off" | "1" | "0"
+        ),
+        "date" => is_date(&text),
+        "time" | "time without time zone" | "time with time zone" => is_time(&text),
+        data_type if data_type.starts_with("timestamp") || data_type == "datetime" => {
+            text.get(..10).is_some_and(is_date)
+                && text
+                    .get(10..11)
+                    .is_some_and(|separator| separator == " " || separator == "T")
+                && text.get(11..).is_some_and(is_time)
+        }
+        "json" | "jsonb" => serde_json::from_str::<Value>(&text).is_ok(),
+        "uuid" => is_uuid(&text),
+        _ => true,
+    };
+    if !suits {
+        return Some(format!("\"{}\" is not a valid {}", text, column.data_type));
+    }
+    match column.max_length {
+        Some(max_length) if text.chars().count() > max_length => Some(format!(
+            "\"{}\" is longer than {} characters",
+            text, max_length
+        )),
+        _ => None,
+    }
+}
+
+/// Check each row against the table's columns, reporting every value that
+/// does not suit its column on standard error, by row and column. Returns
+/// the number of rows checked.
+pub fn check(
+    table: &str,
+    table_columns: &[TableColumn],
+    columns: &[String],
+    rows: impl Iterator<Item = Result<Vec<Field>>>,
+) -> Result<usize> {
+    let available: Vec<&str> = table_columns
+        .iter()
+        .map(|column| column.name.as_str())
+        .collect();
+    let matched = columns
+        .iter()
+        .map(|name| {
+            table_columns
+                .iter()
+                .find(|column| &column.name == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "No column named \"{}\" in {}, available columns are {:?}",
+                        name,
+                        table,
+                        available
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(missing) = table_columns
+        .iter()
+        .find(|column| !column.nullable && !column.has_default && !columns.contains(&column.name))
+    {
+        return Err(anyhow!(
+            "Column \"{}\" of {} is NOT NULL without a default, but is not in the input",
+            missing.name,
+            table
+        ));
+    }
+    let mut count = 0;
+    let mut mismatches = 0;
+    for (index, row) in rows.enumerate() {
+        for (column, value) in matched.iter().zip(row?.iter()) {
+            if let Some(mismatch) = mismatch(column, value) {
+                eprintln!(
+                    "Row {}, column \"{}\": {}",
+                    index + 1,
+                    column.name,
+                    mismatch
+                );
+                mismatches += 1;
+            }
+        }
+        count += 1;
+    }
+    if mismatches > 0 {
+        return Err(anyhow!(
+            "{} values do not suit the columns of {}",
+            mismatches,
+            table
+        ));
+    }
+    Ok(count)
+}
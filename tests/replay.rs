@@ -0,0 +1,412 @@
+//! Discovery and execution end to end, against recorded AWS responses
+//! replayed through the SDK's `StaticReplayClient`.
+
+use aws_config::{BehaviorVersion, SdkConfig};
+use aws_sdk_rdsdata::config::{Credentials, Region, SharedCredentialsProvider};
+use aws_smithy_http_client::test_util::{ReplayEvent, StaticReplayClient};
+use aws_smithy_types::body::SdkBody;
+use query_rds_data::{
+    client::{AwsClient, Client},
+    format_header, format_rows, get_arns_with, instance_cluster_id_with, Config, Error,
+    QueryExecutor, Request, Target,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+const RDS: &str = "https://rds.us-east-1.amazonaws.com/";
+const SECRETS_MANAGER: &str = "https://secretsmanager.us-east-1.amazonaws.com/";
+const RDS_DATA: &str = "https://rds-data.us-east-1.amazonaws.com/Execute";
+
+fn response_file(name: &str) -> String {
+    let path = format!("{}/tests/responses/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+fn event(uri: &str, request_body: &str, response_name: &str) -> ReplayEvent {
+    ReplayEvent::new(
+        http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .body(SdkBody::from(request_body))
+            .unwrap(),
+        http::Response::builder()
+            .status(200)
+            .body(SdkBody::from(response_file(response_name)))
+            .unwrap(),
+    )
+}
+
+/// A Data API error, the kind named in a header as the service sends it.
+fn error_event(uri: &str, error_type: &str, response_name: &str) -> ReplayEvent {
+    ReplayEvent::new(
+        http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .body(SdkBody::empty())
+            .unwrap(),
+        http::Response::builder()
+            .status(400)
+            .header("x-amzn-ErrorType", error_type)
+            .body(SdkBody::from(response_file(response_name)))
+            .unwrap(),
+    )
+}
+
+fn sdk_config(http_client: &StaticReplayClient) -> SdkConfig {
+    SdkConfig::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(SharedCredentialsProvider::new(Credentials::new(
+            "AKIDEXAMPLE",
+            "secret",
+            None,
+            None,
+            "replay",
+        )))
+        .http_client(http_client.clone())
+        .build()
+}
+
+/// A replay for each service. Clusters and secrets are listed
+/// concurrently, so a single replay would see their pages in no
+/// particular order.
+struct Replay {
+    rds: StaticReplayClient,
+    secrets_manager: StaticReplayClient,
+    rds_data: StaticReplayClient,
+}
+
+impl Replay {
+    /// Two pages each of clusters and secrets, and the given statements.
+    fn new(statements: Vec<ReplayEvent>) -> Replay {
+        Replay {
+            rds: StaticReplayClient::new(vec![
+                event(
+                    RDS,
+                    "Action=DescribeDBClusters&Version=2014-10-31&MaxRecords=100",
+                    "describe_db_clusters_1.xml",
+                ),
+                event(
+                    RDS,
+                    "Action=DescribeDBClusters&Version=2014-10-31&MaxRecords=100&Marker=page-2",
+                    "describe_db_clusters_2.xml",
+                ),
+            ]),
+            secrets_manager: StaticReplayClient::new(vec![
+                event(
+                    SECRETS_MANAGER,
+                    r#"{"MaxResults": 100}"#,
+                    "list_secrets_1.json",
+                ),
+                event(
+                    SECRETS_MANAGER,
+                    r#"{"MaxResults": 100, "NextToken": "page-2"}"#,
+                    "list_secrets_2.json",
+                ),
+            ]),
+            rds_data: StaticReplayClient::new(statements),
+        }
+    }
+
+    fn client(&self) -> AwsClient {
+        AwsClient::from_clients(
+            aws_sdk_rds::Client::new(&sdk_config(&self.rds)),
+            aws_sdk_secretsmanager::Client::new(&sdk_config(&self.secrets_manager)),
+            aws_sdk_rdsdata::Client::new(&sdk_config(&self.rds_data)),
+        )
+    }
+
+    /// Every recorded request was made, as recorded.
+    fn assert_requests_match(&self) {
+        self.rds.relaxed_requests_match();
+        self.secrets_manager.relaxed_requests_match();
+        self.rds_data.relaxed_requests_match();
+    }
+}
+
+fn some(id: &str) -> Option<String> {
+    Some(id.to_owned())
+}
+
+#[tokio::test]
+async fn finds_the_named_cluster_on_a_later_page() {
+    let replay = Replay::new(Vec::new());
+    let arns = get_arns_with(&replay.client(), &some("reporting"), &some("reader"))
+        .await
+        .unwrap();
+    assert_eq!(
+        arns.db_cluster_or_instance_arn,
+        "arn:aws:rds:us-east-1:123456789012:cluster:reporting"
+    );
+    assert_eq!(
+        arns.aws_secret_store_arn,
+        "arn:aws:secretsmanager:us-east-1:123456789012:secret:rds-db-credentials/cluster-REPORTING/reader-YzAbCd"
+    );
+    assert_eq!(arns.engine.as_deref(), Some("aurora-postgresql"));
+    replay.assert_requests_match();
+}
+
+#[tokio::test]
+async fn picks_the_only_user_of_a_cluster() {
+    let replay = Replay::new(Vec::new());
+    let arns = get_arns_with(&replay.client(), &some("inventory"), &None)
+        .await
+        .unwrap();
+    assert_eq!(
+        arns.aws_secret_store_arn,
+        "arn:aws:secretsmanager:us-east-1:123456789012:secret:rds-db-credentials/cluster-INVENTORY/admin-GhIjKl"
+    );
+    assert_eq!(arns.engine.as_deref(), Some("aurora-mysql"));
+}
+
+#[tokio::test]
+async fn lists_every_cluster_when_none_is_named() {
+    let replay = Replay::new(Vec::new());
+    let error = get_arns_with(&replay.client(), &None, &None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"Multiple clusters found, please specify one of ["orders", "inventory", "reporting"]"#
+    );
+}
+
+#[tokio::test]
+async fn reports_an_unknown_cluster() {
+    let replay = Replay::new(Vec::new());
+    let error = get_arns_with(&replay.client(), &some("billing"), &None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"No cluster matched "billing", available ids are ["orders", "inventory", "reporting"]"#
+    );
+}
+
+#[tokio::test]
+async fn lists_the_users_of_a_cluster() {
+    let replay = Replay::new(Vec::new());
+    let error = get_arns_with(&replay.client(), &some("reporting"), &None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"Multiple cluster users found, please specify one of ["admin", "reader"]"#
+    );
+
+    let replay = Replay::new(Vec::new());
+    let error = get_arns_with(&replay.client(), &some("orders"), &some("writer"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"No cluster user matched "writer", available users are ["admin"]"#
+    );
+}
+
+#[tokio::test]
+async fn finds_the_cluster_of_an_instance() {
+    let http_client = StaticReplayClient::new(vec![event(
+        RDS,
+        "Action=DescribeDBInstances&Version=2014-10-31&DBInstanceIdentifier=orders-instance-1",
+        "describe_db_instances.xml",
+    )]);
+    let client = AwsClient::new(&sdk_config(&http_client));
+    let cluster_id = instance_cluster_id_with(&client, "orders-instance-1")
+        .await
+        .unwrap();
+    assert_eq!(cluster_id, "orders");
+    http_client.relaxed_requests_match();
+}
+
+#[tokio::test]
+async fn formats_statement_results() {
+    let http_client = StaticReplayClient::new(vec![event(
+        RDS_DATA,
+        r#"{
+            "resourceArn": "arn:aws:rds:us-east-1:123456789012:cluster:orders",
+            "secretArn": "arn:aws:secretsmanager:us-east-1:123456789012:secret:orders",
+            "sql": "select id, name, active from products",
+            "includeResultMetadata": true,
+            "resultSetOptions": {"decimalReturnType": "STRING"}
+        }"#,
+        "execute_statement.json",
+    )]);
+    let request = Request {
+        target: Target {
+            resource_arn: "arn:aws:rds:us-east-1:123456789012:cluster:orders".to_owned(),
+            secret_arn: "arn:aws:secretsmanager:us-east-1:123456789012:secret:orders".to_owned(),
+            database: None,
+            schema: None,
+            engine: None,
+        },
+        params: Vec::new(),
+        sql: "select id, name, active from products".to_owned(),
+    };
+    let output = AwsClient::new(&sdk_config(&http_client))
+        .execute_statement(request, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        format_header(&output).collect::<Vec<_>>(),
+        ["id", "name", "active"]
+    );
+    let rows: Vec<Vec<String>> = format_rows(&output).map(Iterator::collect).collect();
+    assert_eq!(rows, [["1", "widget", "true"], ["2", "NULL", "false"]]);
+    http_client.relaxed_requests_match();
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Product {
+    id: i64,
+    name: Option<String>,
+    active: bool,
+}
+
+#[tokio::test]
+async fn query_executor_returns_typed_rows() {
+    let replay = Replay::new(vec![event(
+        RDS_DATA,
+        r#"{
+            "resourceArn": "arn:aws:rds:us-east-1:123456789012:cluster:orders",
+            "secretArn": "arn:aws:secretsmanager:us-east-1:123456789012:secret:rds-db-credentials/cluster-ORDERS/admin-AbCdEf",
+            "database": "shop",
+            "sql": "select id, name, active from products",
+            "includeResultMetadata": true,
+            "resultSetOptions": {"decimalReturnType": "STRING"}
+        }"#,
+        "execute_statement.json",
+    )]);
+    let mut config = Config::new(SdkConfig::builder().build());
+    config.cluster_id = some("orders");
+    config.database = some("shop");
+    let executor = QueryExecutor::connect_with(Arc::new(replay.client()), config)
+        .await
+        .unwrap();
+    let result_set = executor
+        .execute("select id, name, active from products", &[])
+        .await
+        .unwrap();
+    assert_eq!(result_set.columns, ["id", "name", "active"]);
+    assert_eq!(
+        result_set.deserialize::<Product>().unwrap(),
+        [
+            Product {
+                id: 1,
+                name: some("widget"),
+                active: true,
+            },
+            Product {
+                id: 2,
+                name: None,
+                active: false,
+            },
+        ]
+    );
+    replay.assert_requests_match();
+}
+
+#[tokio::test]
+async fn explains_how_to_enable_the_data_api() {
+    let http_client = StaticReplayClient::new(vec![error_event(
+        RDS_DATA,
+        "BadRequestException",
+        "execute_statement_not_enabled.json",
+    )]);
+    let request = Request {
+        target: Target {
+            resource_arn: "arn:aws:rds:us-east-1:123456789012:cluster:orders".to_owned(),
+            secret_arn: "arn:aws:secretsmanager:us-east-1:123456789012:secret:orders".to_owned(),
+            database: None,
+            schema: None,
+            engine: None,
+        },
+        params: Vec::new(),
+        sql: "select 1".to_owned(),
+    };
+    let error = AwsClient::new(&sdk_config(&http_client))
+        .execute_statement(request, None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<Error>(),
+        Some(&Error::DataApiNotEnabled {
+            resource_arn: "arn:aws:rds:us-east-1:123456789012:cluster:orders".to_owned()
+        })
+    );
+    assert!(error.to_string().contains(
+        "aws rds modify-db-cluster --db-cluster-identifier orders --enable-http-endpoint"
+    ));
+}
+
+#[tokio::test]
+async fn retries_a_dropped_connection() {
+    let http_client = StaticReplayClient::new(vec![
+        error_event(
+            RDS_DATA,
+            "BadRequestException",
+            "execute_statement_link_failure.json",
+        ),
+        event(
+            RDS_DATA,
+            r#"{
+                "resourceArn": "arn:aws:rds:us-east-1:123456789012:cluster:orders",
+                "secretArn": "arn:aws:secretsmanager:us-east-1:123456789012:secret:orders",
+                "sql": "select id, name, active from products",
+                "includeResultMetadata": true,
+                "resultSetOptions": {"decimalReturnType": "STRING"}
+            }"#,
+            "execute_statement.json",
+        ),
+    ]);
+    let request = Request {
+        target: Target {
+            resource_arn: "arn:aws:rds:us-east-1:123456789012:cluster:orders".to_owned(),
+            secret_arn: "arn:aws:secretsmanager:us-east-1:123456789012:secret:orders".to_owned(),
+            database: None,
+            schema: None,
+            engine: None,
+        },
+        params: Vec::new(),
+        sql: "select id, name, active from products".to_owned(),
+    };
+    let client = AwsClient::new(&sdk_config(&http_client)).with_transient_retries(1);
+    let output = client
+        .execute_statement(request.clone(), None)
+        .await
+        .unwrap();
+    assert_eq!(output.records.map(|records| records.len()), Some(2));
+
+    let http_client = StaticReplayClient::new(vec![error_event(
+        RDS_DATA,
+        "BadRequestException",
+        "execute_statement_link_failure.json",
+    )]);
+    let client = AwsClient::new(&sdk_config(&http_client)).with_transient_retries(0);
+    let error = client.execute_statement(request, None).await.unwrap_err();
+    assert!(format!("{:?}", error).contains("Communications link failure"));
+}
+
+#[tokio::test]
+async fn does_not_retry_a_write() {
+    let http_client = StaticReplayClient::new(vec![error_event(
+        RDS_DATA,
+        "BadRequestException",
+        "execute_statement_link_failure.json",
+    )]);
+    let request = Request {
+        target: Target {
+            resource_arn: "arn:aws:rds:us-east-1:123456789012:cluster:orders".to_owned(),
+            secret_arn: "arn:aws:secretsmanager:us-east-1:123456789012:secret:orders".to_owned(),
+            database: None,
+            schema: None,
+            engine: None,
+        },
+        params: Vec::new(),
+        sql: "insert into products (name) values ('lamp')".to_owned(),
+    };
+    let client = AwsClient::new(&sdk_config(&http_client)).with_transient_retries(3);
+    let error = client.execute_statement(request, None).await.unwrap_err();
+    assert!(format!("{:?}", error).contains("Communications link failure"));
+    assert_eq!(http_client.actual_requests().count(), 1);
+}